@@ -2,17 +2,22 @@
 #![no_main]
 #![feature(inline_const, const_mut_refs)]
 
+use core::array;
 use psx::constants::*;
 use psx::gpu::primitives::*;
-use psx::gpu::{link_list, Color, Packet, Vertex};
+use psx::gpu::{link_list, ClipRect, Color, Packet, PrimitiveBuffer, Vertex};
+use psx::gte::{Light, Lighting, GTE};
 use psx::hw::gpu::GP0Command;
 use psx::include_obj;
-use psx::math::{f16, rotate_x, rotate_y, rotate_z, Rad};
+use psx::math::{Mat3, Rad};
 use psx::sys::rng::Rng;
 use psx::{dma, Framebuffer};
 
 psx::sys_heap!(4 kb);
 
+// The area of the screen polygons are drawn in
+const SCREEN: ClipRect = ClipRect::new(Vertex(0, 0), Vertex(319, 239));
+
 #[derive(Debug, Clone, Copy)]
 enum Face {
     Tri([u16; 3]),
@@ -59,6 +64,13 @@ fn main() {
     let mut gpu_dma = dma::GPU::new();
     let rng = Rng::new(0xdeadbeef);
 
+    // Set up the GTE to project vertices 64 units in front of the camera onto
+    // the center of the screen
+    let mut gte = GTE::new();
+    gte.set_translation([0, 0, 64 << 8])
+        .set_projection_distance(256)
+        .set_screen_offset(Vertex(160, 120));
+
     // To display this correctly, we need to sort the faces before drawing just like
     // in the previous examples. This is a problem for this particular .obj since it
     // contains both tris and quads which are laid out in two separate arrays
@@ -75,17 +87,35 @@ fn main() {
     // For each face, create a Face::Quad if it's a quad or a Face::Tri if it's a
     // tri. This array is used for sorting, and not sent to the GPU DMA so the
     // memory layout doesn't matter which allows us to use an enum. Also assign
-    // a random color to each vertex
+    // a random color and compute the normal of each face
+    let normal = |[a, b, c]: [u16; 3]| {
+        let [a, b, c] = [a, b, c].map(|i| monkey.vertices[i as usize].map(|x| x.0 as i32));
+        normalize(cross(sub(b, a), sub(c, a)))
+    };
     let mut faces = monkey.map_faces(
-        |q| (Face::Quad(q), rng.rand_color()),
-        |t| (Face::Tri(t), rng.rand_color()),
+        |q| (Face::Quad(q), rng.rand_color(), normal([q[0], q[1], q[2]])),
+        |t| (Face::Tri(t), rng.rand_color(), normal(t)),
     );
 
+    // Light the faces facing the camera with a white light and the rest with a
+    // dim ambient light
+    let light = Mat3([[0, 0, -0x1000], [0; 3], [0; 3]]);
+    let mut lighting = Lighting::new();
+    lighting
+        .set_light(
+            0,
+            Some(Light {
+                direction: light.0[0],
+                color: WHITE,
+            }),
+        )
+        .set_ambient(Color::new(0x20, 0x20, 0x20));
+    gte.set_lighting(&lighting);
+
     // Define functions to initialize the polygons
-    let init_quad_poly = |q: [u16; 4]| -> Packet<PolyF> {
-        // Create a PolyF4 and initialize it like normal
-        let mut quad = PolyF4::new();
-        quad.set_vertices(q.map(|i| monkey.vertices[i as usize]).map(project_point));
+    let init_quad_poly = |_: [u16; 4]| -> Packet<PolyF> {
+        // Create a PolyF4. Its vertices are set once the GTE projects them
+        let quad = PolyF4::new();
         // Since the array must hold both quads and tris, each packet contains an
         // untagged union with the two types of polygons as its variants. The packet
         // size is initialized to size of PolyF which is the same as size of PolyF4
@@ -93,10 +123,8 @@ fn main() {
         Packet::new(PolyF { quad })
     };
 
-    let init_tri_poly = |t: [u16; 3]| -> Packet<PolyF> {
-        // Create a PolyF3 and initialize it like normal
-        let mut tri = PolyF3::new();
-        tri.set_vertices(t.map(|i| monkey.vertices[i as usize]).map(project_point));
+    let init_tri_poly = |_: [u16; 3]| -> Packet<PolyF> {
+        let tri = PolyF3::new();
         let mut p = Packet::new(PolyF { tri });
         // Since the untagged union was initialized to a tri, we have to shorten the
         // size of the packet.
@@ -109,9 +137,10 @@ fn main() {
     let mut polys_a = monkey.map_faces(init_quad_poly, init_tri_poly);
     let mut polys_b = monkey.map_faces(init_quad_poly, init_tri_poly);
 
-    // Link the packets in each set of polygons
-    link_list(&mut polys_a);
-    link_list(&mut polys_b);
+    // Polygons which are too large for the GPU are split into pieces. The first
+    // piece replaces the polygon and the rest are allocated from these buffers
+    let mut pieces_a = PrimitiveBuffer::<1024>::new();
+    let mut pieces_b = PrimitiveBuffer::<1024>::new();
 
     let mut swapped = false;
 
@@ -127,56 +156,81 @@ fn main() {
         theta += vel;
 
         swapped = !swapped;
-        let (draw_poly, disp_poly) = if swapped {
-            (&mut polys_a, &mut polys_b)
+        let (draw_poly, disp_poly, pieces) = if swapped {
+            (&mut polys_a, &mut polys_b, &mut pieces_a)
         } else {
-            (&mut polys_b, &mut polys_a)
+            (&mut polys_b, &mut polys_a, &mut pieces_b)
         };
         gpu_dma.send_list_and(disp_poly, || {
-            // Rotate all vertices and keep track of the colors
-            let rotated_vertices = monkey
-                .vertices
-                .map(|v| rotate_z(rotate_x(rotate_y(v, theta), phi), psi));
-
-            // Sort the monkey faces by the average z of their rotated vertices. Note that
-            // the average z computation is different for quads and tris.
-            faces.sort_by_key(|(face, _)| {
+            // Relink the packets in the draw list to drop the previous frame's
+            // pieces
+            link_list(draw_poly);
+            pieces.reset();
+
+            // Rotate and project all vertices with the GTE
+            let rotation = Mat3::rotation_z(psi) * Mat3::rotation_x(phi) * Mat3::rotation_y(theta);
+            gte.set_rotation(rotation.into());
+            // Rotate the light into the model's space to light the untransformed
+            // normals
+            gte.set_light_matrix((light * rotation).into());
+            let projected = monkey.vertices.map(|v| gte.rtps(v.map(|x| x.0)));
+
+            // Sort the monkey faces by the average z of their projected vertices. Note
+            // that the average z computation is different for quads and tris.
+            faces.sort_by_key(|(face, ..)| {
                 let mut res = 0;
                 match face {
                     Face::Quad(q) => {
-                        let points = q.map(|i| rotated_vertices[i as usize]);
-                        for [_, _, z] in points {
-                            res += z.0 / 4;
+                        for i in q {
+                            res += projected[*i as usize].z as i32 / 4;
                         }
                     },
                     Face::Tri(t) => {
-                        let points = t.map(|i| rotated_vertices[i as usize]);
-                        for [_, _, z] in points {
-                            res += z.0 / 3;
+                        for i in t {
+                            res += projected[*i as usize].z as i32 / 3;
                         }
                     },
                 }
                 -res
             });
             for n in 0..draw_poly.len() {
-                match faces[n].0 {
+                let (face, color, normal) = faces[n];
+                let color = gte.nccs(normal, color);
+                let packet = &mut draw_poly[n];
+                match face {
                     Face::Quad(q) => {
-                        // Project the vertices onto the screen
-                        let projected_vertices =
-                            q.map(|i| project_point(rotated_vertices[i as usize]));
-                        // Update the vertices and colors of each polygon in the draw list
-                        draw_poly[n]
-                            .as_quad()
-                            .set_vertices(projected_vertices)
-                            .set_color(faces[n].1);
+                        let mut quad = PolyF4::new();
+                        quad.set_vertices(q.map(|i| projected[i as usize].xy))
+                            .set_color(color);
+                        // Hidden polygons produce no pieces so they're collapsed to a
+                        // single point
+                        packet.as_quad().set_vertices([Vertex(0, 0); 4]);
+                        // Vertices projected far off screen can make polygons too large for
+                        // the GPU to draw so split those into smaller pieces
+                        let mut first = true;
+                        quad.split(&SCREEN, |piece| {
+                            if first {
+                                *packet.as_quad() = *piece;
+                                first = false;
+                            } else if let Some(extra) = pieces.alloc(*piece) {
+                                packet.insert_packet(extra);
+                            }
+                        });
                     },
                     Face::Tri(t) => {
-                        let projected_vertices =
-                            t.map(|i| project_point(rotated_vertices[i as usize]));
-                        draw_poly[n]
-                            .as_tri()
-                            .set_vertices(projected_vertices)
-                            .set_color(faces[n].1);
+                        let mut tri = PolyF3::new();
+                        tri.set_vertices(t.map(|i| projected[i as usize].xy))
+                            .set_color(color);
+                        packet.as_tri().set_vertices([Vertex(0, 0); 3]);
+                        let mut first = true;
+                        tri.split(&SCREEN, |piece| {
+                            if first {
+                                *packet.as_tri() = *piece;
+                                first = false;
+                            } else if let Some(extra) = pieces.alloc(*piece) {
+                                packet.insert_packet(extra);
+                            }
+                        });
                     },
                 }
             }
@@ -187,8 +241,31 @@ fn main() {
     }
 }
 
-fn project_point([x, y, z]: [f16; 3]) -> Vertex {
-    let xp = x / (z + 64);
-    let yp = y / (z + 64);
-    Vertex(xp.0, yp.0) + Vertex(160, 120)
+fn sub(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    array::from_fn(|i| a[i] - b[i])
+}
+
+fn cross([ax, ay, az]: [i32; 3], [bx, by, bz]: [i32; 3]) -> [i64; 3] {
+    let (ax, ay, az) = (ax as i64, ay as i64, az as i64);
+    let (bx, by, bz) = (bx as i64, by as i64, bz as i64);
+    [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx]
+}
+
+// Scales a vector to a 1.3.12 fixed-point unit vector
+fn normalize(v: [i64; 3]) -> [i16; 3] {
+    let len = isqrt(v.iter().map(|x| (x * x) as u64).sum()) as i64;
+    if len == 0 {
+        return [0; 3]
+    }
+    v.map(|x| ((x << 12) / len) as i16)
+}
+
+fn isqrt(n: u64) -> u64 {
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
 }
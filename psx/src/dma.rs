@@ -153,8 +153,10 @@ impl<A: MemoryAddress, B: BlockControl, C: ChannelControl> Channel<A, B, C> {
         self.madr.set_address(addr).store();
         // If the block is too long error out
         self.bcr.set_block(block.len())?.store();
-        // Start the DMA transfer
+        // Start the DMA transfer. The direction is set explicitly since a
+        // previous `recv` may have left the channel writing to memory.
         self.control
+            .set_direction(Direction::FromMemory)
             .set_mode(TransferMode::Immediate)
             .start()
             .store();
@@ -200,7 +202,11 @@ impl<A: MemoryAddress, B: BlockControl, C: ChannelControl> Channel<A, B, C> {
         };
         // This will never fail
         self.bcr.set_block(block_len)?.store();
-        self.control.start().store();
+        // Reset the direction in case a `recv_blocks` changed it
+        self.control
+            .set_direction(Direction::FromMemory)
+            .start()
+            .store();
         // This acts like a compiler fence
         unsafe {
             asm!("nop");
@@ -250,4 +256,121 @@ impl<A: MemoryAddress, B: BlockControl, C: ChannelControl> Channel<A, B, C> {
     pub fn send_list<L: LinkedList + ?Sized>(&mut self, list: &L) {
         self.send_list_and(list, || ())
     }
+
+    /// Receives a single block from a DMA channel into a buffer and call `f`
+    /// while the transfer completes.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value or an error if the buffer is too large.
+    pub fn recv_and<F: FnOnce() -> R, R>(&mut self, block: &mut [u32], f: F) -> Result<R> {
+        // If the block is empty, just call `f` and return
+        let addr = match self.block_address(block) {
+            Some(addr) => addr,
+            None => return Ok(f()),
+        };
+        self.madr.set_address(addr).store();
+        // If the block is too long error out
+        self.bcr.set_block(block.len())?.store();
+        // Start the DMA transfer
+        self.control
+            .set_direction(Direction::ToMemory)
+            .set_mode(TransferMode::Immediate)
+            .start()
+            .store();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        let res = f();
+        self.control.wait();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        Ok(res)
+    }
+
+    /// Receives a single block from a DMA channel into a buffer.
+    ///
+    /// Returns an error if the buffer is too large.
+    pub fn recv(&mut self, block: &mut [u32]) -> Result<()> {
+        self.recv_and(block, || ())
+    }
+
+    /// Receives `size` evenly-sized blocks from a DMA channel into a buffer
+    /// and call `f` while the transfer completes. Each block is transferred
+    /// when the device requests it.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value or an error if the buffer can't be split
+    /// into `size` blocks.
+    pub fn recv_blocks_and<F: FnOnce() -> R, R>(
+        &mut self, block: &mut [u32], size: usize, f: F,
+    ) -> Result<R> {
+        // If the block is empty, just call `f` and return
+        let addr = match self.block_address(block) {
+            Some(addr) => addr,
+            None => return Ok(f()),
+        };
+        self.madr.set_address(addr).store();
+        // If the block can't be partitioned into evenly-sized sub-blocks error out
+        if block.len() % size != 0 {
+            return Err(Error::BadBlockPartition)
+        }
+        // If the sub-blocks are too big error out
+        let words = (block.len() / size)
+            .try_into()
+            .map_err(|_| Error::OversizedBlock)?;
+        // If there are too many sub-blocks error out
+        let block_len = BlockMode::Multi {
+            words,
+            blocks: size.try_into().map_err(|_| Error::OversizedBlock)?,
+        };
+        // This will never fail
+        self.bcr.set_block(block_len)?.store();
+        self.control
+            .set_direction(Direction::ToMemory)
+            .set_mode(TransferMode::Request)
+            .start()
+            .store();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        let res = f();
+        self.control.wait();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        Ok(res)
+    }
+
+    /// Receives `size` evenly-sized blocks from a DMA channel into a buffer.
+    ///
+    /// Returns an error if the buffer can't be split into `size` blocks.
+    pub fn recv_blocks(&mut self, block: &mut [u32], size: usize) -> Result<()> {
+        self.recv_blocks_and(block, size, || ())
+    }
+}
+
+impl OTC {
+    /// Clears an ordering table in memory and call `f` while the transfer
+    /// completes.
+    ///
+    /// Each word in `table` is set to point to the previous word and the first
+    /// word is set to the end of list marker. Returns `f`'s return value or
+    /// an error if the table is too large.
+    pub fn clear_and<F: FnOnce() -> R, R>(&mut self, table: &mut [u32], f: F) -> Result<R> {
+        // The OTC channel can only step backwards through memory
+        self.control.set_step(Step::Backward);
+        self.recv_and(table, f)
+    }
+
+    /// Clears an ordering table in memory.
+    ///
+    /// Returns an error if the table is too large.
+    pub fn clear(&mut self, table: &mut [u32]) -> Result<()> {
+        self.clear_and(table, || ())
+    }
 }
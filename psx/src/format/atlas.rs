@@ -0,0 +1,162 @@
+//! Sprite atlas descriptions
+//!
+//! An atlas is a text file naming the [`Frame`]s in a sprite sheet. Each line
+//! has the form
+//!
+//! ```text
+//! name x y width height [pivot_x pivot_y]
+//! ```
+//!
+//! where `x` and `y` are the frame's texcoords relative to the sheet's bitmap.
+//! Blank lines and lines starting with `#` are ignored.
+
+use super::parse::{at_line_end, end_line, parse_i16, parse_u8, parse_word, skip_line};
+use crate::gpu::{Frame, TexCoord, Vertex};
+
+/// Includes an atlas description as an
+/// [`Atlas`][`crate::format::atlas::Atlas`].
+///
+/// The file is parsed at compile-time so a malformed atlas fails the build.
+/// This is meant to be used alongside [`include_tim!`][`crate::include_tim`]
+/// with the frames drawn using the `LoadedTIM` for the sprite sheet.
+#[macro_export]
+macro_rules! include_atlas {
+    ($file:literal) => {{
+        use $crate::format::atlas::{count_frames, Atlas};
+
+        const SRC: &str = include_str!($file);
+        const FRAMES: usize = count_frames(SRC.as_bytes());
+        const ATLAS: Atlas<FRAMES> = Atlas::parse(SRC);
+        ATLAS
+    }};
+}
+
+/// Count the number of frames in an atlas.
+#[doc(hidden)]
+pub const fn count_frames(data: &[u8]) -> usize {
+    let mut i = 0;
+    let mut count = 0;
+    while i < data.len() {
+        if !skip_line(data, &mut i) {
+            count += 1;
+            while i < data.len() && data[i] != b'\n' {
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+    count
+}
+
+/// A set of named frames within a sprite sheet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atlas<const N: usize> {
+    src: &'static str,
+    // The start and end of each frame's name in `src`
+    names: [(usize, usize); N],
+    frames: [Frame; N],
+}
+
+impl<const N: usize> Atlas<N> {
+    /// Parses an atlas description containing `N` frames.
+    ///
+    /// Panics if the description is malformed or doesn't contain exactly `N`
+    /// frames. Use [`include_atlas!`][`crate::include_atlas`] to parse an atlas
+    /// at compile-time.
+    pub const fn parse(src: &'static str) -> Self {
+        let data = src.as_bytes();
+        let mut names = [(0, 0); N];
+        let mut frames = [Frame::new(TexCoord { x: 0, y: 0 }, Vertex(0, 0)); N];
+        let mut n = 0;
+        let mut i = 0;
+        while i < data.len() {
+            if skip_line(data, &mut i) {
+                continue
+            }
+            if n == N {
+                panic!("Atlas has more frames than expected");
+            }
+            names[n] = parse_word(data, &mut i);
+            let x = parse_u8(data, &mut i);
+            let y = parse_u8(data, &mut i);
+            let w = parse_i16(data, &mut i);
+            let h = parse_i16(data, &mut i);
+            if w <= 0 || h <= 0 {
+                panic!("Atlas frame has an empty size");
+            }
+            let mut frame = Frame::new(TexCoord { x, y }, Vertex(w, h));
+            if !at_line_end(data, &mut i) {
+                let px = parse_i16(data, &mut i);
+                let py = parse_i16(data, &mut i);
+                frame = frame.with_pivot(Vertex(px, py));
+            }
+            end_line(data, &mut i);
+            frames[n] = frame;
+            n += 1;
+        }
+        if n != N {
+            panic!("Atlas has fewer frames than expected");
+        }
+        Atlas { src, names, frames }
+    }
+
+    /// Gets the frame with the given name.
+    pub fn get(&self, name: &str) -> Option<Frame> {
+        self.iter()
+            .find(|&(frame_name, _)| frame_name == name)
+            .map(|(_, frame)| frame)
+    }
+
+    /// Returns an iterator over the atlas's frames and their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Frame)> + '_ {
+        let src = self.src;
+        self.names
+            .iter()
+            .zip(self.frames)
+            .map(move |(&(start, end), frame)| (&src[start..end], frame))
+    }
+
+    /// Gets the frames in the order they appear in the atlas.
+    pub fn frames(&self) -> &[Frame; N] {
+        &self.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_frames, Atlas};
+    use crate::gpu::{TexCoord, Vertex};
+
+    const SRC: &str = "# player frames\n\
+                       idle 0 0 16 24 8 24\n\
+                       \n\
+                       run\t16 0 16 24\r\n\
+                       jump 32 8 24 16 -4 16";
+    const FRAMES: usize = count_frames(SRC.as_bytes());
+    const ATLAS: Atlas<FRAMES> = Atlas::parse(SRC);
+
+    #[test_case]
+    fn parse() {
+        assert!(ATLAS.iter().count() == 3);
+        let idle = ATLAS.get("idle").unwrap();
+        assert!(idle.offset == TexCoord { x: 0, y: 0 });
+        assert!(idle.size == Vertex(16, 24));
+        assert!(idle.pivot == Vertex(8, 24));
+        let run = ATLAS.get("run").unwrap();
+        assert!(run.offset == TexCoord { x: 16, y: 0 });
+        assert!(run.pivot == Vertex(0, 0));
+        let jump = ATLAS.get("jump").unwrap();
+        assert!(jump.offset == TexCoord { x: 32, y: 8 });
+        assert!(jump.pivot == Vertex(-4, 16));
+        assert!(ATLAS.get("fall").is_none());
+    }
+
+    #[test_case]
+    fn names() {
+        let mut names = ATLAS.iter().map(|(name, _)| name);
+        assert!(names.next() == Some("idle"));
+        assert!(names.next() == Some("run"));
+        assert!(names.next() == Some("jump"));
+        assert!(names.next().is_none());
+    }
+}
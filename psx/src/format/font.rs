@@ -0,0 +1,221 @@
+//! Bitmap font metrics
+//!
+//! A metrics table is a text file describing the glyphs in a font's TIM. Each
+//! line is one of
+//!
+//! ```text
+//! height line_height
+//! glyph code x y width height advance [bearing_x bearing_y]
+//! kern left right adjust
+//! ```
+//!
+//! where `x` and `y` are the glyph's texcoords within the TIM's texture page.
+//! Character codes are Latin-1 and may be written in decimal, in hex with a
+//! `0x` prefix or as a quoted ASCII character like `'A'`. The line height
+//! defaults to the tallest glyph if it's not given. Blank lines and lines
+//! starting with `#` are ignored.
+
+use super::parse::{at_line_end, end_line, parse_i8, parse_u8, parse_word, skip_line, skip_spaces,
+                   word_eq};
+use crate::gpu::{Font, Glyph, Kerning, TexCoord};
+
+/// Includes a font metrics table as a [`Font`][`crate::gpu::Font`].
+///
+/// The table is parsed at compile-time so a malformed table fails the build.
+/// When given a TIM file and a metrics table this includes both as a
+/// [`TIM`][`crate::format::tim::TIM`] and `Font` pair.
+#[macro_export]
+macro_rules! include_font {
+    ($file:literal) => {{
+        use $crate::format::font::{count_kerning, parse_font, parse_kerning};
+        use $crate::gpu::{Font, Kerning};
+
+        const SRC: &[u8] = include_bytes!($file);
+        const KERNING: [Kerning; count_kerning(SRC)] = parse_kerning(SRC);
+        const FONT: Font = parse_font(SRC, &KERNING);
+        FONT
+    }};
+    ($tim:literal, $metrics:literal) => {
+        ($crate::include_tim!($tim), $crate::include_font!($metrics))
+    };
+}
+
+/// Parse a Latin-1 character code from a byte slice starting at `idx`.
+const fn parse_code(data: &[u8], idx: &mut usize) -> u8 {
+    skip_spaces(data, idx);
+    let i = *idx;
+    if i + 2 < data.len() && data[i] == b'\'' && data[i + 2] == b'\'' {
+        if data[i + 1] >= 0x80 {
+            panic!("Quoted characters must be ASCII");
+        }
+        *idx += 3;
+        return data[i + 1]
+    }
+    if i + 1 < data.len() && data[i] == b'0' && data[i + 1] == b'x' {
+        *idx += 2;
+        let mut res: u16 = 0;
+        let mut digits = 0;
+        while *idx < data.len() {
+            let digit = match data[*idx] {
+                b @ b'0'..=b'9' => b - b'0',
+                b @ b'a'..=b'f' => b - b'a' + 10,
+                b @ b'A'..=b'F' => b - b'A' + 10,
+                _ => break,
+            };
+            res = res * 16 + digit as u16;
+            digits += 1;
+            *idx += 1;
+        }
+        if digits == 0 || res > 0xFF {
+            panic!("Character codes must be between 0x00 and 0xFF");
+        }
+        return res as u8
+    }
+    parse_u8(data, idx)
+}
+
+/// Count the number of kerning pairs in a font metrics table.
+#[doc(hidden)]
+pub const fn count_kerning(data: &[u8]) -> usize {
+    let mut i = 0;
+    let mut count = 0;
+    while i < data.len() {
+        if skip_line(data, &mut i) {
+            continue
+        }
+        if word_eq(data, parse_word(data, &mut i), b"kern") {
+            count += 1;
+        }
+        while i < data.len() && data[i] != b'\n' {
+            i += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
+/// Parse the kerning pairs in a font metrics table.
+#[doc(hidden)]
+pub const fn parse_kerning<const N: usize>(data: &[u8]) -> [Kerning; N] {
+    let mut kerning = [Kerning {
+        left: 0,
+        right: 0,
+        adjust: 0,
+    }; N];
+    let mut n = 0;
+    let mut i = 0;
+    while i < data.len() {
+        if skip_line(data, &mut i) {
+            continue
+        }
+        if word_eq(data, parse_word(data, &mut i), b"kern") {
+            let left = parse_code(data, &mut i);
+            let right = parse_code(data, &mut i);
+            let adjust = parse_i8(data, &mut i);
+            kerning[n] = Kerning {
+                left,
+                right,
+                adjust,
+            };
+            n += 1;
+        }
+        while i < data.len() && data[i] != b'\n' {
+            i += 1;
+        }
+        i += 1;
+    }
+    kerning
+}
+
+/// Parse the glyphs and line height in a font metrics table.
+#[doc(hidden)]
+pub const fn parse_font(data: &[u8], kerning: &'static [Kerning]) -> Font {
+    let mut glyphs = [Glyph::MISSING; 256];
+    let mut height = None;
+    let mut tallest = 0;
+    let mut i = 0;
+    while i < data.len() {
+        if skip_line(data, &mut i) {
+            continue
+        }
+        let word = parse_word(data, &mut i);
+        if word_eq(data, word, b"height") {
+            height = Some(parse_u8(data, &mut i));
+            end_line(data, &mut i);
+        } else if word_eq(data, word, b"glyph") {
+            let code = parse_code(data, &mut i);
+            let x = parse_u8(data, &mut i);
+            let y = parse_u8(data, &mut i);
+            let w = parse_u8(data, &mut i);
+            let h = parse_u8(data, &mut i);
+            let advance = parse_u8(data, &mut i);
+            let mut glyph = Glyph::new(TexCoord { x, y }, w, h, advance);
+            if !at_line_end(data, &mut i) {
+                glyph.bearing = (parse_i8(data, &mut i), parse_i8(data, &mut i));
+            }
+            end_line(data, &mut i);
+            let bottom = glyph.bearing.1 as i16 + h as i16;
+            if bottom > tallest {
+                tallest = bottom;
+            }
+            glyphs[code as usize] = glyph;
+        } else if word_eq(data, word, b"kern") {
+            // Kerning pairs are parsed separately by `parse_kerning`
+            while i < data.len() && data[i] != b'\n' {
+                i += 1;
+            }
+            i += 1;
+        } else {
+            panic!("Font metrics line must start with `height`, `glyph` or `kern`");
+        }
+    }
+    let height = match height {
+        Some(height) => height,
+        None => tallest as u8,
+    };
+    Font::new(glyphs, kerning, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_kerning, parse_font, parse_kerning};
+    use crate::gpu::{Font, Kerning, TexCoord};
+
+    const SRC: &[u8] = b"# test font\n\
+                         glyph 'A' 0 0 7 9 8\n\
+                         glyph 'V' 8 0 7 9 8\r\n\
+                         glyph 0xE9 16 0 6 11 7 0 -2\n\
+                         glyph 32 0 0 0 0 4\n\
+                         \n\
+                         kern 'A' 'V' -2\n\
+                         kern 86 65 -1";
+    const KERNING: [Kerning; count_kerning(SRC)] = parse_kerning(SRC);
+    const FONT: Font = parse_font(SRC, &KERNING);
+
+    #[test_case]
+    fn glyphs() {
+        let e = FONT.glyph(0xE9).unwrap();
+        assert!(e.offset == TexCoord { x: 16, y: 0 });
+        assert!(e.bearing == (0, -2));
+        assert!(e.advance == 7);
+        assert!(FONT.glyph(b' ').unwrap().width == 0);
+        assert!(FONT.glyph(b'B').is_none());
+        // The tallest glyph's bottom edge
+        assert!(FONT.height() == 9);
+    }
+
+    #[test_case]
+    fn kerning() {
+        assert!(KERNING.len() == 2);
+        assert!(FONT.kerning(b'A', b'V') == -2);
+        assert!(FONT.kerning(b'V', b'A') == -1);
+        assert!(FONT.text_width(b"AV A") == 8 + 8 - 2 + 4 + 8);
+    }
+
+    #[test_case]
+    fn line_height() {
+        const SRC: &[u8] = b"height 12\nglyph 'x' 0 0 5 5 6 0 4\n";
+        const FONT: Font = parse_font(SRC, &[]);
+        assert!(FONT.height() == 12);
+    }
+}
@@ -1,3 +1,7 @@
 //! Support for parsing various file formats
+pub mod atlas;
+pub mod font;
 pub mod obj;
+mod parse;
+pub mod png;
 pub mod tim;
@@ -0,0 +1,115 @@
+//! Helpers for parsing line-based text formats at compile-time
+//!
+//! Lines are made of whitespace separated words. Blank lines and lines
+//! starting with `#` contain no data.
+
+pub(crate) const fn is_space(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\r'
+}
+
+pub(crate) const fn skip_spaces(data: &[u8], idx: &mut usize) {
+    while *idx < data.len() && is_space(data[*idx]) {
+        *idx += 1;
+    }
+}
+
+/// Checks if the line starting at `idx` has no data, moving `idx` past it if
+/// so.
+pub(crate) const fn skip_line(data: &[u8], idx: &mut usize) -> bool {
+    let mut i = *idx;
+    skip_spaces(data, &mut i);
+    if i < data.len() && data[i] != b'\n' && data[i] != b'#' {
+        return false
+    }
+    while i < data.len() && data[i] != b'\n' {
+        i += 1;
+    }
+    *idx = i + 1;
+    true
+}
+
+/// Checks if the rest of the line starting at `idx` is empty.
+pub(crate) const fn at_line_end(data: &[u8], idx: &mut usize) -> bool {
+    skip_spaces(data, idx);
+    *idx >= data.len() || data[*idx] == b'\n'
+}
+
+/// Moves `idx` past the end of the line, panicking if the line has any more
+/// data.
+pub(crate) const fn end_line(data: &[u8], idx: &mut usize) {
+    if !at_line_end(data, idx) {
+        panic!("Line has trailing characters");
+    }
+    *idx += 1;
+}
+
+/// Parses a word starting at `idx`, returning its start and end.
+pub(crate) const fn parse_word(data: &[u8], idx: &mut usize) -> (usize, usize) {
+    skip_spaces(data, idx);
+    let start = *idx;
+    while *idx < data.len() && !is_space(data[*idx]) && data[*idx] != b'\n' {
+        *idx += 1;
+    }
+    (start, *idx)
+}
+
+/// Checks if the word in `data[start..end]` is `word`.
+pub(crate) const fn word_eq(data: &[u8], (start, end): (usize, usize), word: &[u8]) -> bool {
+    if end - start != word.len() {
+        return false
+    }
+    let mut i = 0;
+    while i < word.len() {
+        if data[start + i] != word[i] {
+            return false
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Parse an `i16` from a byte slice starting at `idx`.
+pub(crate) const fn parse_i16(data: &[u8], idx: &mut usize) -> i16 {
+    skip_spaces(data, idx);
+    let neg = *idx < data.len() && data[*idx] == b'-';
+    if neg {
+        *idx += 1;
+    }
+    let start = *idx;
+    let mut res: i16 = 0;
+    while *idx < data.len() && data[*idx] >= b'0' && data[*idx] <= b'9' {
+        res = match res.checked_mul(10) {
+            Some(res) => res,
+            None => panic!("Value is out of range"),
+        };
+        res += (data[*idx] - b'0') as i16;
+        *idx += 1;
+    }
+    if *idx == start {
+        panic!("Line is missing a value");
+    }
+    if neg {
+        -res
+    } else {
+        res
+    }
+}
+
+/// Parse a `u8` from a byte slice starting at `idx`.
+pub(crate) const fn parse_u8(data: &[u8], idx: &mut usize) -> u8 {
+    let res = parse_i16(data, idx);
+    // Negative values wrap to large u16s
+    if res as u16 > 0xFF {
+        panic!("Value must be between 0 and 255");
+    }
+    res as u8
+}
+
+/// Parse an `i8` from a byte slice starting at `idx`.
+pub(crate) const fn parse_i8(data: &[u8], idx: &mut usize) -> i8 {
+    let res = parse_i16(data, idx);
+    if res < i8::MIN as i16 || res > i8::MAX as i16 {
+        panic!("Value must be between -128 and 127");
+    }
+    res as i8
+}
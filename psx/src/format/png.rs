@@ -0,0 +1,128 @@
+//! Minimal PNG encoding
+//!
+//! This writes uncompressed 8-bit RGB images, which is enough for dumping VRAM
+//! without an allocator or compression library.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// The largest block an uncompressed deflate stream can contain
+const MAX_BLOCK: usize = u16::MAX as usize;
+
+fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
+
+/// A PNG chunk being written.
+struct Chunk<'a, F: FnMut(&[u8])> {
+    crc: u32,
+    out: &'a mut F,
+}
+
+impl<'a, F: FnMut(&[u8])> Chunk<'a, F> {
+    fn new(out: &'a mut F, len: usize, ty: &[u8; 4]) -> Self {
+        out(&(len as u32).to_be_bytes());
+        let mut chunk = Chunk { crc: !0, out };
+        chunk.write(ty);
+        chunk
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.crc = crc32(self.crc, bytes);
+        (self.out)(bytes);
+    }
+
+    fn finish(self) {
+        (self.out)(&(!self.crc).to_be_bytes());
+    }
+}
+
+/// Encodes a `width` by `height` RGB image as a PNG.
+///
+/// `pixel` is called with the coordinates of each pixel in row-major order and
+/// the encoded bytes are passed to `out` as they're produced. Returns `None`
+/// without writing anything if a row is too large to encode.
+pub fn encode_rgb<P, F>(width: u32, height: u32, mut pixel: P, mut out: F) -> Option<()>
+where
+    P: FnMut(u32, u32) -> [u8; 3],
+    F: FnMut(&[u8]), {
+    // Each row is stored in its own deflate block with a leading filter byte
+    let row_len = 1 + 3 * width as usize;
+    if row_len > MAX_BLOCK {
+        return None
+    }
+    out(&SIGNATURE);
+
+    let mut ihdr = Chunk::new(&mut out, 13, b"IHDR");
+    ihdr.write(&width.to_be_bytes());
+    ihdr.write(&height.to_be_bytes());
+    // 8-bit depth, RGB, deflate, no filter and no interlacing
+    ihdr.write(&[8, 2, 0, 0, 0]);
+    ihdr.finish();
+
+    let zlib_len = 2 + height as usize * (5 + row_len) + 4;
+    let mut idat = Chunk::new(&mut out, zlib_len, b"IDAT");
+    // zlib header for deflate with a 32K window and no compression
+    idat.write(&[0x78, 0x01]);
+    let (mut a, mut b) = (1u32, 0u32);
+    let mut adler = |bytes: &[u8]| {
+        for &byte in bytes {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+    };
+    for y in 0..height {
+        let last = (y + 1 == height) as u8;
+        let len = row_len as u16;
+        idat.write(&[last]);
+        idat.write(&len.to_le_bytes());
+        idat.write(&(!len).to_le_bytes());
+        idat.write(&[0]);
+        adler(&[0]);
+        for x in 0..width {
+            let rgb = pixel(x, y);
+            idat.write(&rgb);
+            adler(&rgb);
+        }
+    }
+    idat.write(&((b << 16) | a).to_be_bytes());
+    idat.finish();
+
+    Chunk::new(&mut out, 0, b"IEND").finish();
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, encode_rgb};
+
+    #[test_case]
+    fn crc() {
+        assert!(!crc32(!0, b"IEND") == 0xAE42_6082);
+    }
+
+    #[test_case]
+    fn encode() {
+        let mut bytes = [0; 128];
+        let mut len = 0;
+        encode_rgb(
+            2,
+            2,
+            |x, y| [x as u8, y as u8, 0],
+            |b| {
+                bytes[len..len + b.len()].copy_from_slice(b);
+                len += b.len();
+            },
+        )
+        .unwrap();
+        // Signature, IHDR, IDAT and IEND
+        assert!(len == 8 + 25 + (12 + 2 + 2 * 12 + 4) + 12);
+        assert!(bytes[len - 8..len - 4] == *b"IEND");
+        assert!(bytes[len - 4..len] == [0xAE, 0x42, 0x60, 0x82]);
+    }
+}
@@ -1,15 +1,25 @@
 use crate::dma;
 use crate::format::tim::TIM;
 use crate::gpu::colors::WHITE;
-use crate::gpu::primitives::Sprt8;
-use crate::gpu::{Clut, Color, DMAMode, Depth, DispEnv, DrawEnv, Packet, TexColor, TexCoord,
-                 TexPage, Vertex, VertexError, VideoMode, GPU_BUFFER_SIZE};
+use crate::gpu::env::DrawMode;
+use crate::gpu::primitives::Sprt;
+use crate::gpu::{page_tex_coord, to_latin1, Bpp, Clut, Color, DMAMode, Depth, DispEnv, DrawEnv,
+                 Font, Glyph, OrderingTable, Packet, PrimitiveBuffer, TexColor, TexCoord, TexPage,
+                 VRAMRegion, Vertex, VertexError, VideoMode, GPU_BUFFER_SIZE};
 use crate::hw::gpu::{GP0Command, GP0, GP1};
 use crate::hw::{gpu, irq, Register};
 use crate::include_tim;
 use crate::irq::IRQ;
-use core::fmt;
-use core::mem::size_of;
+use crate::sys::vsync;
+use core::mem::{size_of, MaybeUninit};
+use core::{array, fmt};
+
+/// Gets the number of words needed to hold a rectangle of VRAM of the given
+/// size.
+fn vram_words(size: Vertex) -> usize {
+    let halfwords = size.0 as usize * size.1 as usize;
+    (halfwords + 1) / 2
+}
 
 fn draw_sync() {
     let mut gpu_stat = gpu::Status::new();
@@ -18,11 +28,14 @@ fn draw_sync() {
     }
 }
 
-/// A double-buffered framebuffer configuration
+/// A framebuffer configuration with `B` buffers
 ///
 /// Maintains the framebuffer's configuration and state. Also provides acess to
-/// the GPU registers `GP0`, `GP1` and `GPU_STATUS`.
-pub struct Framebuffer {
+/// the GPU registers `GP0`, `GP1` and `GPU_STATUS`. Buffers are displayed in
+/// order. A single buffer is drawn to while it's displayed. With three or more
+/// buffers [`Framebuffer::present`] lets the GPU draw ahead into free buffers
+/// while finished frames wait to be displayed.
+pub struct Framebuffer<const B: usize = 2> {
     /// The write-only GPU I/O port for GP0 commands and packets
     pub gp0: GP0,
     /// The write-only GPU I/O port for GP1 commands
@@ -33,11 +46,20 @@ pub struct Framebuffer {
     pub irq_mask: irq::Mask,
     /// The IRQ mask register
     pub irq_status: irq::Status,
-    disp_envs: [DispEnv; 2],
-    draw_envs: [Packet<DrawEnv>; 2],
-    swapped: bool,
+    disp_envs: [DispEnv; B],
+    draw_envs: [Packet<DrawEnv>; B],
+    // The buffer being displayed
+    displayed: usize,
+    // The number of finished frames waiting to be displayed
+    queued: usize,
 }
 
+/// Requests a GPU interrupt once the commands sent before it are processed.
+#[repr(C)]
+struct InterruptRequest(u32);
+
+impl GP0Command for InterruptRequest {}
+
 impl Default for Framebuffer {
     fn default() -> Self {
         // SAFETY: The framebuffer parameters are valid.
@@ -46,15 +68,115 @@ impl Default for Framebuffer {
 }
 
 impl Framebuffer {
-    /// Creates a new framebuffer.
+    /// Creates a new double-buffered framebuffer.
     ///
     /// Places one buffer at `buf0` and the other at `buf1` and uses the
     /// specified resolution and background color (or black if `bg_color` is
-    /// `None`). Also resets the GPU, enables DMA to GP0 on the GPU-side and
-    /// enables the display.
+    /// `None`). The video mode is detected with [`VideoMode::detect`]. Also
+    /// resets the GPU, enables DMA to GP0 on the GPU-side and enables the
+    /// display.
     pub fn new(
         buf0: (i16, i16), buf1: (i16, i16), res: (i16, i16), bg_color: Option<Color>,
     ) -> Result<Self, VertexError> {
+        Self::with_mode(buf0, buf1, res, VideoMode::detect(), bg_color)
+    }
+
+    /// Creates a new double-buffered framebuffer with the specified video mode.
+    ///
+    /// This is otherwise the same as [`Framebuffer::new`].
+    pub fn with_mode(
+        buf0: (i16, i16), buf1: (i16, i16), res: (i16, i16), mode: VideoMode,
+        bg_color: Option<Color>,
+    ) -> Result<Self, VertexError> {
+        Self::with_buffers([buf0, buf1], res, mode, bg_color)
+    }
+
+    /// Creates a new 24-bit true-color framebuffer with the specified video
+    /// mode.
+    ///
+    /// Each buffer takes 1.5 halfwords of VRAM per pixel horizontally. The GPU
+    /// can't draw primitives in 24-bit color so this is only useful for
+    /// displaying images uploaded with [`Framebuffer::load_image24`], such as
+    /// MDEC-decoded frames. The buffers are cleared to black on each swap.
+    pub fn true_color(
+        buf0: (i16, i16), buf1: (i16, i16), res: (i16, i16), mode: VideoMode,
+    ) -> Result<Self, VertexError> {
+        let vram_res = (Depth::Bits24.vram_width(res.0), res.1);
+        let disp_envs = [
+            DispEnv::with_mode(buf0, res, mode)?,
+            DispEnv::with_mode(buf1, res, mode)?,
+        ];
+        let draw_envs = [
+            Packet::new(DrawEnv::new(buf1, vram_res, None)?),
+            Packet::new(DrawEnv::new(buf0, vram_res, None)?),
+        ];
+        Self::init(disp_envs, draw_envs, res, mode, Depth::Bits24, false)
+    }
+
+    /// Creates a new interlaced framebuffer with a single buffer at `buf`.
+    ///
+    /// The resolution's height should be 480 for NTSC or 512 for PAL. Each
+    /// frame only draws to the lines of the field which isn't being displayed,
+    /// so the whole frame should be redrawn every vblank. Use
+    /// [`Framebuffer::odd_field`] to check which field is being displayed.
+    pub fn interlaced(
+        buf: (i16, i16), res: (i16, i16), mode: VideoMode, bg_color: Option<Color>,
+    ) -> Result<Self, VertexError> {
+        let disp_envs = [
+            DispEnv::with_mode(buf, res, mode)?,
+            DispEnv::with_mode(buf, res, mode)?,
+        ];
+        let mut draw_envs = [
+            Packet::new(DrawEnv::new(buf, res, bg_color)?),
+            Packet::new(DrawEnv::new(buf, res, bg_color)?),
+        ];
+        for env in &mut draw_envs {
+            env.contents.mask_display();
+        }
+        Self::init(disp_envs, draw_envs, res, mode, Depth::Bits15, true)
+    }
+}
+
+impl<const B: usize> Framebuffer<B> {
+    const AT_LEAST_ONE_BUFFER: () = {
+        if B == 0 {
+            panic!("Framebuffer must have at least one buffer.");
+        }
+    };
+
+    /// Creates a new framebuffer with a buffer at each offset in `bufs`.
+    ///
+    /// Buffers are displayed in the order they're given in. This is otherwise
+    /// the same as [`Framebuffer::with_mode`].
+    pub fn with_buffers(
+        bufs: [(i16, i16); B], res: (i16, i16), mode: VideoMode, bg_color: Option<Color>,
+    ) -> Result<Self, VertexError> {
+        let mut disp_envs = MaybeUninit::uninit_array();
+        let mut draw_envs = MaybeUninit::uninit_array();
+        let envs = disp_envs.iter_mut().zip(&mut draw_envs);
+        for (i, (disp_env, draw_env)) in envs.enumerate() {
+            disp_env.write(DispEnv::with_mode(bufs[i], res, mode)?);
+            // Each frame is drawn to the buffer after the one being displayed
+            let next = bufs[(i + 1) % B];
+            draw_env.write(Packet::new(DrawEnv::new(next, res, bg_color)?));
+        }
+        // SAFETY: Every element was initialized. The environments don't
+        // implement `Drop` so returning early only leaks them.
+        let (disp_envs, draw_envs) = unsafe {
+            (
+                MaybeUninit::array_assume_init(disp_envs),
+                MaybeUninit::array_assume_init(draw_envs),
+            )
+        };
+        Self::init(disp_envs, draw_envs, res, mode, Depth::Bits15, false)
+    }
+
+    #[allow(path_statements)]
+    fn init(
+        disp_envs: [DispEnv; B], draw_envs: [Packet<DrawEnv>; B], res: (i16, i16), mode: VideoMode,
+        depth: Depth, interlace: bool,
+    ) -> Result<Self, VertexError> {
+        Self::AT_LEAST_ONE_BUFFER;
         let mut fb = Framebuffer {
             // These registers are read-only
             gp0: GP0::skip_load(),
@@ -63,17 +185,15 @@ impl Framebuffer {
             // wait_vblank will reload this anyway
             irq_status: irq::Status::skip_load(),
             irq_mask: irq::Mask::new(),
-            disp_envs: [DispEnv::new(buf0, res)?, DispEnv::new(buf1, res)?],
-            draw_envs: [
-                Packet::new(DrawEnv::new(buf1, res, bg_color)?),
-                Packet::new(DrawEnv::new(buf0, res, bg_color)?),
-            ],
-            swapped: false,
+            disp_envs,
+            draw_envs,
+            displayed: 0,
+            queued: 0,
         };
         GP1::skip_load()
             .reset_gpu()
             .dma_mode(Some(DMAMode::GP0))
-            .display_mode(res, VideoMode::NTSC, Depth::Bits15, false)?
+            .display_mode(res, mode, depth, interlace)?
             .enable_display(true);
         fb.irq_mask.enable_irq(IRQ::Vblank).store();
         //fb.wait_vblank();
@@ -84,30 +204,117 @@ impl Framebuffer {
     /// Changes the framebuffer's background color.
     pub fn set_bg_color(&mut self, color: Color) {
         for packet_env in &mut self.draw_envs {
-            packet_env.contents.bg_color = color;
+            packet_env.contents.set_bg_color(color);
         }
     }
 
+    /// Checks if buffers are cleared to the background color on each swap.
+    pub fn get_clear(&self) -> bool {
+        self.draw_envs[0].contents.get_clear()
+    }
+
+    /// Enables or disables clearing buffers to the background color on each
+    /// swap.
+    ///
+    /// Clearing may be skipped when each frame covers the whole screen.
+    pub fn set_clear(&mut self, clear: bool) -> &mut Self {
+        for packet_env in &mut self.draw_envs {
+            packet_env.contents.set_clear(clear);
+        }
+        self
+    }
+
+    /// Advances to the next buffer, returning its index.
+    ///
+    /// This drops any frames queued by [`Framebuffer::present`].
+    fn next(&mut self) -> usize {
+        self.displayed = (self.displayed + 1) % B;
+        self.queued = 0;
+        self.displayed
+    }
+
     /// Swaps the framebuffers using only GPU I/O ports.
     pub fn swap(&mut self) {
-        self.swapped = !self.swapped;
-        let idx = self.swapped as usize;
+        let idx = self.next();
         self.gp1.set_display_env(&self.disp_envs[idx]);
         self.gp0.send_command(&self.draw_envs[idx].contents);
     }
 
     /// Swaps the framebuffers using GPU I/O ports and the DMA channel
     pub fn dma_swap(&mut self, gpu_dma: &mut dma::GPU) {
-        self.swapped = !self.swapped;
-        let idx = self.swapped as usize;
+        let idx = self.next();
         self.gp1.set_display_env(&self.disp_envs[idx]);
         gpu_dma.send_list(&self.draw_envs[idx]);
     }
 
-    /// Loads a `TIM` file into VRAM.
+    /// Presents the frame that was just drawn.
+    ///
+    /// The frame is queued behind an interrupt request which the GPU raises
+    /// once it finishes drawing the frame. If a buffer is free this starts
+    /// the next frame without waiting for the GPU or for vblank. Otherwise it
+    /// waits for the oldest queued frame to finish and for the next vblank,
+    /// then displays that frame to free its predecessor. With one or two
+    /// buffers every call waits, like a [`Framebuffer::draw_sync`] and
+    /// [`Framebuffer::wait_vblank`] before [`Framebuffer::dma_swap`].
+    ///
+    /// This uses the GPU's interrupt request flag so it shouldn't be
+    /// acknowledged elsewhere. Since DMA transfers block until they're
+    /// complete this should be called after the frame's ordering table was
+    /// sent.
+    pub fn present(&mut self, gpu_dma: &mut dma::GPU) {
+        self.queued += 1;
+        // Drawing to the displayed buffer is only allowed with a single buffer
+        if self.queued + 1 >= B {
+            self.wait_frame();
+            self.wait_vblank();
+            self.displayed = (self.displayed + 1) % B;
+            self.queued -= 1;
+            self.gp1.set_display_env(&self.disp_envs[self.displayed]);
+        }
+        // `draw_envs[i]` draws to the buffer after `i`
+        let draw_env = &mut self.draw_envs[(self.displayed + self.queued) % B];
+        let mut end_of_frame = Packet::new(InterruptRequest(0x1F << 24));
+        end_of_frame.insert_packet(draw_env);
+        gpu_dma.send_list(&end_of_frame);
+    }
+
+    /// Spins until the oldest queued frame is finished.
+    fn wait_frame(&mut self) {
+        // Interrupt requests are processed in order, so one raised since the
+        // last acknowledgement can't belong to an older frame. If several were
+        // raised, later frames fall back to waiting for the GPU to go idle.
+        self.gpu_status.load();
+        while !self.gpu_status.irq_pending() &&
+            (!self.gpu_status.cmd_ready() || !self.gpu_status.dma_ready())
+        {
+            self.gpu_status.load();
+        }
+        self.gp1.ack_irq();
+    }
+
+    /// Gets the index of the buffer being displayed.
+    pub fn displayed(&self) -> usize {
+        self.displayed
+    }
+
+    /// Gets the index of the buffer being drawn to.
+    pub fn drawing(&self) -> usize {
+        (self.displayed + self.queued + 1) % B
+    }
+
+    /// Gets the offset and size in VRAM of each buffer.
+    ///
+    /// Buffers are in the order they're displayed in.
+    pub fn buffers(&self) -> [(Vertex, Vertex); B] {
+        // `draw_envs[i]` holds the buffer after `i`
+        array::from_fn(|i| self.draw_envs[(i + B - 1) % B].contents.area())
+    }
+
+    /// Loads a `TIM` file into VRAM at the offsets specified in the file.
     ///
     /// After loading a TIM into VRAM, the copy in memory isn't necessary so the
-    /// lifetimes of the `TIM` and `LoadedTIM` are completely disconnected.
+    /// lifetimes of the `TIM` and `LoadedTIM` are completely disconnected. Use
+    /// [`Framebuffer::load_tim_into`] to avoid overwriting other data in VRAM.
     pub fn load_tim<const N: usize, const M: usize>(&mut self, tim: TIM<N, M>) -> LoadedTIM {
         // Used to avoid implementing GP0Command for any &[u32]
         // TIM::new ensures that the bitmap data is a valid GP0 command
@@ -121,18 +328,50 @@ impl Framebuffer {
 
         self.draw_sync();
         self.gp0.send_command(&CopyToVRAM(&tim.bmp.data));
-        let clut = if M != 0 {
+        if M != 0 {
             self.draw_sync();
             self.gp0.send_command(&CopyToVRAM(&tim.clut.data));
-            Some(tim.clut.offset)
-        } else {
-            None
-        };
+        }
+        LoadedTIM::from_tim(&tim)
+    }
 
-        LoadedTIM {
-            tex_page: tim.bmp.offset,
-            clut,
+    /// Loads a `TIM` file into regions of VRAM allocated by a
+    /// [`VRAMAllocator`][crate::gpu::VRAMAllocator].
+    ///
+    /// The bitmap is relocated to the upper left corner of `bmp` and the CLUT,
+    /// if any, to `clut`. The returned `LoadedTIM`'s `tex_coord` is where the
+    /// bitmap starts within its texture page. Returns `None` if either region
+    /// is too small or if the TIM has a CLUT and `clut` is `None` or unaligned.
+    pub fn load_tim_into<const N: usize, const M: usize>(
+        &mut self, mut tim: TIM<N, M>, bmp: &VRAMRegion, clut: Option<&VRAMRegion>,
+    ) -> Option<LoadedTIM> {
+        relocate_tim(&mut tim, bmp, clut)?;
+        Some(self.load_tim(tim))
+    }
+
+    /// Uploads a 24-bit image of `size` pixels to `offset` in VRAM.
+    ///
+    /// `data` contains the image's pixels as packed 3-byte RGB triples. The
+    /// width must be even so each line takes a whole number of halfwords.
+    /// Returns an error if the width is odd.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is too small to hold the image.
+    pub fn load_image24(
+        &mut self, offset: Vertex, size: Vertex, data: &[u32],
+    ) -> Result<(), VertexError> {
+        if size.0 % 2 != 0 {
+            return Err(VertexError::InvalidX)
         }
+        let vram_size = Vertex(Depth::Bits24.vram_width(size.0), size.1);
+        let data = &data[..vram_words(vram_size)];
+        self.draw_sync();
+        self.gp0.copy_to_vram(offset, vram_size);
+        for &word in data {
+            self.gp0.assign(word).store();
+        }
+        Ok(())
     }
 
     /// Loads the default font TIM into VRAM.
@@ -146,6 +385,65 @@ impl Framebuffer {
         self.load_tim(font)
     }
 
+    /// Copies a rectangle within VRAM from `src` to `dst`.
+    pub fn copy_vram(&mut self, src: Vertex, dst: Vertex, size: Vertex) {
+        self.draw_sync();
+        self.gp0.copy_rectangle(src, dst, size);
+    }
+
+    /// Reads a rectangle of VRAM into `dst` using the GPUREAD register.
+    ///
+    /// Each word in `dst` holds two halfwords from VRAM. If the rectangle has
+    /// an odd number of halfwords, the upper half of the last word is
+    /// undefined.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` is too small to hold the rectangle.
+    pub fn read_vram(&mut self, offset: Vertex, size: Vertex, dst: &mut [u32]) {
+        let dst = &mut dst[..vram_words(size)];
+        self.draw_sync();
+        self.gp0.request_vram(offset, size);
+        self.gpu_status.wait_vram();
+        let mut gpu_read = gpu::Response::skip_load();
+        for word in dst {
+            *word = gpu_read.load().to_bits();
+        }
+    }
+
+    /// Reads a rectangle of VRAM into `dst` using the GPU DMA channel.
+    ///
+    /// This temporarily switches the GPU's DMA direction to GPUREAD. Otherwise
+    /// it behaves like [`Framebuffer::read_vram`]. Returns an error if the
+    /// rectangle can't be split into few enough DMA blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` is too small to hold the rectangle.
+    pub fn dma_read_vram(
+        &mut self, offset: Vertex, size: Vertex, dst: &mut [u32], gpu_dma: &mut dma::GPU,
+    ) -> Result<(), dma::Error> {
+        let dst = &mut dst[..vram_words(size)];
+        // Use the largest block size which evenly divides the rectangle
+        let block_words = [16, 8, 4, 2]
+            .into_iter()
+            .find(|&n| dst.len() % n == 0)
+            .unwrap_or(1);
+        let blocks = dst.len() / block_words;
+        // Check this before requesting the rectangle to avoid leaving the GPU
+        // waiting for it to be read
+        if blocks > u16::MAX as usize {
+            return Err(dma::Error::OversizedBlock)
+        }
+        self.draw_sync();
+        self.gp1.dma_mode(Some(DMAMode::GPUREAD));
+        self.gp0.request_vram(offset, size);
+        self.gpu_status.wait_vram();
+        let res = gpu_dma.recv_blocks(dst, blocks);
+        self.gp1.dma_mode(Some(DMAMode::GP0));
+        res
+    }
+
     /// Spins until the GPU is ready to draw.
     pub fn draw_sync(&mut self) {
         self.gpu_status.load();
@@ -154,22 +452,51 @@ impl Framebuffer {
         }
     }
 
+    /// Checks if the odd field is being displayed in interlaced mode.
+    pub fn odd_field(&mut self) -> bool {
+        self.gpu_status.load().odd_line()
+    }
+
     /// Spins until vblank.
+    ///
+    /// If a [`VSync`][crate::sys::vsync::VSync] handler is installed this
+    /// waits for its count to change since the BIOS acknowledges the
+    /// interrupt.
     pub fn wait_vblank(&mut self) {
-        self.irq_status.ack(IRQ::Vblank).store().wait(IRQ::Vblank);
+        if vsync::is_installed() {
+            vsync::wait_vblank();
+        } else {
+            self.irq_status.ack(IRQ::Vblank).store().wait(IRQ::Vblank);
+        }
     }
 }
 
+/// Moves a `TIM`'s bitmap to the upper left corner of `bmp` and its CLUT, if
+/// any, to `clut`.
+fn relocate_tim<const N: usize, const M: usize>(
+    tim: &mut TIM<N, M>, bmp: &VRAMRegion, clut: Option<&VRAMRegion>,
+) -> Option<()> {
+    let fits =
+        |region: &VRAMRegion, size: Vertex| region.size().0 >= size.0 && region.size().1 >= size.1;
+    if !fits(bmp, tim.bmp.size) {
+        return None
+    }
+    if M != 0 {
+        let clut = clut?;
+        if !fits(clut, tim.clut.size) {
+            return None
+        }
+        tim.clut.offset = clut.clut()?;
+        tim.clut.data[1] = u32::from(clut.offset());
+    }
+    tim.bmp.offset = bmp.tex_page();
+    tim.bmp.data[1] = u32::from(bmp.offset());
+    Some(())
+}
+
 impl fmt::Write for TextBox {
     fn write_str(&mut self, msg: &str) -> fmt::Result {
-        for c in msg.chars() {
-            if c.is_ascii() {
-                self.print_char(c as u8);
-            } else {
-                // Print '?' for non-ascii UTF-8
-                self.print_char(b'?');
-            }
-        }
+        self.write_with(msg, TextBox::send);
         Ok(())
     }
 }
@@ -182,107 +509,442 @@ impl fmt::Write for TextBox {
 pub struct LoadedTIM {
     /// The loaded TIM's texture page attribute.
     pub tex_page: TexPage,
+    /// The texcoord of the bitmap's upper left corner within `tex_page`.
+    pub tex_coord: TexCoord,
+    /// The bitmap's bits per pixel.
+    pub bpp: Bpp,
     /// The loaded TIM's color loookup table attribute.
     pub clut: Option<Clut>,
 }
 
-// Up to 5 `Sprt8`s fit in the GPU buffer at one time.
-const TEXT_BOX_BUFFER: usize = GPU_BUFFER_SIZE / size_of::<Sprt8>();
+/// The horizontal alignment of lines in a [`TextBox`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Align {
+    /// Lines start at the left edge of the text box.
+    Left,
+    /// Lines are centered in the text box.
+    Center,
+    /// Lines end at the right edge of the text box.
+    Right,
+}
+
+// Up to 3 `Sprt`s fit in the GPU buffer after the font's draw mode.
+const TEXT_BOX_BUFFER: usize = (GPU_BUFFER_SIZE - size_of::<DrawMode>()) / size_of::<Sprt>();
 
 /// A text box configuration and in-memory buffer.
+///
+/// Printing to a text box draws each glyph immediately through GP0, waiting
+/// for the GPU whenever its command buffer may be full. Each group of glyphs is
+/// preceded by a [`DrawMode`] selecting the font's texture page and bpp. The
+/// previous draw mode isn't restored, so rectangles drawn after printing must
+/// set their own texture page. Use [`TextBox::batch`] to add glyphs to a
+/// frame's packet list instead.
 pub struct TextBox {
+    font: &'static Font,
     color: TexColor,
     initial: Vertex,
     cursor: Vertex,
     size: Vertex,
-    idx: usize,
-    buffer: [Sprt8; TEXT_BOX_BUFFER],
+    align: Align,
+    word_wrap: bool,
+    // The Latin-1 code of the last character printed on the current line
+    prev: Option<u8>,
+    // A sprite with the font's CLUT and color used for each glyph
+    letter: Sprt,
+    // The font's bitmap in VRAM
+    tim: LoadedTIM,
+    // The draw mode selecting the font's texture page and bpp
+    font_mode: DrawMode,
+    // The number of glyphs sent since the last `draw_sync`
+    queued: usize,
 }
 
 impl LoadedTIM {
-    /// Creates a new text box using the loaded TIM as the font.
+    /// Gets the properties of `tim` once it's loaded at the offsets in its
+    /// bitmaps.
+    fn from_tim<const N: usize, const M: usize>(tim: &TIM<N, M>) -> Self {
+        // The second word of the bitmap's copy command holds its VRAM offset
+        let offset = tim.bmp.data[1];
+        let offset = Vertex(offset as u16 as i16, (offset >> 16) as u16 as i16);
+        LoadedTIM {
+            tex_page: tim.bmp.offset,
+            tex_coord: page_tex_coord(offset, tim.bpp),
+            bpp: tim.bpp,
+            clut: (M != 0).then_some(tim.clut.offset),
+        }
+    }
+
+    /// Gets the texcoord within the texture page of `offset` within the
+    /// bitmap.
+    pub(crate) fn page_coord(&self, offset: TexCoord) -> TexCoord {
+        TexCoord {
+            x: self.tex_coord.x.wrapping_add(offset.x),
+            y: self.tex_coord.y.wrapping_add(offset.y),
+        }
+    }
+
+    /// Creates a new text box using the loaded TIM as the default font.
     pub fn new_text_box(&self, offset: (i16, i16), size: (i16, i16)) -> TextBox {
+        self.new_text_box_with_font(&Font::DEFAULT, offset, size)
+    }
+
+    /// Creates a new text box using the loaded TIM as a font with the given
+    /// metrics.
+    pub fn new_text_box_with_font(
+        &self, font: &'static Font, offset: (i16, i16), size: (i16, i16),
+    ) -> TextBox {
         let offset = Vertex::new(offset);
         let size = Vertex::new(size);
         let color = TexColor::from(WHITE);
-        let mut buffer = [Sprt8::new(); TEXT_BOX_BUFFER];
-        for letter in &mut buffer {
-            if let Some(clut) = self.clut {
-                letter.set_clut(clut);
-            }
-            letter.set_color(color);
+        let mut letter = Sprt::new();
+        if let Some(clut) = self.clut {
+            letter.set_clut(clut);
         }
+        letter.set_color(color);
         TextBox {
+            font,
             color,
             initial: offset,
             cursor: offset,
             size,
-            idx: 0,
-            buffer,
+            align: Align::Left,
+            word_wrap: false,
+            prev: None,
+            letter,
+            tim: *self,
+            font_mode: self.draw_mode(),
+            queued: 0,
         }
     }
 }
 
-const FONT_SIZE: u8 = 8;
+/// The part of some text which fits on the current line of a `TextBox`.
+struct Line<'a> {
+    text: &'a str,
+    width: i16,
+    rest: &'a str,
+    newline: bool,
+}
 
 impl TextBox {
     /// Move the cursor to the beginning of the next line.
+    ///
+    /// The cursor moves back to its initial position if the next line doesn't
+    /// fit in the text box.
     pub fn newline(&mut self) {
-        self.cursor = Vertex(self.initial.0, self.cursor.1 + FONT_SIZE as i16);
+        let height = self.font.height() as i16;
+        self.cursor = Vertex(self.initial.0, self.cursor.1 + height);
+        self.prev = None;
+        if self.cursor.1 + height > self.initial.1 + self.size.1 {
+            self.cursor = self.initial;
+        }
     }
     /// Move the cursor to its initial position.
     pub fn reset(&mut self) {
         self.cursor = self.initial;
+        self.prev = None;
     }
     /// Change the font color.
     pub fn change_color(&mut self, color: Color) {
         let color = TexColor::from(color);
         if color != self.color {
             self.color = color;
-            for letter in &mut self.buffer {
-                letter.set_color(color);
+            self.letter.set_color(color);
+        }
+    }
+    /// Gets the text box's font.
+    pub fn get_font(&self) -> &'static Font {
+        self.font
+    }
+    /// Gets the alignment of new lines.
+    pub fn get_align(&self) -> Align {
+        self.align
+    }
+    /// Sets the alignment of new lines.
+    ///
+    /// Alignment is applied to each line as it starts, so a line printed in
+    /// several pieces is aligned according to its first piece.
+    pub fn set_align(&mut self, align: Align) -> &mut Self {
+        self.align = align;
+        self
+    }
+    /// Checks if lines are broken between words.
+    pub fn get_word_wrap(&self) -> bool {
+        self.word_wrap
+    }
+    /// Sets whether lines are broken between words rather than at the first
+    /// character which doesn't fit.
+    pub fn set_word_wrap(&mut self, word_wrap: bool) -> &mut Self {
+        self.word_wrap = word_wrap;
+        self
+    }
+
+    /// Gets the glyph used to print a Latin-1 character and the character it
+    /// represents.
+    ///
+    /// Characters which aren't in the font are printed as '?'.
+    fn glyph(&self, code: u8) -> Option<(u8, &'static Glyph)> {
+        let font = self.font;
+        match font.glyph(code) {
+            Some(glyph) => Some((code, glyph)),
+            None => font.glyph(b'?').map(|glyph| (b'?', glyph)),
+        }
+    }
+
+    /// Gets how far the cursor moves when printing `code` after `prev`.
+    fn advance(&self, prev: Option<u8>, code: u8) -> (Option<u8>, i16) {
+        match self.glyph(code) {
+            Some((code, glyph)) => {
+                let kerning = prev.map_or(0, |prev| self.font.kerning(prev, code));
+                (Some(code), kerning as i16 + glyph.advance as i16)
+            },
+            None => (prev, 0),
+        }
+    }
+
+    /// Splits off the part of `text` which fits on the current line.
+    fn layout<'a>(&self, text: &'a str) -> Line<'a> {
+        let available = self.initial.0 + self.size.0 - self.cursor.0;
+        let at_start = self.cursor.0 == self.initial.0;
+        let mut width = 0;
+        let mut prev = self.prev;
+        // The end and width of the text before the last space and the start of
+        // the following word
+        let mut last_break = None;
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                return Line {
+                    text: &text[..i],
+                    width,
+                    rest: &text[i + 1..],
+                    newline: true,
+                }
             }
+            if c == ' ' {
+                last_break = Some((i, width, i + 1));
+            }
+            let (next, advance) = self.advance(prev, to_latin1(c).unwrap_or(b'?'));
+            // Always print something at the start of a line to make progress
+            if width + advance > available && (i != 0 || !at_start) {
+                let brk = if c == ' ' || self.word_wrap {
+                    last_break
+                } else {
+                    None
+                };
+                let (end, width, start) = brk.unwrap_or((i, width, i));
+                return Line {
+                    text: &text[..end],
+                    width,
+                    rest: &text[start..],
+                    newline: true,
+                }
+            }
+            width += advance;
+            prev = next;
+        }
+        Line {
+            text,
+            width,
+            rest: "",
+            newline: false,
+        }
+    }
+
+    /// Gets the offset from the left edge of the text box to a line of the
+    /// given width.
+    fn align_offset(&self, width: i16) -> i16 {
+        let space = self.size.0 - width;
+        match self.align {
+            Align::Left => 0,
+            Align::Center => space / 2,
+            Align::Right => space,
+        }
+    }
+
+    /// Prints a single Latin-1 character.
+    pub fn print_char(&mut self, code: u8) {
+        self.print_char_with(code, TextBox::send);
+    }
+
+    /// Creates a batch which adds the glyphs printed to this text box to
+    /// `ot` at depth `z` rather than drawing them immediately.
+    ///
+    /// Glyphs take their texture page from the draw mode in effect when
+    /// they're drawn, so when the batch is dropped a [`DrawMode`] selecting
+    /// the font's texture page is added at depth `z` to be sent before the
+    /// glyphs. Rectangles sent after the glyphs must set their own texture
+    /// page.
+    pub fn batch<'a, const N: usize, const Z: usize>(
+        &'a mut self, prims: &'a mut PrimitiveBuffer<N>, ot: &'a mut OrderingTable<Z>, z: usize,
+    ) -> TextBatch<'a, N, Z> {
+        TextBatch {
+            text_box: self,
+            prims,
+            ot,
+            z,
+            full: false,
+            glyphs: false,
         }
     }
-    /// Prints a single character.
-    pub fn print_char(&mut self, ascii: u8) {
-        if ascii == b'\n' {
+
+    fn print_char_with<F: FnMut(&mut Self, Sprt)>(&mut self, code: u8, mut emit: F) {
+        if code == b'\n' {
             self.newline();
-            self.cursor.0 = self.initial.0;
-        } else {
-            let ascii_per_row = 128 / FONT_SIZE;
-            // The default font omits the first 32 characters to save on VRAM. These
-            // characters are printed as '?'
-            let ascii = if ascii < (2 * ascii_per_row) {
-                b'?'
-            } else {
-                ascii - (2 * ascii_per_row)
-            };
-            let x = (ascii % ascii_per_row) * FONT_SIZE;
-            let y = (ascii / ascii_per_row) * FONT_SIZE;
-            if self.idx == 0 {
-                draw_sync();
+            return
+        }
+        let (_, advance) = self.advance(self.prev, code);
+        let right = self.initial.0 + self.size.0;
+        if self.cursor.0 != self.initial.0 && self.cursor.0 + advance > right {
+            self.newline();
+        }
+        if self.cursor.0 == self.initial.0 {
+            self.cursor.0 += self.align_offset(advance);
+        }
+        if let Some(sprt) = self.next_glyph(code) {
+            emit(self, sprt);
+        }
+    }
+
+    fn write_with<F: FnMut(&mut Self, Sprt)>(&mut self, msg: &str, mut emit: F) {
+        let mut rest = msg;
+        while !rest.is_empty() {
+            let line = self.layout(rest);
+            if self.cursor.0 == self.initial.0 {
+                self.cursor.0 += self.align_offset(line.width);
             }
-            self.buffer[self.idx]
-                .set_offset(self.cursor)
-                .set_tex_coord(TexCoord { x, y });
-            GP0::skip_load().send_command(&self.buffer[self.idx]);
-            self.idx += 1;
-            if self.idx == TEXT_BOX_BUFFER {
-                self.idx = 0;
+            for c in line.text.chars() {
+                // Print '?' for characters outside of Latin-1
+                if let Some(sprt) = self.next_glyph(to_latin1(c).unwrap_or(b'?')) {
+                    emit(self, sprt);
+                }
             }
-            self.cursor.0 += FONT_SIZE as i16;
-            if self.cursor.0 == self.initial.0 + self.size.0 {
+            if line.newline {
                 self.newline();
-                self.cursor.0 = self.initial.0;
-            }
-            if self.cursor.1 == self.initial.1 + self.size.1 {
-                self.cursor = self.initial;
             }
+            rest = line.rest;
+        }
+    }
+
+    /// Moves the cursor past `code` and returns the sprite for its glyph, if
+    /// it's visible.
+    fn next_glyph(&mut self, code: u8) -> Option<Sprt> {
+        let (code, glyph) = self.glyph(code)?;
+        if let Some(prev) = self.prev {
+            self.cursor.0 += self.font.kerning(prev, code) as i16;
+        }
+        let bearing = Vertex(glyph.bearing.0 as i16, glyph.bearing.1 as i16);
+        let mut sprt = self.letter;
+        sprt.set_offset(self.cursor + bearing)
+            .set_size(Vertex(glyph.width as i16, glyph.height as i16))
+            .set_tex_coord(self.tim.page_coord(glyph.offset));
+        self.cursor.0 += glyph.advance as i16;
+        self.prev = Some(code);
+        if glyph.width != 0 && glyph.height != 0 {
+            Some(sprt)
+        } else {
+            None
+        }
+    }
+
+    /// Sends a glyph directly through GP0, preceded by the font's draw mode
+    /// after waiting for the GPU.
+    fn send(&mut self, sprt: Sprt) {
+        if self.queued == 0 {
+            draw_sync();
+            GP0::skip_load().send_command(&self.font_mode);
+        }
+        GP0::skip_load().send_command(&sprt);
+        self.queued += 1;
+        if self.queued == TEXT_BOX_BUFFER {
+            self.queued = 0;
         }
     }
 }
 
+/// A [`TextBox`] which adds its glyphs to an [`OrderingTable`].
+///
+/// This is created by [`TextBox::batch`] and may be printed to with
+/// [`dprint!`][crate::dprint] and [`dprintln!`][crate::dprintln] like a
+/// `TextBox`. Printing moves the text box's cursor as usual. The glyphs are
+/// sent when the ordering table is, so the text box's font must remain in VRAM
+/// until then.
+pub struct TextBatch<'a, const N: usize, const Z: usize> {
+    text_box: &'a mut TextBox,
+    prims: &'a mut PrimitiveBuffer<N>,
+    ot: &'a mut OrderingTable<Z>,
+    z: usize,
+    full: bool,
+    // Whether any glyphs were inserted and need the font's draw mode
+    glyphs: bool,
+}
+
+impl<const N: usize, const Z: usize> TextBatch<'_, N, Z> {
+    /// Prints a single Latin-1 character.
+    pub fn print_char(&mut self, code: u8) {
+        let TextBatch {
+            text_box,
+            prims,
+            ot,
+            z,
+            full,
+            glyphs,
+        } = self;
+        text_box.print_char_with(code, |_, sprt| {
+            insert_glyph(prims, ot, *z, sprt, full, glyphs);
+        });
+    }
+
+    /// Checks if any glyphs were dropped because the primitive buffer was full
+    /// or the depth was invalid.
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+}
+
+impl<const N: usize, const Z: usize> fmt::Write for TextBatch<'_, N, Z> {
+    fn write_str(&mut self, msg: &str) -> fmt::Result {
+        let TextBatch {
+            text_box,
+            prims,
+            ot,
+            z,
+            full,
+            glyphs,
+        } = self;
+        text_box.write_with(msg, |_, sprt| {
+            insert_glyph(prims, ot, *z, sprt, full, glyphs);
+        });
+        if *full {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<const N: usize, const Z: usize> Drop for TextBatch<'_, N, Z> {
+    fn drop(&mut self) {
+        // Packets in the same bucket are sent in the reverse order of insertion
+        // so the draw mode is inserted last to be sent before the glyphs
+        if self.glyphs {
+            self.prims.insert(self.ot, self.z, self.text_box.font_mode);
+        }
+    }
+}
+
+/// Inserts a glyph for a [`TextBatch`], leaving room for the font's draw mode.
+fn insert_glyph<const N: usize, const Z: usize>(
+    prims: &mut PrimitiveBuffer<N>, ot: &mut OrderingTable<Z>, z: usize, sprt: Sprt,
+    full: &mut bool, glyphs: &mut bool,
+) {
+    let words = Packet::<Sprt>::WORDS + Packet::<DrawMode>::WORDS;
+    if prims.remaining() < words || prims.insert(ot, z, sprt).is_none() {
+        *full = true;
+    } else {
+        *glyphs = true;
+    }
+}
+
 /// Print a rust-style format string and args using the `&mut TextBox` specified
 /// by `$box`.
 #[macro_export]
@@ -304,3 +966,179 @@ macro_rules! dprintln {
         $box.print_char(b'\n');
     };
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "mips")]
+    use super::Framebuffer;
+    use super::{relocate_tim, Align, LoadedTIM, TextBox};
+    #[cfg(target_arch = "mips")]
+    use crate::gpu::VideoMode;
+    use crate::gpu::{Bpp, Flip, Frame, OrderingTable, PrimitiveBuffer, TexCoord, TexPage,
+                     VRAMAllocator, Vertex};
+    use crate::include_tim;
+    use core::fmt::Write;
+
+    fn text_box() -> TextBox {
+        let font = LoadedTIM {
+            tex_page: TexPage::try_from(Vertex(5, 0)).unwrap(),
+            tex_coord: TexCoord { x: 0, y: 0 },
+            bpp: Bpp::Bits15,
+            clut: None,
+        };
+        font.new_text_box((0, 0), (40, 32))
+    }
+
+    #[test_case]
+    fn layout() {
+        let mut txt = text_box();
+        let line = txt.layout("ab\ncd");
+        assert!(line.text == "ab" && line.width == 16 && line.rest == "cd" && line.newline);
+        let line = txt.layout("abc defgh");
+        assert!(line.text == "abc d" && line.width == 40 && line.rest == "efgh");
+        txt.set_word_wrap(true);
+        let line = txt.layout("abc defgh");
+        assert!(line.text == "abc" && line.width == 24 && line.rest == "defgh");
+        // Words longer than a line are split
+        let line = txt.layout("abcdefg");
+        assert!(line.text == "abcde" && line.rest == "fg");
+        let line = txt.layout("abc");
+        assert!(line.text == "abc" && line.rest.is_empty() && !line.newline);
+    }
+
+    #[test_case]
+    fn align() {
+        let mut txt = text_box();
+        assert!(txt.align_offset(24) == 0);
+        txt.set_align(Align::Center);
+        assert!(txt.align_offset(24) == 8);
+        txt.set_align(Align::Right);
+        assert!(txt.align_offset(24) == 16);
+    }
+
+    #[test_case]
+    fn batch() {
+        let mut txt = text_box();
+        // Room for 8 sprite packets of 5 words each
+        let mut prims = PrimitiveBuffer::<40>::new();
+        let mut ot = OrderingTable::<4>::new();
+        // Invalid depths are reported as a full batch
+        let mut batch = txt.batch(&mut prims, &mut ot, 4);
+        batch.print_char(b'x');
+        assert!(batch.is_full());
+        drop(batch);
+        assert!(prims.remaining() == 40);
+        txt.reset();
+
+        let mut batch = txt.batch(&mut prims, &mut ot, 2);
+        assert!(write!(batch, "ab c").is_ok());
+        assert!(!batch.is_full());
+        drop(batch);
+        // Four glyphs and the font's draw mode, which is added when the batch is
+        // dropped
+        assert!(prims.remaining() == 40 - 4 * 5 - 2);
+        assert!(txt.cursor == Vertex(32, 0));
+        let mut batch = txt.batch(&mut prims, &mut ot, 2);
+        assert!(write!(batch, "defgh").is_err());
+        assert!(batch.is_full());
+        drop(batch);
+        // Glyphs leave room for the draw mode so only three more fit
+        assert!(prims.remaining() == 18 - 3 * 5 - 2);
+    }
+
+    #[test_case]
+    fn relocated() {
+        let vram = VRAMAllocator::empty();
+        let _used = vram.alloc_texture(Vertex(16, 16), Bpp::Bits4).unwrap();
+        let mut font = include_tim!("../font.tim");
+        let bmp = vram.alloc_texture(font.bmp.size, font.bpp).unwrap();
+        let clut = vram.alloc_clut(font.bpp).unwrap();
+        assert!(bmp.offset() == Vertex(16, 0));
+        assert!(relocate_tim(&mut font, &bmp, Some(&clut)).is_some());
+        let loaded = LoadedTIM::from_tim(&font);
+        assert!(loaded.tex_page == TexPage::try_from(Vertex(0, 0)).unwrap());
+        assert!(loaded.tex_coord == TexCoord { x: 64, y: 0 } && loaded.bpp == Bpp::Bits4);
+        assert!(loaded.clut == clut.clut());
+        // Frames are relative to the relocated bitmap
+        let frame = Frame::new(TexCoord { x: 8, y: 4 }, Vertex(8, 8));
+        let sprt = loaded.sprite(&frame, Vertex(0, 0));
+        assert!(sprt.get_tex_coord() == TexCoord { x: 72, y: 4 });
+        let quad = loaded.quad(&frame, Vertex(0, 0), Flip::NONE);
+        assert!(quad.get_tex_coords()[3] == TexCoord { x: 80, y: 12 });
+        let mut txt = loaded.new_text_box((0, 0), (40, 32));
+        let glyph = txt.next_glyph(b'a').unwrap();
+        let offset = txt.font.glyph(b'a').unwrap().offset;
+        assert!(glyph.get_tex_coord() == loaded.page_coord(offset));
+        assert!(glyph.get_tex_coord().x >= 64);
+    }
+
+    // Walking the ordering table needs the console's 24-bit addresses
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn batch_draw_mode() {
+        use crate::dma;
+        use crate::gpu::decode::{Decoded, PacketIter};
+        use crate::gpu::env::DrawMode;
+
+        let mut txt = text_box();
+        let mut prims = PrimitiveBuffer::<40>::new();
+        let mut ot = OrderingTable::<4>::new();
+        ot.clear(&mut dma::OTC::new());
+        assert!(write!(txt.batch(&mut prims, &mut ot, 2), "ab").is_ok());
+        // SAFETY: The ordering table and packets aren't modified while iterating.
+        let mut commands = unsafe { PacketIter::new(&ot) }.flat_map(|p| p.commands());
+        // The font's texture page and bpp are selected before its glyphs
+        let mut draw_mode = DrawMode::new();
+        draw_mode
+            .set_tex_page(TexPage::try_from(Vertex(5, 0)).unwrap())
+            .set_bpp(Bpp::Bits15);
+        assert!(commands.next() == Some(Decoded::DrawMode(draw_mode)));
+        assert!(matches!(commands.next(), Some(Decoded::Sprt(_))));
+        assert!(matches!(commands.next(), Some(Decoded::Sprt(_))));
+        assert!(commands.next().is_none());
+    }
+
+    // Creating a framebuffer configures the GPU
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn triple_buffered() {
+        use crate::dma;
+
+        let bufs = [(0, 0), (0, 240), (320, 0)];
+        let mut fb = Framebuffer::with_buffers(bufs, (320, 240), VideoMode::NTSC, None).unwrap();
+        let size = Vertex(320, 240);
+        assert!(
+            fb.buffers() ==
+                [
+                    (Vertex(0, 0), size),
+                    (Vertex(0, 240), size),
+                    (Vertex(320, 0), size)
+                ]
+        );
+        assert!(fb.displayed() == 0);
+        assert!(fb.drawing() == 1);
+        let mut gpu_dma = dma::GPU::new();
+        // The first frame is queued while a third buffer is free
+        fb.present(&mut gpu_dma);
+        assert!(fb.displayed() == 0);
+        assert!(fb.drawing() == 2);
+        // Without a free buffer the oldest frame is displayed
+        fb.present(&mut gpu_dma);
+        assert!(fb.displayed() == 1);
+        assert!(fb.drawing() == 0);
+        fb.dma_swap(&mut gpu_dma);
+        assert!(fb.displayed() == 2);
+        assert!(fb.drawing() == 0);
+        assert!(fb.get_clear());
+        fb.set_clear(false);
+        assert!(!fb.get_clear());
+    }
+
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn interlaced() {
+        // A PAL interlaced buffer ends at the bottom of VRAM
+        let fb = Framebuffer::interlaced((0, 0), (640, 512), VideoMode::PAL, None).unwrap();
+        assert!(fb.buffers() == [(Vertex(0, 0), Vertex(640, 512)); 2]);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::gpu::{DrawEnv, TexCoord, Vertex};
+use core::array;
+
+/// The largest horizontal distance between a polygon's vertices that the GPU
+/// will draw.
+pub const MAX_POLY_WIDTH: i16 = 1023;
+/// The largest vertical distance between a polygon's vertices that the GPU will
+/// draw.
+pub const MAX_POLY_HEIGHT: i16 = 511;
+
+// The range of vertex coordinates the GPU accepts
+const MIN_COORD: i16 = -1024;
+const MAX_COORD: i16 = 1023;
+
+/// A rectangle in drawing coordinates used to cull polygons.
+///
+/// Both corners are inclusive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClipRect {
+    /// The upper-left corner.
+    pub upper_left: Vertex,
+    /// The lower-right corner.
+    pub lower_right: Vertex,
+}
+
+/// How a polygon should be handled before it's drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Visibility {
+    /// The polygon is entirely outside the clip rectangle.
+    Hidden,
+    /// The polygon overlaps the clip rectangle and may be drawn as is.
+    Visible,
+    /// The polygon overlaps the clip rectangle but is too large for the GPU to
+    /// draw, so it must be split first.
+    Oversized,
+}
+
+impl ClipRect {
+    /// Creates a clip rectangle from its upper-left and lower-right corners.
+    pub const fn new(upper_left: Vertex, lower_right: Vertex) -> Self {
+        ClipRect {
+            upper_left,
+            lower_right,
+        }
+    }
+
+    /// Tests a polygon's vertices against the clip rectangle.
+    ///
+    /// Polygons are hidden if their bounding box doesn't overlap the rectangle
+    /// and oversized if their bounding box is larger than
+    /// [`MAX_POLY_WIDTH`]x[`MAX_POLY_HEIGHT`] or any vertex is outside the
+    /// GPU's signed 11-bit coordinate range of -1024 to 1023.
+    pub fn test(&self, vertices: &[Vertex]) -> Visibility {
+        let mut min = Vertex(i16::MAX, i16::MAX);
+        let mut max = Vertex(i16::MIN, i16::MIN);
+        for v in vertices {
+            min = Vertex(min.0.min(v.0), min.1.min(v.1));
+            max = Vertex(max.0.max(v.0), max.1.max(v.1));
+        }
+        if max.0 < self.upper_left.0 ||
+            max.1 < self.upper_left.1 ||
+            min.0 > self.lower_right.0 ||
+            min.1 > self.lower_right.1
+        {
+            return Visibility::Hidden
+        }
+        // Compute the size with i32s since the vertices may be up to 65535 apart
+        let width = max.0 as i32 - min.0 as i32;
+        let height = max.1 as i32 - min.1 as i32;
+        let out_of_range =
+            min.0 < MIN_COORD || min.1 < MIN_COORD || max.0 > MAX_COORD || max.1 > MAX_COORD;
+        if width > MAX_POLY_WIDTH as i32 || height > MAX_POLY_HEIGHT as i32 || out_of_range {
+            Visibility::Oversized
+        } else {
+            Visibility::Visible
+        }
+    }
+}
+
+impl DrawEnv {
+    /// Gets the drawing area relative to the drawing offset.
+    ///
+    /// This is the rectangle that primitives' vertices are drawn in.
+    pub fn clip_rect(&self) -> ClipRect {
+        let offset = Vertex::from(self.offset);
+        ClipRect {
+            upper_left: Vertex::from(self.upper_left) - offset,
+            lower_right: Vertex::from(self.lower_right) - offset,
+        }
+    }
+}
+
+/// A polygon vertex with the attributes interpolated when splitting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Point {
+    pub vertex: Vertex,
+    pub color: [u8; 3],
+    pub tex_coord: TexCoord,
+}
+
+impl Point {
+    pub fn new(vertex: Vertex) -> Self {
+        Point {
+            vertex,
+            color: [0; 3],
+            tex_coord: TexCoord { x: 0, y: 0 },
+        }
+    }
+
+    fn midpoint(&self, other: &Self) -> Self {
+        let mid = |a: i32, b: i32| (a + b).div_euclid(2);
+        Point {
+            vertex: Vertex(
+                mid(self.vertex.0 as i32, other.vertex.0 as i32) as i16,
+                mid(self.vertex.1 as i32, other.vertex.1 as i32) as i16,
+            ),
+            color: array::from_fn(|i| mid(self.color[i] as i32, other.color[i] as i32) as u8),
+            tex_coord: TexCoord {
+                x: mid(self.tex_coord.x as i32, other.tex_coord.x as i32) as u8,
+                y: mid(self.tex_coord.y as i32, other.tex_coord.y as i32) as u8,
+            },
+        }
+    }
+}
+
+/// Splits a triangle or quad into four pieces along the midpoints of its
+/// edges.
+///
+/// Quads use the GPU's vertex order so `points[0]` and `points[3]` are opposite
+/// corners.
+pub(crate) fn subdivide<const N: usize>(points: [Point; N]) -> [[Point; N]; 4] {
+    let p = |i: usize| points[i % N];
+    let (grid, pieces): ([Point; 9], &[[usize; 4]; 4]) = match N {
+        // The corners followed by the midpoints of 0-1, 1-2 and 2-0
+        3 => (
+            [
+                p(0),
+                p(1),
+                p(2),
+                p(0).midpoint(&p(1)),
+                p(1).midpoint(&p(2)),
+                p(2).midpoint(&p(0)),
+                p(0),
+                p(0),
+                p(0),
+            ],
+            &[[0, 3, 5, 0], [3, 1, 4, 0], [5, 4, 2, 0], [3, 4, 5, 0]],
+        ),
+        // The corners followed by the midpoints of 0-1, 0-2, 1-3 and 2-3 and
+        // the center
+        4 => {
+            let top = p(0).midpoint(&p(1));
+            let bottom = p(2).midpoint(&p(3));
+            (
+                [
+                    p(0),
+                    p(1),
+                    p(2),
+                    p(3),
+                    top,
+                    p(0).midpoint(&p(2)),
+                    p(1).midpoint(&p(3)),
+                    bottom,
+                    top.midpoint(&bottom),
+                ],
+                &[[0, 4, 5, 8], [4, 1, 8, 6], [5, 8, 2, 7], [8, 6, 7, 3]],
+            )
+        },
+        _ => panic!("Only triangles and quads can be subdivided"),
+    };
+    array::from_fn(|i| array::from_fn(|j| grid[pieces[i][j]]))
+}
+
+/// Recursively splits a polygon until each piece is small enough to draw,
+/// calling `f` with each piece which is visible in `rect`.
+pub(crate) fn split<const N: usize>(
+    points: [Point; N], rect: &ClipRect, f: &mut dyn FnMut([Point; N]),
+) {
+    match rect.test(&points.map(|p| p.vertex)) {
+        Visibility::Hidden => {},
+        Visibility::Visible => f(points),
+        Visibility::Oversized => {
+            for piece in subdivide(points) {
+                split(piece, rect, f);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipRect, Visibility};
+    use crate::gpu::primitives::{PolyF3, PolyGT4};
+    use crate::gpu::{Color, DrawEnv, TexColor, TexCoord, Vertex};
+
+    const SCREEN: ClipRect = ClipRect::new(Vertex(0, 0), Vertex(319, 239));
+
+    #[test_case]
+    fn visibility() {
+        let mut tri = PolyF3::new();
+        tri.set_vertices([Vertex(-20, 0), Vertex(-10, 50), Vertex(-1, 239)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Hidden);
+        tri.set_vertices([Vertex(-20, 0), Vertex(10, 50), Vertex(-1, 239)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Visible);
+        tri.set_vertices([Vertex(-1000, 0), Vertex(100, 50), Vertex(0, 100)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Oversized);
+        // Hidden polygons are rejected even if they're too large
+        tri.set_vertices([Vertex(-30000, -10), Vertex(30000, -50), Vertex(0, -1000)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Hidden);
+        // Small polygons are still oversized if they're outside the GPU's
+        // coordinate range
+        tri.set_vertices([Vertex(300, 0), Vertex(1100, 0), Vertex(300, 100)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Oversized);
+        tri.set_vertices([Vertex(0, -1030), Vertex(10, 0), Vertex(0, 10)]);
+        assert!(tri.get_visibility(&SCREEN) == Visibility::Oversized);
+    }
+
+    #[test_case]
+    fn draw_env() {
+        let env = DrawEnv::new((0, 240), (320, 240), None).unwrap();
+        assert!(env.clip_rect() == ClipRect::new(Vertex(0, 0), Vertex(319, 239)));
+    }
+
+    #[test_case]
+    fn split_triangle() {
+        let mut tri = PolyF3::new();
+        tri.set_vertices([Vertex(0, 0), Vertex(100, 0), Vertex(0, 100)]);
+        let mut pieces = 0;
+        tri.split(&SCREEN, |_| pieces += 1);
+        assert!(pieces == 1);
+
+        // Splitting once gives four 1000x500 pieces but only the upper-left
+        // and center ones overlap the screen
+        tri.set_vertices([Vertex(0, 0), Vertex(2000, 0), Vertex(0, 1000)]);
+        let mut pieces = 0;
+        tri.split(&SCREEN, |piece| {
+            assert!(piece.get_visibility(&SCREEN) == Visibility::Visible);
+            pieces += 1;
+        });
+        assert!(pieces == 2);
+    }
+
+    #[test_case]
+    fn split_quad() {
+        let mut quad = PolyGT4::new();
+        quad.set_vertices([
+            Vertex(0, 0),
+            Vertex(1200, 0),
+            Vertex(0, 200),
+            Vertex(1200, 200),
+        ])
+        .set_colors([
+            TexColor::from(Color::new(0, 0, 0)),
+            TexColor::from(Color::new(0xFF, 0, 0)),
+            TexColor::from(Color::new(0, 0, 0)),
+            TexColor::from(Color::new(0xFF, 0, 0)),
+        ])
+        .set_tex_coords([
+            TexCoord { x: 0, y: 0 },
+            TexCoord { x: 200, y: 0 },
+            TexCoord { x: 0, y: 100 },
+            TexCoord { x: 200, y: 100 },
+        ]);
+        let mut pieces = [PolyGT4::new(); 4];
+        let mut n = 0;
+        quad.split(&SCREEN, |piece| {
+            pieces[n] = *piece;
+            n += 1;
+        });
+        // The right half is off screen
+        assert!(n == 2);
+        let [upper_left, ..] = pieces;
+        assert!(
+            upper_left.get_vertices() ==
+                [
+                    Vertex(0, 0),
+                    Vertex(600, 0),
+                    Vertex(0, 100),
+                    Vertex(600, 100)
+                ]
+        );
+        assert!(
+            upper_left.get_tex_coords() ==
+                [
+                    TexCoord { x: 0, y: 0 },
+                    TexCoord { x: 100, y: 0 },
+                    TexCoord { x: 0, y: 50 },
+                    TexCoord { x: 100, y: 50 },
+                ]
+        );
+        let [c0, c1, ..] = upper_left.get_colors();
+        assert!(c0.red == 0 && c1.red == 0x3F);
+    }
+}
@@ -0,0 +1,563 @@
+//! GP0 command stream decoding.
+//!
+//! This decodes raw GP0 words or packet linked lists back into typed
+//! primitives and environment commands. Their `Debug` output can be used to
+//! dump a frame's display list over TTY. Decoding a word stream doesn't touch
+//! any hardware registers, so it may also be used to check the output of
+//! packet builders in tests.
+
+use crate::dma::LinkedList;
+use crate::gpu::env::{DrawAreaBottomRight, DrawAreaTopLeft, DrawMode, DrawOffset, MaskBit,
+                      TexWindow};
+use crate::gpu::primitives::{LineF2, LineG2, PolyF3, PolyF4, PolyFT3, PolyFT4, PolyG3, PolyG4,
+                             PolyGT3, PolyGT4, Sprt, Sprt16, Sprt8, Tile, Tile1, Tile16, Tile8};
+use crate::gpu::{Color, Vertex};
+use crate::hw::gpu::GP0Command;
+use core::fmt;
+use core::mem::size_of;
+
+const TERMINATION: u32 = 0x00FF_FFFF;
+// Physical addresses in packet headers are accessed through KSEG0
+const KSEG0: u32 = 0x8000_0000;
+
+fn color(word: u32) -> Color {
+    Color::new(word as u8, (word >> 8) as u8, (word >> 16) as u8)
+}
+
+fn vertex(word: u32) -> Vertex {
+    Vertex(word as i16, (word >> 16) as i16)
+}
+
+/// Checks if a word is a poly-line's termination code.
+fn is_line_termination(word: u32) -> bool {
+    word & 0xF000_F000 == 0x5000_5000
+}
+
+/// A sequence of words which is summarized instead of printed in `Debug`
+/// output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Words<'a>(pub &'a [u32]);
+
+impl fmt::Debug for Words<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.first() {
+            Some(first) => write!(f, "[{:#010x}, .. {} words]", first, self.0.len()),
+            None => write!(f, "[]"),
+        }
+    }
+}
+
+/// A flat-shaded poly-line of any length (GP0(48h)).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FlatPolyLine<'a> {
+    words: &'a [u32],
+}
+
+impl FlatPolyLine<'_> {
+    /// Gets the line's color.
+    pub fn color(&self) -> Color {
+        color(self.words[0])
+    }
+
+    /// Checks if the line is semi-transparent.
+    pub fn semi_transparent(&self) -> bool {
+        self.words[0] & (1 << 25) != 0
+    }
+
+    /// Gets the line's vertices.
+    pub fn vertices(&self) -> impl Iterator<Item = Vertex> + '_ {
+        self.words[1..].iter().map(|&w| vertex(w))
+    }
+}
+
+impl fmt::Debug for FlatPolyLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineFN")
+            .field("color", &self.color())
+            .field("semi_transparent", &self.semi_transparent())
+            .field("vertices", &DebugIter(|| self.vertices()))
+            .finish()
+    }
+}
+
+/// A gouraud-shaded poly-line of any length (GP0(58h)).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct GouraudPolyLine<'a> {
+    words: &'a [u32],
+}
+
+impl GouraudPolyLine<'_> {
+    /// Checks if the line is semi-transparent.
+    pub fn semi_transparent(&self) -> bool {
+        self.words[0] & (1 << 25) != 0
+    }
+
+    /// Gets the line's vertices and their colors.
+    pub fn colored_vertices(&self) -> impl Iterator<Item = (Color, Vertex)> + '_ {
+        self.words
+            .chunks_exact(2)
+            .map(|pair| (color(pair[0]), vertex(pair[1])))
+    }
+}
+
+impl fmt::Debug for GouraudPolyLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineGN")
+            .field("semi_transparent", &self.semi_transparent())
+            .field("colored_vertices", &DebugIter(|| self.colored_vertices()))
+            .finish()
+    }
+}
+
+/// Prints an iterator as a list without consuming it.
+struct DebugIter<F>(F);
+
+impl<F: Fn() -> I, I: Iterator<Item = T>, T: fmt::Debug> fmt::Debug for DebugIter<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0()).finish()
+    }
+}
+
+/// A decoded GP0 command.
+///
+/// Note that a [`DrawEnv`][crate::gpu::DrawEnv] is decoded as the sequence of
+/// environment commands and the fills or NOPs which clear its background.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoded<'a> {
+    Nop,
+    ClearCache,
+    Fill {
+        color: Color,
+        offset: Vertex,
+        size: Vertex,
+    },
+    InterruptRequest,
+    PolyF3(PolyF3),
+    PolyF4(PolyF4),
+    PolyFT3(PolyFT3),
+    PolyFT4(PolyFT4),
+    PolyG3(PolyG3),
+    PolyG4(PolyG4),
+    PolyGT3(PolyGT3),
+    PolyGT4(PolyGT4),
+    LineF2(LineF2),
+    LineG2(LineG2),
+    LineFN(FlatPolyLine<'a>),
+    LineGN(GouraudPolyLine<'a>),
+    Tile(Tile),
+    Tile1(Tile1),
+    Tile8(Tile8),
+    Tile16(Tile16),
+    Sprt(Sprt),
+    Sprt8(Sprt8),
+    Sprt16(Sprt16),
+    CopyVRAM {
+        src: Vertex,
+        dst: Vertex,
+        size: Vertex,
+    },
+    CopyToVRAM {
+        offset: Vertex,
+        size: Vertex,
+        data: Words<'a>,
+    },
+    CopyFromVRAM {
+        offset: Vertex,
+        size: Vertex,
+    },
+    DrawMode(DrawMode),
+    TexWindow(TexWindow),
+    DrawAreaTopLeft(DrawAreaTopLeft),
+    DrawAreaBottomRight(DrawAreaBottomRight),
+    DrawOffset(DrawOffset),
+    MaskBit(MaskBit),
+    /// A command which isn't recognized or doesn't have a typed
+    /// representation.
+    Unknown(Words<'a>),
+    /// A command which needs more words than are left in the stream.
+    Truncated(Words<'a>),
+}
+
+/// An iterator decoding a stream of GP0 words.
+///
+/// Iteration stops after the last command or at the first truncated command.
+#[derive(Clone)]
+pub struct Decoder<'a> {
+    words: &'a [u32],
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a decoder for a stream of GP0 words.
+    pub fn new(words: &'a [u32]) -> Self {
+        Decoder { words }
+    }
+
+    /// Reads a `T` from the start of the stream.
+    fn read<T: GP0Command>(&self) -> Option<(T, usize)> {
+        let len = size_of::<T>() / size_of::<u32>();
+        if self.words.len() < len {
+            return None
+        }
+        let ptr = self.words.as_ptr() as *const T;
+        // SAFETY: There are enough words left for a `T` and the GP0 commands
+        // are plain data which are valid for any bit pattern.
+        Some((unsafe { ptr.read_unaligned() }, len))
+    }
+
+    /// Gets the length of the poly-line at the start of the stream, including
+    /// its termination code.
+    fn line_len(&self, gouraud: bool) -> Option<usize> {
+        // The first two vertices are never checked for the termination code
+        let (first, step) = if gouraud { (4, 2) } else { (3, 1) };
+        (first..self.words.len())
+            .step_by(step)
+            .find(|&i| is_line_termination(self.words[i]))
+            .map(|i| i + 1)
+    }
+
+    /// Decodes the command at the start of the stream and returns it along
+    /// with the number of words it takes.
+    fn decode(&self) -> (Decoded<'a>, usize) {
+        let words = self.words;
+        let word = words[0];
+        let cmd = (word >> 24) as u8;
+        let decoded = match cmd {
+            0x00 => Some((Decoded::Nop, 1)),
+            0x01 => Some((Decoded::ClearCache, 1)),
+            0x02 => words.get(..3).map(|w| {
+                let fill = Decoded::Fill {
+                    color: color(w[0]),
+                    offset: vertex(w[1]),
+                    size: vertex(w[2]),
+                };
+                (fill, 3)
+            }),
+            0x1F => Some((Decoded::InterruptRequest, 1)),
+            0x20..=0x3F => match cmd & 0x1C {
+                0x00 => self.read().map(|(p, n)| (Decoded::PolyF3(p), n)),
+                0x08 => self.read().map(|(p, n)| (Decoded::PolyF4(p), n)),
+                0x04 => self.read().map(|(p, n)| (Decoded::PolyFT3(p), n)),
+                0x0C => self.read().map(|(p, n)| (Decoded::PolyFT4(p), n)),
+                0x10 => self.read().map(|(p, n)| (Decoded::PolyG3(p), n)),
+                0x18 => self.read().map(|(p, n)| (Decoded::PolyG4(p), n)),
+                0x14 => self.read().map(|(p, n)| (Decoded::PolyGT3(p), n)),
+                _ => self.read().map(|(p, n)| (Decoded::PolyGT4(p), n)),
+            },
+            0x40..=0x5F => match cmd & 0x18 {
+                0x00 => self.read().map(|(p, n)| (Decoded::LineF2(p), n)),
+                0x10 => self.read().map(|(p, n)| (Decoded::LineG2(p), n)),
+                0x08 => self.line_len(false).map(|n| {
+                    let line = FlatPolyLine {
+                        words: &words[..n - 1],
+                    };
+                    (Decoded::LineFN(line), n)
+                }),
+                _ => self.line_len(true).map(|n| {
+                    let line = GouraudPolyLine {
+                        words: &words[..n - 1],
+                    };
+                    (Decoded::LineGN(line), n)
+                }),
+            },
+            0x60..=0x7F => match cmd & 0x1C {
+                0x00 => self.read().map(|(p, n)| (Decoded::Tile(p), n)),
+                0x08 => self.read().map(|(p, n)| (Decoded::Tile1(p), n)),
+                0x10 => self.read().map(|(p, n)| (Decoded::Tile8(p), n)),
+                0x18 => self.read().map(|(p, n)| (Decoded::Tile16(p), n)),
+                0x04 => self.read().map(|(p, n)| (Decoded::Sprt(p), n)),
+                0x14 => self.read().map(|(p, n)| (Decoded::Sprt8(p), n)),
+                0x1C => self.read().map(|(p, n)| (Decoded::Sprt16(p), n)),
+                // There's no typed 1x1 sprite
+                _ => words.get(..3).map(|w| (Decoded::Unknown(Words(w)), 3)),
+            },
+            0x80..=0x9F => words.get(..4).map(|w| {
+                let copy = Decoded::CopyVRAM {
+                    src: vertex(w[1]),
+                    dst: vertex(w[2]),
+                    size: vertex(w[3]),
+                };
+                (copy, 4)
+            }),
+            0xA0..=0xBF => words.get(..3).and_then(|w| {
+                let size = vertex(w[2]);
+                // A size of 0 wraps around to the maximum
+                let width = ((size.0 as u16).wrapping_sub(1) & 0x3FF) as usize + 1;
+                let height = ((size.1 as u16).wrapping_sub(1) & 0x1FF) as usize + 1;
+                let len = 3 + (width * height + 1) / 2;
+                let copy = Decoded::CopyToVRAM {
+                    offset: vertex(w[1]),
+                    size,
+                    data: Words(words.get(3..len)?),
+                };
+                Some((copy, len))
+            }),
+            0xC0..=0xDF => words.get(..3).map(|w| {
+                let copy = Decoded::CopyFromVRAM {
+                    offset: vertex(w[1]),
+                    size: vertex(w[2]),
+                };
+                (copy, 3)
+            }),
+            0xE1 => Some(env(words, Decoded::DrawMode)),
+            0xE2 => Some(env(words, Decoded::TexWindow)),
+            0xE3 => Some(env(words, Decoded::DrawAreaTopLeft)),
+            0xE4 => Some(env(words, Decoded::DrawAreaBottomRight)),
+            0xE5 => Some(env(words, Decoded::DrawOffset)),
+            0xE6 => Some(env(words, Decoded::MaskBit)),
+            _ => Some((Decoded::Unknown(Words(&words[..1])), 1)),
+        };
+        decoded.unwrap_or((Decoded::Truncated(Words(words)), words.len()))
+    }
+}
+
+/// Decodes a single-word environment command.
+fn env<'a, T: TryFrom<u32>>(words: &'a [u32], f: fn(T) -> Decoded<'a>) -> (Decoded<'a>, usize) {
+    let decoded = match T::try_from(words[0]) {
+        Ok(t) => f(t),
+        Err(_) => Decoded::Unknown(Words(&words[..1])),
+    };
+    (decoded, 1)
+}
+
+impl<'a> Decoder<'a> {
+    /// Decodes the next command and returns it along with the words it was
+    /// decoded from.
+    pub fn next_with_words(&mut self) -> Option<(Decoded<'a>, &'a [u32])> {
+        if self.words.is_empty() {
+            return None
+        }
+        let (decoded, len) = self.decode();
+        let (words, rest) = self.words.split_at(len);
+        self.words = rest;
+        Some((decoded, words))
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Decoded<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_words().map(|(decoded, _)| decoded)
+    }
+}
+
+impl fmt::Debug for Decoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// A packet in a linked list.
+#[derive(Clone, Copy)]
+pub struct DecodedPacket<'a> {
+    /// The packet's header.
+    pub header: u32,
+    /// The packet's contents.
+    pub words: &'a [u32],
+}
+
+impl<'a> DecodedPacket<'a> {
+    /// Decodes the packet's contents.
+    pub fn commands(&self) -> Decoder<'a> {
+        Decoder::new(self.words)
+    }
+}
+
+impl fmt::Debug for DecodedPacket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("next", &format_args!("{:#08x}", self.header & TERMINATION))
+            .field("commands", &self.commands())
+            .finish()
+    }
+}
+
+/// An iterator over the packets in a linked list.
+///
+/// Iteration stops at the termination marker. Empty packets, such as ordering
+/// table entries, are included.
+#[derive(Clone)]
+pub struct PacketIter<'a> {
+    next: Option<&'a u32>,
+}
+
+impl<'a> PacketIter<'a> {
+    /// Creates an iterator over the packets in a linked list.
+    ///
+    /// # Safety
+    ///
+    /// All packets in the list must be valid and remain unmodified while
+    /// they're being iterated over.
+    pub unsafe fn new<L: LinkedList + ?Sized>(list: &'a L) -> Self {
+        PacketIter {
+            next: list.address(),
+        }
+    }
+}
+
+impl<'a> Iterator for PacketIter<'a> {
+    type Item = DecodedPacket<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header_ref = self.next?;
+        let header = *header_ref;
+        let len = (header >> 24) as usize;
+        let ptr = header_ref as *const u32;
+        // SAFETY: `PacketIter::new`'s caller ensured the packet is valid so its
+        // contents immediately follow the header.
+        let words = unsafe { core::slice::from_raw_parts(ptr.add(1), len) };
+        let next = header & TERMINATION;
+        self.next = if next == TERMINATION {
+            None
+        } else {
+            // SAFETY: The next packet is valid and in main RAM.
+            unsafe { ((next | KSEG0) as *const u32).as_ref() }
+        };
+        Some(DecodedPacket { header, words })
+    }
+}
+
+impl fmt::Debug for PacketIter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoded, Decoder, Words};
+    use crate::gpu::colors::RED;
+    use crate::gpu::env::{DrawMode, DrawOffset};
+    use crate::gpu::primitives::{PolyGT4, Tile8};
+    use crate::gpu::{Color, DrawEnv, Vertex};
+    use crate::hw::gpu::GP0Command;
+
+    #[test_case]
+    fn primitives() {
+        let mut quad = PolyGT4::new();
+        quad.set_vertices([Vertex(0, 0), Vertex(8, 0), Vertex(0, 8), Vertex(8, 8)])
+            .set_semi_transparent(true);
+        let mut tile = Tile8::new();
+        tile.set_color(RED).set_offset(Vertex(4, 4));
+        let mut words = [0; 16];
+        words[..12].copy_from_slice(quad.data());
+        words[12..14].copy_from_slice(tile.data());
+        words[14] = u32::from(DrawOffset::new(Vertex(-2, 3)).unwrap());
+        words[15] = 0x0000_0000;
+        let mut decoder = Decoder::new(&words);
+        assert!(decoder.next() == Some(Decoded::PolyGT4(quad)));
+        assert!(decoder.next() == Some(Decoded::Tile8(tile)));
+        assert!(
+            decoder.next() == Some(Decoded::DrawOffset(DrawOffset::new(Vertex(-2, 3)).unwrap()))
+        );
+        assert!(decoder.next() == Some(Decoded::Nop));
+        assert!(decoder.next().is_none());
+    }
+
+    #[test_case]
+    fn truncated() {
+        let words = [0x0200_00FF, 0];
+        let mut decoder = Decoder::new(&words);
+        assert!(decoder.next() == Some(Decoded::Truncated(Words(&words))));
+        assert!(decoder.next().is_none());
+    }
+
+    #[test_case]
+    fn poly_line() {
+        let words = [
+            0x4800_00FF,
+            0x0000_0000,
+            0x0010_0010,
+            0x0020_0000,
+            0x5555_5555,
+            0xE100_0000,
+        ];
+        let mut decoder = Decoder::new(&words);
+        match decoder.next() {
+            Some(Decoded::LineFN(line)) => {
+                assert!(line.color() == Color::new(0xFF, 0, 0));
+                assert!(line.vertices().count() == 3);
+                assert!(line.vertices().last() == Some(Vertex(0, 0x20)));
+            },
+            _ => panic!("Expected a poly-line"),
+        }
+        assert!(decoder.next() == Some(Decoded::DrawMode(DrawMode::new())));
+    }
+
+    #[test_case]
+    fn draw_env() {
+        let mut env = DrawEnv::new((0, 240), (320, 240), Some(RED)).unwrap();
+        let fill = Decoded::Fill {
+            color: RED,
+            offset: Vertex(0, 240),
+            size: Vertex(320, 240),
+        };
+        // Buffers up to 256 lines tall only need one fill
+        assert!(Decoder::new(env.data()).nth(4) == Some(fill));
+        assert!(Decoder::new(env.data())
+            .skip(5)
+            .all(|cmd| cmd == Decoded::Nop));
+        // The fills are replaced by a NOP for each of their words
+        env.set_clear(false);
+        assert!(!env.get_clear());
+        assert!(Decoder::new(env.data())
+            .skip(4)
+            .all(|cmd| cmd == Decoded::Nop));
+        assert!(Decoder::new(env.data()).count() == 10);
+        env.set_clear(true);
+        assert!(Decoder::new(env.data()).nth(4) == Some(fill));
+    }
+
+    #[test_case]
+    fn tall_draw_env() {
+        let mut env = DrawEnv::new((0, 0), (640, 512), Some(RED)).unwrap();
+        // Fills and rectangles only have 9 bits for their height so 512 lines
+        // are cleared in two parts
+        let fill = |y| Decoded::Fill {
+            color: RED,
+            offset: Vertex(0, y),
+            size: Vertex(640, 256),
+        };
+        let mut decoder = Decoder::new(env.data()).skip(4);
+        assert!(decoder.next() == Some(fill(0)));
+        assert!(decoder.next() == Some(fill(256)));
+        env.mask_display();
+        let mut decoder = Decoder::new(env.data()).skip(4);
+        for y in [0, 256] {
+            match decoder.next() {
+                Some(Decoded::Tile(tile)) => {
+                    assert!(tile.get_offset() == Vertex(0, y));
+                    assert!(tile.get_size() == Vertex(640, 256));
+                    assert!(tile.get_color() == RED);
+                },
+                _ => panic!("Expected a rectangle"),
+            }
+        }
+        assert!(decoder.next().is_none());
+    }
+
+    // Walking a linked list needs the console's 24-bit addresses and clearing
+    // the ordering table uses DMA
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn packet_list() {
+        use super::PacketIter;
+        use crate::dma;
+        use crate::gpu::colors::BLUE;
+        use crate::gpu::{OrderingTable, PrimitiveBuffer};
+
+        let mut prims = PrimitiveBuffer::<16>::new();
+        let mut ot = OrderingTable::<4>::new();
+        ot.clear(&mut dma::OTC::new());
+        let mut tile = Tile8::new();
+        tile.set_color(BLUE);
+        prims.insert(&mut ot, 2, tile).unwrap();
+        // SAFETY: The ordering table and packets aren't modified while iterating.
+        let packets = unsafe { PacketIter::new(&ot) };
+        assert!(packets.clone().count() == 5);
+        let mut commands = packets.flat_map(|p| p.commands());
+        assert!(commands.next() == Some(Decoded::Tile8(tile)));
+        assert!(commands.next().is_none());
+    }
+}
@@ -0,0 +1,360 @@
+use crate::gpu::colors::{BLACK, WHITE};
+use crate::gpu::env::DrawMode;
+use crate::gpu::primitives::Tile;
+use crate::gpu::{BlendMode, ClipRect, Color, OrderingTable, Packet, PrimitiveBuffer, Vertex};
+
+/// A value which moves linearly between two levels over a number of frames.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Ramp {
+    from: i16,
+    to: i16,
+    duration: u32,
+    elapsed: u32,
+}
+
+impl Ramp {
+    fn new(from: i16, to: i16, duration: u32) -> Self {
+        Ramp {
+            from,
+            to,
+            duration,
+            elapsed: 0,
+        }
+    }
+
+    fn value(&self) -> i16 {
+        if self.elapsed >= self.duration {
+            return self.to
+        }
+        let range = self.to as i32 - self.from as i32;
+        (self.from as i32 + range * self.elapsed as i32 / self.duration as i32) as i16
+    }
+
+    fn step(&mut self, frames: u32) {
+        self.elapsed = self.elapsed.saturating_add(frames).min(self.duration);
+    }
+
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Gets the size of a clip rectangle.
+fn rect_size(rect: &ClipRect) -> Vertex {
+    rect.lower_right - rect.upper_left + Vertex(1, 1)
+}
+
+/// A full-screen color blend animated over a number of frames.
+///
+/// The blend's level ranges from `-255` to `255`. Positive levels add the
+/// color scaled by the level to the screen and negative levels subtract it, so
+/// fading to black subtracts white and fading to white adds it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fade {
+    color: Color,
+    level: Ramp,
+}
+
+impl Fade {
+    /// The largest level magnitude.
+    pub const MAX_LEVEL: i16 = 255;
+
+    /// Creates a blend of `color` which moves from level `from` to level `to`
+    /// over `frames` frames.
+    pub fn new(color: Color, from: i16, to: i16, frames: u32) -> Self {
+        let clamp = |level: i16| level.clamp(-Self::MAX_LEVEL, Self::MAX_LEVEL);
+        Fade {
+            color,
+            level: Ramp::new(clamp(from), clamp(to), frames),
+        }
+    }
+
+    /// Fades the screen out to black.
+    pub fn to_black(frames: u32) -> Self {
+        Self::new(WHITE, 0, -Self::MAX_LEVEL, frames)
+    }
+
+    /// Fades the screen in from black.
+    pub fn from_black(frames: u32) -> Self {
+        Self::new(WHITE, -Self::MAX_LEVEL, 0, frames)
+    }
+
+    /// Fades the screen out to white.
+    pub fn to_white(frames: u32) -> Self {
+        Self::new(WHITE, 0, Self::MAX_LEVEL, frames)
+    }
+
+    /// Fades the screen in from white.
+    pub fn from_white(frames: u32) -> Self {
+        Self::new(WHITE, Self::MAX_LEVEL, 0, frames)
+    }
+
+    /// Flashes the screen with `color`, fading out over `frames` frames.
+    pub fn flash(color: Color, frames: u32) -> Self {
+        Self::new(color, Self::MAX_LEVEL, 0, frames)
+    }
+
+    /// Moves the screen's brightness from level `from` to level `to`.
+    ///
+    /// Levels below zero darken the screen and levels above zero brighten it.
+    pub fn brightness(from: i16, to: i16, frames: u32) -> Self {
+        Self::new(WHITE, from, to, frames)
+    }
+
+    /// Advances the fade by `frames` frames.
+    pub fn step(&mut self, frames: u32) -> &mut Self {
+        self.level.step(frames);
+        self
+    }
+
+    /// Checks if the fade reached its final level.
+    pub fn is_done(&self) -> bool {
+        self.level.is_done()
+    }
+
+    /// Gets the current level.
+    pub fn level(&self) -> i16 {
+        self.level.value()
+    }
+
+    /// Gets the blend mode used for the current level.
+    pub fn blend_mode(&self) -> BlendMode {
+        if self.level() < 0 {
+            BlendMode::Subtract
+        } else {
+            BlendMode::Add
+        }
+    }
+
+    /// Gets the color blended with the screen at the current level.
+    pub fn blend_color(&self) -> Color {
+        let level = self.level().unsigned_abs();
+        let scale = |c: u8| (c as u16 * level / Self::MAX_LEVEL as u16) as u8;
+        Color::new(
+            scale(self.color.red),
+            scale(self.color.green),
+            scale(self.color.blue),
+        )
+    }
+
+    /// Inserts the fade covering `screen` into `ot` at depth `z`.
+    ///
+    /// Untextured semi-transparent primitives use the current draw mode's
+    /// blend mode, so this also inserts `draw_mode` with its blend mode
+    /// changed before the tile and `draw_mode` itself after it. Since the draw
+    /// mode also sets the texture page for rectangles it should be the one used
+    /// by the rest of the frame. Nothing is inserted at level zero. Returns
+    /// `None` without inserting anything if `z` is invalid or `prims` is full.
+    pub fn insert<const N: usize, const Z: usize>(
+        &self, prims: &mut PrimitiveBuffer<N>, ot: &mut OrderingTable<Z>, z: usize,
+        screen: &ClipRect, draw_mode: DrawMode,
+    ) -> Option<()> {
+        if self.level() == 0 {
+            return Some(())
+        }
+        if z >= Z || Packet::<Tile>::WORDS + 2 * Packet::<DrawMode>::WORDS > prims.remaining() {
+            return None
+        }
+        let mut tile = Tile::new();
+        tile.set_offset(screen.upper_left)
+            .set_size(rect_size(screen))
+            .set_color(self.blend_color())
+            .set_semi_transparent(true);
+        let mut blend_mode = draw_mode;
+        blend_mode.set_blend_mode(self.blend_mode());
+        // Packets in the same bucket are sent in reverse order so the blend
+        // mode is sent first and the caller's draw mode is restored last
+        prims.insert(ot, z, draw_mode)?;
+        prims.insert(ot, z, tile)?;
+        prims.insert(ot, z, blend_mode)?;
+        Some(())
+    }
+}
+
+/// Letterbox bars at the top and bottom of the screen which slide in or out
+/// over a number of frames.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Letterbox {
+    color: Color,
+    height: Ramp,
+}
+
+impl Letterbox {
+    /// Creates black bars which grow from `from` to `to` pixels tall over
+    /// `frames` frames.
+    pub fn new(from: i16, to: i16, frames: u32) -> Self {
+        Letterbox {
+            color: BLACK,
+            height: Ramp::new(from.max(0), to.max(0), frames),
+        }
+    }
+
+    /// Sets the bars' color.
+    pub fn set_color(&mut self, color: Color) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Advances the bars' animation by `frames` frames.
+    pub fn step(&mut self, frames: u32) -> &mut Self {
+        self.height.step(frames);
+        self
+    }
+
+    /// Checks if the bars reached their final height.
+    pub fn is_done(&self) -> bool {
+        self.height.is_done()
+    }
+
+    /// Gets the current height of each bar.
+    pub fn height(&self) -> i16 {
+        self.height.value()
+    }
+
+    /// Gets the opaque tiles for the top and bottom bars covering `screen`.
+    pub fn tiles(&self, screen: &ClipRect) -> [Tile; 2] {
+        let size = rect_size(screen);
+        let height = self.height().min(size.1 / 2);
+        let mut top = Tile::new();
+        top.set_offset(screen.upper_left)
+            .set_size(Vertex(size.0, height))
+            .set_color(self.color);
+        let mut bottom = top;
+        bottom.set_offset(Vertex(
+            screen.upper_left.0,
+            screen.lower_right.1 + 1 - height,
+        ));
+        [top, bottom]
+    }
+
+    /// Inserts the bars covering `screen` into `ot` at depth `z`.
+    ///
+    /// Nothing is inserted if the bars have no height. Returns `None` if `z` is
+    /// invalid or `prims` is full.
+    pub fn insert<const N: usize, const Z: usize>(
+        &self, prims: &mut PrimitiveBuffer<N>, ot: &mut OrderingTable<Z>, z: usize,
+        screen: &ClipRect,
+    ) -> Option<()> {
+        if self.height() == 0 {
+            return Some(())
+        }
+        // Check everything up front to avoid inserting only the top bar
+        if z >= Z || 2 * Packet::<Tile>::WORDS > prims.remaining() {
+            return None
+        }
+        for tile in self.tiles(screen) {
+            prims.insert(ot, z, tile)?;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fade, Letterbox};
+    use crate::gpu::colors::RED;
+    use crate::gpu::env::DrawMode;
+    use crate::gpu::{BlendMode, ClipRect, Color, OrderingTable, PrimitiveBuffer, Vertex};
+
+    const SCREEN: ClipRect = ClipRect::new(Vertex(0, 0), Vertex(319, 239));
+
+    #[test_case]
+    fn fade_to_black() {
+        let mut fade = Fade::to_black(4);
+        assert!(fade.level() == 0);
+        fade.step(1);
+        assert!(fade.level() == -63);
+        assert!(fade.blend_mode() == BlendMode::Subtract);
+        assert!(fade.blend_color() == Color::new(63, 63, 63));
+        fade.step(10);
+        assert!(fade.is_done());
+        assert!(fade.blend_color() == Color::new(255, 255, 255));
+    }
+
+    #[test_case]
+    fn flash() {
+        let mut fade = Fade::flash(RED, 2);
+        assert!(fade.blend_mode() == BlendMode::Add);
+        assert!(fade.blend_color() == RED);
+        fade.step(1);
+        assert!(fade.blend_color().red == 128 && fade.blend_color().green == 0);
+        let zero = Fade::brightness(0, 0, 0);
+        assert!(zero.is_done() && zero.level() == 0);
+    }
+
+    #[test_case]
+    fn insert() {
+        let mut prims = PrimitiveBuffer::<16>::new();
+        let mut ot = OrderingTable::<2>::new();
+        let fade = Fade::from_black(30);
+        assert!(fade
+            .insert(&mut prims, &mut ot, 1, &SCREEN, DrawMode::new())
+            .is_some());
+        // A tile and two draw modes
+        assert!(prims.remaining() == 16 - 4 - 2 * 2);
+        let done = *Fade::from_black(30).step(30);
+        assert!(done
+            .insert(&mut prims, &mut ot, 1, &SCREEN, DrawMode::new())
+            .is_some());
+        assert!(prims.remaining() == 8);
+        assert!(fade
+            .insert(&mut prims, &mut ot, 2, &SCREEN, DrawMode::new())
+            .is_none());
+        assert!(prims.remaining() == 8);
+    }
+
+    // Walking the ordering table needs the console's 24-bit addresses
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn insert_order() {
+        use crate::dma;
+        use crate::gpu::decode::{Decoded, PacketIter};
+
+        let mut prims = PrimitiveBuffer::<16>::new();
+        let mut ot = OrderingTable::<2>::new();
+        ot.clear(&mut dma::OTC::new());
+        let mut draw_mode = DrawMode::new();
+        draw_mode.set_blend_mode(BlendMode::Average);
+        let fade = *Fade::to_black(2).step(1);
+        assert!(fade
+            .insert(&mut prims, &mut ot, 1, &SCREEN, draw_mode)
+            .is_some());
+        // SAFETY: The ordering table and packets aren't modified while iterating.
+        let mut commands = unsafe { PacketIter::new(&ot) }.flat_map(|p| p.commands());
+        let mut blend_mode = draw_mode;
+        blend_mode.set_blend_mode(BlendMode::Subtract);
+        assert!(commands.next() == Some(Decoded::DrawMode(blend_mode)));
+        match commands.next() {
+            Some(Decoded::Tile(tile)) => {
+                assert!(tile.get_offset() == Vertex(0, 0) && tile.get_size() == Vertex(320, 240));
+            },
+            _ => panic!("expected the fade's tile"),
+        }
+        assert!(commands.next() == Some(Decoded::DrawMode(draw_mode)));
+        assert!(commands.next().is_none());
+    }
+
+    #[test_case]
+    fn letterbox() {
+        let mut bars = Letterbox::new(0, 30, 3);
+        bars.step(2);
+        assert!(bars.height() == 20);
+        let [top, bottom] = bars.tiles(&SCREEN);
+        assert!(top.get_offset() == Vertex(0, 0) && top.get_size() == Vertex(320, 20));
+        assert!(bottom.get_offset() == Vertex(0, 220) && bottom.get_size() == Vertex(320, 20));
+    }
+
+    #[test_case]
+    fn letterbox_insert() {
+        let mut prims = PrimitiveBuffer::<12>::new();
+        let mut ot = OrderingTable::<2>::new();
+        let bars = *Letterbox::new(0, 30, 3).step(1);
+        assert!(bars.insert(&mut prims, &mut ot, 2, &SCREEN).is_none());
+        assert!(bars.insert(&mut prims, &mut ot, 1, &SCREEN).is_some());
+        // Two tiles
+        assert!(prims.remaining() == 12 - 2 * 4);
+        // Neither bar is inserted if only one fits
+        assert!(bars.insert(&mut prims, &mut ot, 1, &SCREEN).is_none());
+        assert!(prims.remaining() == 4);
+    }
+}
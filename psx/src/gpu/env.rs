@@ -0,0 +1,582 @@
+//! GPU rendering environment commands.
+//!
+//! These are single-word GP0 commands which change the GPU's drawing state.
+//! Since they implement [`GP0Command`] they may be sent directly through
+//! [`GP0`][`crate::hw::gpu::GP0`] or inserted into packet lists to change the
+//! drawing state between groups of primitives.
+
+use crate::gpu::{BlendMode, Bpp, Command, CommandError, PackedVertex, TexCoord, TexPage, Vertex,
+                 VertexError};
+use crate::hw::gpu::GP0Command;
+use core::convert::TryFrom;
+
+const BLEND_MODE: u32 = 5;
+const BPP: u32 = 7;
+const DITHER: u32 = 9;
+const DRAW_TO_DISPLAY: u32 = 10;
+const TEXTURE_DISABLE: u32 = 11;
+const X_FLIP: u32 = 12;
+const Y_FLIP: u32 = 13;
+
+const SET_MASK: u32 = 0;
+const CHECK_MASK: u32 = 1;
+
+/// Splits a command word into its command and parameter bits.
+fn split(word: u32, cmd: Command) -> Result<u32, CommandError> {
+    if (word >> 24) as Command != cmd {
+        return Err(CommandError::InvalidCommand)
+    }
+    Ok(word & 0x00FF_FFFF)
+}
+
+/// Draw mode setting command (GP0(E1h)).
+///
+/// This sets the texture page used by textured rectangles, the
+/// semi-transparency mode, dithering and whether drawing to the displayed area
+/// is allowed.
+#[repr(C, align(4))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DrawMode {
+    settings: u16,
+    _pad: u8,
+    cmd: Command,
+}
+
+impl DrawMode {
+    const CMD: Command = 0xE1;
+    const MASK: u32 = (1 << 14) - 1;
+
+    /// Creates a new draw mode command using texture page (0, 0) with 4-bit
+    /// colors, averaged semi-transparency and all flags disabled.
+    pub const fn new() -> Self {
+        DrawMode {
+            settings: 0,
+            _pad: 0,
+            cmd: Self::CMD,
+        }
+    }
+
+    fn get_bit(&self, bit: u32) -> bool {
+        self.settings & (1 << bit) != 0
+    }
+
+    fn set_bit(&mut self, bit: u32, value: bool) -> &mut Self {
+        self.settings &= !(1 << bit);
+        self.settings |= (value as u16) << bit;
+        self
+    }
+
+    /// Gets the texture page.
+    pub fn get_tex_page(&self) -> TexPage {
+        PackedVertex {
+            data: (self.settings & 0x1F).to_le_bytes(),
+        }
+    }
+
+    /// Sets the texture page.
+    pub fn set_tex_page<T>(&mut self, tpage: T) -> &mut Self
+    where TexPage: From<T> {
+        self.settings &= !0x1F;
+        self.settings |= u32::from(TexPage::from(tpage)) as u16;
+        self
+    }
+
+    /// Gets the semi-transparency mode.
+    pub fn get_blend_mode(&self) -> BlendMode {
+        BlendMode::from_bits((self.settings >> BLEND_MODE) as u32)
+    }
+
+    /// Sets the semi-transparency mode.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) -> &mut Self {
+        self.settings &= !(0b11 << BLEND_MODE);
+        self.settings |= (blend_mode as u16) << BLEND_MODE;
+        self
+    }
+
+    /// Gets the texture page colors.
+    pub fn get_bpp(&self) -> Bpp {
+        // `TryFrom<u32>` ensures the colors aren't the reserved value
+        match (self.settings >> BPP) & 0b11 {
+            0 => Bpp::Bits4,
+            1 => Bpp::Bits8,
+            _ => Bpp::Bits15,
+        }
+    }
+
+    /// Sets the texture page colors.
+    pub fn set_bpp(&mut self, bpp: Bpp) -> &mut Self {
+        self.settings &= !(0b11 << BPP);
+        self.settings |= (bpp as u16) << BPP;
+        self
+    }
+
+    /// Checks if dithering from 24-bit to 15-bit colors is enabled.
+    pub fn get_dither(&self) -> bool {
+        self.get_bit(DITHER)
+    }
+
+    /// Enables dithering from 24-bit to 15-bit colors.
+    pub fn set_dither(&mut self, dither: bool) -> &mut Self {
+        self.set_bit(DITHER, dither)
+    }
+
+    /// Checks if drawing to the displayed area is allowed.
+    pub fn get_draw_to_display(&self) -> bool {
+        self.get_bit(DRAW_TO_DISPLAY)
+    }
+
+    /// Allows drawing to the displayed area.
+    pub fn set_draw_to_display(&mut self, allowed: bool) -> &mut Self {
+        self.set_bit(DRAW_TO_DISPLAY, allowed)
+    }
+
+    /// Checks if textures are disabled.
+    ///
+    /// This only has an effect if texture disabling was enabled with
+    /// GP1(09h).
+    pub fn get_texture_disable(&self) -> bool {
+        self.get_bit(TEXTURE_DISABLE)
+    }
+
+    /// Disables textures.
+    ///
+    /// This only has an effect if texture disabling was enabled with
+    /// GP1(09h).
+    pub fn set_texture_disable(&mut self, disabled: bool) -> &mut Self {
+        self.set_bit(TEXTURE_DISABLE, disabled)
+    }
+
+    /// Checks if textured rectangles are flipped horizontally.
+    pub fn get_x_flip(&self) -> bool {
+        self.get_bit(X_FLIP)
+    }
+
+    /// Flips textured rectangles horizontally.
+    pub fn set_x_flip(&mut self, flip: bool) -> &mut Self {
+        self.set_bit(X_FLIP, flip)
+    }
+
+    /// Checks if textured rectangles are flipped vertically.
+    pub fn get_y_flip(&self) -> bool {
+        self.get_bit(Y_FLIP)
+    }
+
+    /// Flips textured rectangles vertically.
+    pub fn set_y_flip(&mut self, flip: bool) -> &mut Self {
+        self.set_bit(Y_FLIP, flip)
+    }
+}
+
+impl From<DrawMode> for u32 {
+    fn from(draw_mode: DrawMode) -> u32 {
+        (draw_mode.cmd as u32) << 24 | draw_mode.settings as u32
+    }
+}
+
+impl TryFrom<u32> for DrawMode {
+    type Error = CommandError;
+
+    fn try_from(word: u32) -> Result<Self, CommandError> {
+        let settings = split(word, Self::CMD)? & Self::MASK;
+        if (settings >> BPP) & 0b11 == 0b11 {
+            return Err(CommandError::InvalidParameter)
+        }
+        Ok(DrawMode {
+            settings: settings as u16,
+            _pad: 0,
+            cmd: Self::CMD,
+        })
+    }
+}
+
+/// Texture window setting command (GP0(E2h)).
+///
+/// This repeats a region of the texture page over textured primitives. The
+/// mask and offset are specified in pixels, but only multiples of 8 are
+/// representable so the lower 3 bits of each component are ignored.
+#[repr(C, align(4))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TexWindow {
+    settings: [u8; 3],
+    cmd: Command,
+}
+
+impl TexWindow {
+    const CMD: Command = 0xE2;
+    const MASK: u32 = (1 << 20) - 1;
+
+    /// Creates a new texture window command which leaves texcoords unchanged.
+    pub const fn new() -> Self {
+        TexWindow {
+            settings: [0; 3],
+            cmd: Self::CMD,
+        }
+    }
+
+    fn settings(&self) -> u32 {
+        u32::from_le_bytes([self.settings[0], self.settings[1], self.settings[2], 0])
+    }
+
+    fn set_settings(&mut self, settings: u32) -> &mut Self {
+        let [a, b, c, _] = settings.to_le_bytes();
+        self.settings = [a, b, c];
+        self
+    }
+
+    fn get_pair(&self, shift: u32) -> TexCoord {
+        let pair = self.settings() >> shift;
+        TexCoord {
+            x: ((pair & 0x1F) << 3) as u8,
+            y: (((pair >> 5) & 0x1F) << 3) as u8,
+        }
+    }
+
+    fn set_pair(&mut self, shift: u32, t: TexCoord) -> &mut Self {
+        let pair = (t.x as u32 >> 3) | ((t.y as u32 >> 3) << 5);
+        let settings = (self.settings() & !(0x3FF << shift)) | (pair << shift);
+        self.set_settings(settings)
+    }
+
+    /// Gets the texture window mask in pixels.
+    pub fn get_mask(&self) -> TexCoord {
+        self.get_pair(0)
+    }
+
+    /// Sets the texture window mask in pixels.
+    pub fn set_mask<T>(&mut self, mask: T) -> &mut Self
+    where TexCoord: From<T> {
+        self.set_pair(0, TexCoord::from(mask))
+    }
+
+    /// Gets the texture window offset in pixels.
+    pub fn get_offset(&self) -> TexCoord {
+        self.get_pair(10)
+    }
+
+    /// Sets the texture window offset in pixels.
+    pub fn set_offset<T>(&mut self, offset: T) -> &mut Self
+    where TexCoord: From<T> {
+        self.set_pair(10, TexCoord::from(offset))
+    }
+}
+
+impl From<TexWindow> for u32 {
+    fn from(tex_window: TexWindow) -> u32 {
+        (tex_window.cmd as u32) << 24 | tex_window.settings()
+    }
+}
+
+impl TryFrom<u32> for TexWindow {
+    type Error = CommandError;
+
+    fn try_from(word: u32) -> Result<Self, CommandError> {
+        let settings = split(word, Self::CMD)? & Self::MASK;
+        let mut tex_window = TexWindow::new();
+        tex_window.set_settings(settings);
+        Ok(tex_window)
+    }
+}
+
+macro_rules! draw_area_cmd {
+    ($(#[$($meta:meta)*])* $name:ident, $cmd:expr) => {
+        $(#[$($meta)*])*
+        #[repr(C, align(4))]
+        #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+        pub struct $name {
+            corner: PackedVertex<3, 10, 9>,
+            cmd: Command,
+        }
+
+        impl $name {
+            const CMD: Command = $cmd;
+            const MASK: u32 = (1 << 19) - 1;
+
+            /// Creates a new drawing area command.
+            pub fn new<T>(corner: T) -> Result<Self, VertexError>
+            where Vertex: From<T> {
+                Ok($name {
+                    corner: PackedVertex::try_from(Vertex::from(corner))?,
+                    cmd: Self::CMD,
+                })
+            }
+
+            /// Gets the drawing area's corner.
+            pub fn get_corner(&self) -> Vertex {
+                Vertex::from(self.corner)
+            }
+
+            /// Sets the drawing area's corner.
+            pub fn set_corner<T>(&mut self, corner: T) -> Result<&mut Self, VertexError>
+            where Vertex: From<T> {
+                self.corner = PackedVertex::try_from(Vertex::from(corner))?;
+                Ok(self)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(area: $name) -> u32 {
+                (area.cmd as u32) << 24 | u32::from(area.corner)
+            }
+        }
+
+        impl TryFrom<u32> for $name {
+            type Error = CommandError;
+
+            fn try_from(word: u32) -> Result<Self, CommandError> {
+                let corner = split(word, Self::CMD)? & Self::MASK;
+                let corner = Vertex((corner & 0x3FF) as i16, (corner >> 10) as i16);
+                // This can't fail since the value is masked
+                $name::new(corner).map_err(|_| CommandError::InvalidParameter)
+            }
+        }
+
+        impl GP0Command for $name {}
+    };
+}
+
+draw_area_cmd! {
+    /// Drawing area top left command (GP0(E3h)).
+    DrawAreaTopLeft, 0xE3
+}
+
+draw_area_cmd! {
+    /// Drawing area bottom right command (GP0(E4h)).
+    DrawAreaBottomRight, 0xE4
+}
+
+/// Drawing offset command (GP0(E5h)).
+///
+/// This offset is added to the vertices of all primitives. Each component is a
+/// signed 11-bit value.
+#[repr(C, align(4))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DrawOffset {
+    offset: PackedVertex<3, 11, 11>,
+    cmd: Command,
+}
+
+impl DrawOffset {
+    const CMD: Command = 0xE5;
+    const MASK: u32 = (1 << 22) - 1;
+    const MIN: i16 = -(1 << 10);
+    const MAX: i16 = (1 << 10) - 1;
+
+    /// Creates a new drawing offset command.
+    pub fn new<T>(offset: T) -> Result<Self, VertexError>
+    where Vertex: From<T> {
+        let mut res = DrawOffset {
+            offset: PackedVertex::try_from(Vertex(0, 0))?,
+            cmd: Self::CMD,
+        };
+        res.set_offset(offset)?;
+        Ok(res)
+    }
+
+    /// Gets the drawing offset.
+    pub fn get_offset(&self) -> Vertex {
+        fn sign_extend(x: i16) -> i16 {
+            (x << 5) >> 5
+        }
+        let Vertex(x, y) = Vertex::from(self.offset);
+        Vertex(sign_extend(x), sign_extend(y))
+    }
+
+    /// Sets the drawing offset.
+    pub fn set_offset<T>(&mut self, offset: T) -> Result<&mut Self, VertexError>
+    where Vertex: From<T> {
+        let Vertex(x, y) = Vertex::from(offset);
+        if x < Self::MIN || x > Self::MAX {
+            return Err(VertexError::InvalidX)
+        }
+        if y < Self::MIN || y > Self::MAX {
+            return Err(VertexError::InvalidY)
+        }
+        self.offset = PackedVertex::try_from(Vertex(x & 0x7FF, y & 0x7FF))?;
+        Ok(self)
+    }
+}
+
+impl From<DrawOffset> for u32 {
+    fn from(draw_offset: DrawOffset) -> u32 {
+        (draw_offset.cmd as u32) << 24 | u32::from(draw_offset.offset)
+    }
+}
+
+impl TryFrom<u32> for DrawOffset {
+    type Error = CommandError;
+
+    fn try_from(word: u32) -> Result<Self, CommandError> {
+        let offset = split(word, Self::CMD)? & Self::MASK;
+        let offset = Vertex((offset & 0x7FF) as i16, (offset >> 11) as i16);
+        // This can't fail since the value is masked
+        let offset = PackedVertex::try_from(offset).map_err(|_| CommandError::InvalidParameter)?;
+        Ok(DrawOffset {
+            offset,
+            cmd: Self::CMD,
+        })
+    }
+}
+
+/// Mask bit setting command (GP0(E6h)).
+#[repr(C, align(4))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MaskBit {
+    settings: u8,
+    _pad: u16,
+    cmd: Command,
+}
+
+impl MaskBit {
+    const CMD: Command = 0xE6;
+    const MASK: u32 = 0b11;
+
+    /// Creates a new mask bit setting command with both flags disabled.
+    pub const fn new() -> Self {
+        MaskBit {
+            settings: 0,
+            _pad: 0,
+            cmd: Self::CMD,
+        }
+    }
+
+    /// Checks if the mask bit is set when drawing pixels.
+    pub fn get_set_mask(&self) -> bool {
+        self.settings & (1 << SET_MASK) != 0
+    }
+
+    /// Sets the mask bit when drawing pixels.
+    pub fn set_set_mask(&mut self, set: bool) -> &mut Self {
+        self.settings &= !(1 << SET_MASK);
+        self.settings |= (set as u8) << SET_MASK;
+        self
+    }
+
+    /// Checks if pixels with the mask bit set are protected from drawing.
+    pub fn get_check_mask(&self) -> bool {
+        self.settings & (1 << CHECK_MASK) != 0
+    }
+
+    /// Protects pixels with the mask bit set from drawing.
+    pub fn set_check_mask(&mut self, check: bool) -> &mut Self {
+        self.settings &= !(1 << CHECK_MASK);
+        self.settings |= (check as u8) << CHECK_MASK;
+        self
+    }
+}
+
+impl From<MaskBit> for u32 {
+    fn from(mask_bit: MaskBit) -> u32 {
+        (mask_bit.cmd as u32) << 24 | mask_bit.settings as u32
+    }
+}
+
+impl TryFrom<u32> for MaskBit {
+    type Error = CommandError;
+
+    fn try_from(word: u32) -> Result<Self, CommandError> {
+        let settings = split(word, Self::CMD)? & Self::MASK;
+        Ok(MaskBit {
+            settings: settings as u8,
+            _pad: 0,
+            cmd: Self::CMD,
+        })
+    }
+}
+
+impl GP0Command for DrawMode {}
+impl GP0Command for TexWindow {}
+impl GP0Command for DrawOffset {}
+impl GP0Command for MaskBit {}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "mips")]
+    use super::{DrawAreaBottomRight, DrawAreaTopLeft, DrawOffset};
+    use super::{DrawMode, MaskBit, TexWindow};
+    use crate::gpu::{BlendMode, Bpp, CommandError, TexCoord, TexPage, Vertex};
+    #[cfg(target_arch = "mips")]
+    use crate::hw::gpu::GP0Command;
+    use core::convert::TryFrom;
+
+    // The fuzz macros only run on the console
+    #[cfg(target_arch = "mips")]
+    macro_rules! round_trip {
+        ($name:ident, $cmd:expr, $mask:expr) => {
+            fuzz!(|params: u32| {
+                let word = ($cmd << 24) | (params & 0x00FF_FFFF);
+                let res = $name::try_from(word);
+                if let Ok(res) = res {
+                    assert!(u32::from(res) == word & ($cmd << 24 | $mask));
+                    assert!(res.data() == [u32::from(res)]);
+                }
+                assert!($name::try_from(word ^ (1 << 24)) == Err(CommandError::InvalidCommand));
+            });
+        };
+    }
+
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn round_trip() {
+        round_trip!(DrawMode, 0xE1, 0x3FFF);
+        round_trip!(TexWindow, 0xE2, 0xF_FFFF);
+        round_trip!(DrawAreaTopLeft, 0xE3, 0x7_FFFF);
+        round_trip!(DrawAreaBottomRight, 0xE4, 0x7_FFFF);
+        round_trip!(DrawOffset, 0xE5, 0x3F_FFFF);
+        round_trip!(MaskBit, 0xE6, 0b11);
+    }
+
+    #[test_case]
+    fn draw_mode() {
+        let tex_page = TexPage::try_from(Vertex(10, 1)).unwrap();
+        let mut draw_mode = DrawMode::new();
+        draw_mode
+            .set_tex_page(tex_page)
+            .set_blend_mode(BlendMode::Subtract)
+            .set_bpp(Bpp::Bits15)
+            .set_dither(true)
+            .set_draw_to_display(true)
+            .set_y_flip(true);
+        assert!(
+            u32::from(draw_mode) ==
+                0xE100_0000 | 0x1A | 2 << 5 | 2 << 7 | 1 << 9 | 1 << 10 | 1 << 13
+        );
+        assert!(draw_mode.get_tex_page() == tex_page);
+        assert!(draw_mode.get_blend_mode() == BlendMode::Subtract);
+        assert!(draw_mode.get_bpp() == Bpp::Bits15);
+        assert!(!draw_mode.get_x_flip());
+        assert!(DrawMode::try_from(0xE100_0000 | 3 << 7) == Err(CommandError::InvalidParameter));
+    }
+
+    #[test_case]
+    fn tex_window() {
+        let mut tex_window = TexWindow::new();
+        tex_window
+            .set_mask(TexCoord { x: 0xF8, y: 0x0F })
+            .set_offset(TexCoord { x: 0x10, y: 0x80 });
+        assert!(u32::from(tex_window) == 0xE200_0000 | 0x1F | 1 << 5 | 2 << 10 | 0x10 << 15);
+        assert!(tex_window.get_mask() == TexCoord { x: 0xF8, y: 0x08 });
+        assert!(tex_window.get_offset() == TexCoord { x: 0x10, y: 0x80 });
+    }
+
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn draw_offset() {
+        fuzz!(|x: i16, y: i16| {
+            let offset = DrawOffset::new(Vertex(x, y));
+            if (-1024..1024).contains(&x) && (-1024..1024).contains(&y) {
+                assert!(offset.unwrap().get_offset() == Vertex(x, y));
+            } else {
+                assert!(offset.is_err());
+            }
+        });
+    }
+
+    #[test_case]
+    fn mask_bit() {
+        let mut mask_bit = MaskBit::new();
+        mask_bit.set_check_mask(true);
+        assert!(u32::from(mask_bit) == 0xE600_0002);
+        mask_bit.set_set_mask(true).set_check_mask(false);
+        assert!(u32::from(mask_bit) == 0xE600_0001);
+    }
+}
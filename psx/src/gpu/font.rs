@@ -0,0 +1,198 @@
+use crate::gpu::TexCoord;
+
+/// A glyph's location in a font's texture and its metrics.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph {
+    /// The glyph's upper-left texcoord.
+    pub offset: TexCoord,
+    /// The glyph's width in texels.
+    pub width: u8,
+    /// The glyph's height in texels.
+    pub height: u8,
+    /// The offset from the cursor to the glyph's upper-left corner.
+    pub bearing: (i8, i8),
+    /// The distance to move the cursor after the glyph.
+    pub advance: u8,
+}
+
+impl Glyph {
+    /// A glyph which isn't in the font.
+    pub const MISSING: Self = Glyph::new(TexCoord { x: 0, y: 0 }, 0, 0, 0);
+
+    /// Creates a glyph drawn at the cursor.
+    pub const fn new(offset: TexCoord, width: u8, height: u8, advance: u8) -> Self {
+        Glyph {
+            offset,
+            width,
+            height,
+            bearing: (0, 0),
+            advance,
+        }
+    }
+
+    /// Checks if the glyph isn't in the font.
+    pub const fn is_missing(&self) -> bool {
+        self.width == 0 && self.advance == 0
+    }
+}
+
+/// An adjustment to the advance between a pair of characters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Kerning {
+    /// The Latin-1 code of the first character.
+    pub left: u8,
+    /// The Latin-1 code of the second character.
+    pub right: u8,
+    /// The amount to move the second character to the right.
+    pub adjust: i8,
+}
+
+/// A bitmap font's metrics.
+///
+/// Glyphs are indexed by their [Latin-1](https://en.wikipedia.org/wiki/ISO/IEC_8859-1)
+/// code and their texcoords are relative to the texture page of the TIM
+/// containing the font. Use [`include_font!`][crate::include_font] to create a
+/// `Font` from a metrics table at compile-time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Font {
+    glyphs: [Glyph; 256],
+    kerning: &'static [Kerning],
+    height: u8,
+}
+
+const DEFAULT_SIZE: u8 = 8;
+
+impl Font {
+    /// The metrics for the default 8x8 monospace font.
+    ///
+    /// This is the font loaded by
+    /// [`Framebuffer::load_default_font`][crate::Framebuffer::load_default_font].
+    /// It omits the first 32 characters and anything outside of ASCII to save
+    /// on VRAM.
+    pub const DEFAULT: Self = {
+        let per_row = 128 / DEFAULT_SIZE;
+        let mut glyphs = [Glyph::MISSING; 256];
+        let mut code = b' ';
+        while code < 0x80 {
+            let idx = code - b' ';
+            let offset = TexCoord {
+                x: (idx % per_row) * DEFAULT_SIZE,
+                y: (idx / per_row) * DEFAULT_SIZE,
+            };
+            glyphs[code as usize] = Glyph::new(offset, DEFAULT_SIZE, DEFAULT_SIZE, DEFAULT_SIZE);
+            code += 1;
+        }
+        Font::new(glyphs, &[], DEFAULT_SIZE)
+    };
+
+    /// Creates a font with glyphs indexed by Latin-1 code, the given kerning
+    /// pairs and distance between lines.
+    pub const fn new(glyphs: [Glyph; 256], kerning: &'static [Kerning], height: u8) -> Self {
+        Font {
+            glyphs,
+            kerning,
+            height,
+        }
+    }
+
+    /// Gets the distance between lines.
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// Gets the glyph for the Latin-1 character `code` if it's in the font.
+    pub fn glyph(&self, code: u8) -> Option<&Glyph> {
+        let glyph = &self.glyphs[code as usize];
+        if glyph.is_missing() {
+            None
+        } else {
+            Some(glyph)
+        }
+    }
+
+    /// Gets the kerning adjustment between the Latin-1 characters `left` and
+    /// `right`.
+    pub fn kerning(&self, left: u8, right: u8) -> i8 {
+        self.kerning
+            .iter()
+            .find(|k| k.left == left && k.right == right)
+            .map(|k| k.adjust)
+            .unwrap_or(0)
+    }
+
+    /// Gets the width of a single line of Latin-1 text.
+    ///
+    /// Characters which aren't in the font are not counted.
+    pub fn text_width(&self, text: &[u8]) -> i16 {
+        let mut width = 0;
+        let mut prev = None;
+        for &code in text {
+            if let Some(glyph) = self.glyph(code) {
+                if let Some(prev) = prev {
+                    width += self.kerning(prev, code) as i16;
+                }
+                width += glyph.advance as i16;
+                prev = Some(code);
+            }
+        }
+        width
+    }
+}
+
+/// Maps a character to its Latin-1 code.
+///
+/// Characters outside of Latin-1 are mapped to a similar character if there's
+/// an obvious one, for example curly quotes are mapped to straight quotes.
+pub fn to_latin1(c: char) -> Option<u8> {
+    match c {
+        '\u{0}'..='\u{FF}' => Some(c as u8),
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => Some(b'\''),
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => Some(b'"'),
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => Some(b'-'),
+        '\u{2022}' => Some(0xB7),
+        '\u{2039}' => Some(b'<'),
+        '\u{203A}' => Some(b'>'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_latin1, Font, Glyph, Kerning};
+    use crate::gpu::TexCoord;
+
+    #[test_case]
+    fn default_font() {
+        let font = Font::DEFAULT;
+        assert!(font.glyph(b'\n').is_none());
+        assert!(font.glyph(0xE9).is_none());
+        let a = font.glyph(b'A').unwrap();
+        assert!(a.offset == TexCoord { x: 8, y: 16 });
+        assert!(font.text_width(b"Hello") == 40);
+    }
+
+    #[test_case]
+    fn kerning() {
+        const KERNING: [Kerning; 1] = [Kerning {
+            left: b'A',
+            right: b'V',
+            adjust: -2,
+        }];
+        let mut glyphs = [Glyph::MISSING; 256];
+        glyphs[b'A' as usize] = Glyph::new(TexCoord { x: 0, y: 0 }, 7, 9, 8);
+        glyphs[b'V' as usize] = Glyph::new(TexCoord { x: 8, y: 0 }, 7, 9, 8);
+        let font = Font::new(glyphs, &KERNING, 10);
+        assert!(font.kerning(b'A', b'V') == -2);
+        assert!(font.kerning(b'V', b'A') == 0);
+        assert!(font.text_width(b"AVA") == 22);
+    }
+
+    #[test_case]
+    fn latin1() {
+        assert!(to_latin1('a') == Some(b'a'));
+        assert!(to_latin1('é') == Some(0xE9));
+        assert!(to_latin1('ß') == Some(0xDF));
+        assert!(to_latin1('\u{2019}') == Some(b'\''));
+        assert!(to_latin1('€').is_none());
+    }
+}
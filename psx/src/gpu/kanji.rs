@@ -0,0 +1,284 @@
+use crate::framebuffer::Framebuffer;
+use crate::gpu::env::DrawMode;
+use crate::gpu::primitives::Sprt16;
+use crate::gpu::{Bpp, Clut, Color, TexColor, TexCoord, TexPage, VRAMRegion, Vertex};
+use crate::hw::Register;
+use crate::sys::kanji::{self, Glyph, GLYPH_HEIGHT, GLYPH_WIDTH, QUESTION_MARK, SPACE};
+
+// Each 4-bit glyph is stored in a 4x16 halfword slot
+const SLOT_WIDTH: i16 = (GLYPH_WIDTH / 4) as i16;
+const SLOT_HEIGHT: i16 = 16;
+// Every code the BIOS has a glyph for has a non-zero lead byte
+const EMPTY: u16 = 0;
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    code: u16,
+    last_used: u32,
+}
+
+/// A cache of up to `N` Kanji ROM glyphs in VRAM.
+///
+/// Glyphs are converted to 4-bit textures and uploaded as they're needed,
+/// replacing the least recently used glyph when the cache is full. The cache
+/// doesn't track the lifetime of its VRAM regions so it's the user's
+/// responsibility to keep them allocated while the cache is in use.
+#[derive(Debug)]
+pub struct KanjiCache<const N: usize> {
+    offset: Vertex,
+    columns: i16,
+    len: usize,
+    tex_page: TexPage,
+    tex_coord: TexCoord,
+    clut: Clut,
+    slots: [Slot; N],
+    clock: u32,
+}
+
+impl<const N: usize> KanjiCache<N> {
+    /// Creates a glyph cache in `bmp` and uploads its CLUT to `clut`.
+    ///
+    /// `bmp` should be allocated for a 4-bit texture and each glyph takes 4x16
+    /// halfwords of it. Only the part of `bmp` within its texture page is used
+    /// since glyphs are addressed by texture coordinates. Returns `None` if
+    /// `bmp` can't hold any glyphs or `clut` isn't a valid CLUT.
+    pub fn new<const B: usize>(
+        fb: &mut Framebuffer<B>, bmp: &VRAMRegion, clut: &VRAMRegion,
+    ) -> Option<Self> {
+        let cache = Self::with_region(
+            bmp.offset(),
+            bmp.size(),
+            bmp.tex_page(),
+            bmp.tex_coord(Bpp::Bits4),
+            clut.clut()?,
+        )?;
+        // Index 0 is transparent and index 1 is white to be blended with the
+        // text color.
+        let mut palette = [0; 8];
+        palette[0] = 0x7FFF << 16;
+        fb.draw_sync();
+        fb.gp0.copy_to_vram(clut.offset(), Vertex(16, 1));
+        for word in palette {
+            fb.gp0.assign(word).store();
+        }
+        Some(cache)
+    }
+
+    fn with_region(
+        offset: Vertex, size: Vertex, tex_page: TexPage, tex_coord: TexCoord, clut: Clut,
+    ) -> Option<Self> {
+        // Slots are 16x16 texels and those past the end of the texture page
+        // can't be addressed
+        let tex_slots = |start: u8| (256 - start as i16) / 16;
+        let columns = (size.0 / SLOT_WIDTH).min(tex_slots(tex_coord.x));
+        let rows = (size.1 / SLOT_HEIGHT).min(tex_slots(tex_coord.y));
+        let len = (columns.max(0) as usize * rows.max(0) as usize).min(N);
+        if len == 0 {
+            return None
+        }
+        Some(KanjiCache {
+            offset,
+            columns,
+            len,
+            tex_page,
+            tex_coord,
+            clut,
+            slots: [Slot {
+                code: EMPTY,
+                last_used: 0,
+            }; N],
+            clock: 0,
+        })
+    }
+
+    /// Gets the number of glyphs the cache can hold.
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Removes all glyphs from the cache.
+    pub fn clear(&mut self) -> &mut Self {
+        for slot in &mut self.slots {
+            slot.code = EMPTY;
+        }
+        self
+    }
+
+    /// Gets the slot to use for `code` and whether the glyph is already there.
+    fn find(&mut self, code: u16) -> (usize, bool) {
+        self.clock = self.clock.wrapping_add(1);
+        let slots = &mut self.slots[..self.len];
+        let (idx, cached) = match slots.iter().position(|slot| slot.code == code) {
+            Some(idx) => (idx, true),
+            None => {
+                // Empty slots are never used so they're picked first
+                let idx = slots
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, slot)| (slot.code != EMPTY, slot.last_used))
+                    .map(|(idx, _)| idx)
+                    .unwrap_or(0);
+                (idx, false)
+            },
+        };
+        slots[idx] = Slot {
+            code,
+            last_used: self.clock,
+        };
+        (idx, cached)
+    }
+
+    fn slot_offset(&self, idx: usize) -> Vertex {
+        let idx = idx as i16;
+        let col = idx % self.columns;
+        let row = idx / self.columns;
+        Vertex(col * SLOT_WIDTH, row * SLOT_HEIGHT)
+    }
+
+    fn slot_tex_coord(&self, idx: usize) -> TexCoord {
+        let Vertex(x, y) = self.slot_offset(idx);
+        TexCoord {
+            x: self.tex_coord.x + (x * 4) as u8,
+            y: self.tex_coord.y + y as u8,
+        }
+    }
+
+    /// Gets the slot containing the glyph for `code`, uploading it if
+    /// necessary.
+    ///
+    /// Returns `None` if the character is not in the Kanji ROM.
+    fn load<const B: usize>(&mut self, fb: &mut Framebuffer<B>, code: u16) -> Option<usize> {
+        let cached = self.slots[..self.len].iter().any(|slot| slot.code == code);
+        let glyph = if cached {
+            None
+        } else {
+            Some(Glyph::new(code)?)
+        };
+        let (idx, _) = self.find(code);
+        let glyph = match glyph {
+            Some(glyph) => glyph,
+            None => return Some(idx),
+        };
+        fb.draw_sync();
+        fb.gp0.copy_to_vram(
+            self.offset + self.slot_offset(idx),
+            Vertex(SLOT_WIDTH, SLOT_HEIGHT),
+        );
+        for y in 0..SLOT_HEIGHT as usize {
+            let row = if y < GLYPH_HEIGHT { glyph.row(y) } else { 0 };
+            for word in expand_row(row) {
+                fb.gp0.assign(word).store();
+            }
+        }
+        Some(idx)
+    }
+
+    /// Draws the Shift-JIS string `text` with its upper-left corner at `pos`.
+    ///
+    /// Printable ASCII is drawn with the equivalent full-width characters and
+    /// `'\n'` starts a new line. Characters which aren't in the Kanji ROM are
+    /// drawn as a full-width `'？'`. This changes the draw mode to the cache's
+    /// texture page.
+    pub fn draw<const B: usize>(
+        &mut self, fb: &mut Framebuffer<B>, text: &[u8], pos: Vertex, color: Color,
+    ) {
+        let mut draw_mode = DrawMode::new();
+        draw_mode.set_tex_page(self.tex_page).set_bpp(Bpp::Bits4);
+        fb.draw_sync();
+        fb.gp0.send_command(&draw_mode);
+
+        let mut sprt = Sprt16::new();
+        sprt.set_clut(self.clut).set_color(TexColor::from(color));
+        let mut cursor = pos;
+        for code in kanji::chars(text) {
+            if code == b'\n' as u16 {
+                cursor = Vertex(pos.0, cursor.1 + SLOT_HEIGHT);
+                continue
+            }
+            let code = if code < 0x80 {
+                kanji::to_full_width(code as u8).unwrap_or(QUESTION_MARK)
+            } else {
+                code
+            };
+            if code != SPACE {
+                let idx = self.load(fb, code).or_else(|| self.load(fb, QUESTION_MARK));
+                if let Some(idx) = idx {
+                    sprt.set_offset(cursor)
+                        .set_tex_coord(self.slot_tex_coord(idx));
+                    fb.draw_sync();
+                    fb.gp0.send_command(&sprt);
+                }
+            }
+            cursor.0 += GLYPH_WIDTH as i16;
+        }
+    }
+}
+
+/// Converts a row of a 1-bit glyph to 4-bit texels using CLUT indices 0 and 1.
+fn expand_row(row: u16) -> [u32; 2] {
+    let mut words = [0; 2];
+    for x in 0..GLYPH_WIDTH {
+        if row & (0x8000 >> x) != 0 {
+            words[x / 8] |= 1 << ((x % 8) * 4);
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_row, KanjiCache};
+    use crate::gpu::{Clut, TexCoord, TexPage, Vertex};
+
+    fn with_region<const N: usize>(size: Vertex, tex_coord: TexCoord) -> Option<KanjiCache<N>> {
+        KanjiCache::with_region(
+            Vertex(320, 256),
+            size,
+            TexPage::try_from(Vertex(5, 1)).unwrap(),
+            tex_coord,
+            Clut::try_from(Vertex(0, 500)).unwrap(),
+        )
+    }
+
+    fn new_cache<const N: usize>() -> KanjiCache<N> {
+        with_region(Vertex(8, 32), TexCoord { x: 0, y: 0 }).unwrap()
+    }
+
+    #[test_case]
+    fn expand() {
+        assert!(expand_row(0x8001) == [0x0000_0001, 0x1000_0000]);
+        assert!(expand_row(0x0F00) == [0x1111_0000, 0]);
+    }
+
+    #[test_case]
+    fn slots() {
+        let cache = new_cache::<8>();
+        assert!(cache.capacity() == 4);
+        assert!(cache.slot_offset(3) == Vertex(4, 16));
+        assert!(cache.slot_tex_coord(3) == TexCoord { x: 16, y: 16 });
+        assert!(new_cache::<2>().capacity() == 2);
+    }
+
+    #[test_case]
+    fn texture_page_bounds() {
+        // Only 2x1 slots fit between (224, 240) and the end of the texture page
+        let cache = with_region::<16>(Vertex(64, 64), TexCoord { x: 224, y: 240 }).unwrap();
+        assert!(cache.capacity() == 2);
+        assert!(cache.slot_tex_coord(1) == TexCoord { x: 240, y: 240 });
+        assert!(with_region::<16>(Vertex(64, 64), TexCoord { x: 248, y: 0 }).is_none());
+    }
+
+    #[test_case]
+    fn least_recently_used() {
+        let mut cache = new_cache::<3>();
+        assert!(cache.find(0x8260) == (0, false));
+        assert!(cache.find(0x8261) == (1, false));
+        assert!(cache.find(0x8262) == (2, false));
+        assert!(cache.find(0x8260) == (0, true));
+        // 0x8261 is the least recently used glyph
+        assert!(cache.find(0x8263) == (1, false));
+        assert!(cache.find(0x8262) == (2, true));
+        assert!(cache.find(0x8260) == (0, true));
+        assert!(cache.find(0x8264) == (1, false));
+    }
+}
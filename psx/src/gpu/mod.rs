@@ -1,14 +1,39 @@
 //! GPU types
+use crate::gpu::env::DrawMode;
 use crate::hw::gpu::GP0Command;
 
+mod clip;
 /// Predefined colors
 pub mod colors;
+pub mod decode;
+mod effects;
+pub mod env;
+mod font;
+mod kanji;
+mod ordering_table;
 mod packet;
+mod primitive_buffer;
 /// GPU primitives implementing [`GP0Command`].
 pub mod primitives;
+pub mod raster;
+mod sprite;
+mod subdivide;
+mod tilemap;
 mod vertex;
+mod vram;
 
+pub use clip::{ClipRect, Visibility, MAX_POLY_HEIGHT, MAX_POLY_WIDTH};
+pub use effects::{Fade, Letterbox};
+pub use font::{to_latin1, Font, Glyph, Kerning};
+pub use kanji::KanjiCache;
+pub use ordering_table::OrderingTable;
 pub use packet::{link_list, ordering_table};
+pub use primitive_buffer::{DoubleBuffer, PrimitiveBuffer};
+pub use sprite::{Flip, Frame};
+pub use subdivide::MAX_SUBDIVISIONS;
+pub use tilemap::{MapTile, TileSize, Tilemap};
+pub(crate) use vram::page_tex_coord;
+pub use vram::{VRAMAllocator, VRAMRegion};
 
 type Command = u8;
 
@@ -47,6 +72,15 @@ pub enum VertexError {
     InvalidY,
 }
 
+/// Error for conversions from GP0 command words.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CommandError {
+    /// The word's command byte doesn't match the expected command.
+    InvalidCommand,
+    /// The word's parameters contain a reserved value.
+    InvalidParameter,
+}
+
 // This is conceptually a vector since it encodes a direction, but that term is
 // overloaded so let's call it a `Vertex`.
 /// An (x, y) tuple representing a vector in VRAM.
@@ -112,6 +146,42 @@ pub enum VideoMode {
     PAL,
 }
 
+impl VideoMode {
+    /// Detects the console's video mode.
+    ///
+    /// This uses the region feature if one is enabled, otherwise it checks the
+    /// video mode bit in GPUSTAT. The BIOS sets this bit for the console's
+    /// region at boot, so this should be called before the GPU's display mode
+    /// is changed. Use
+    /// [`Framebuffer::with_mode`][crate::Framebuffer::with_mode]
+    /// to choose the mode explicitly instead.
+    pub fn detect() -> Self {
+        if cfg!(feature = "EU_region") {
+            return VideoMode::PAL
+        }
+        if cfg!(any(feature = "NA_region", feature = "J_region")) {
+            return VideoMode::NTSC
+        }
+        crate::hw::gpu::Status::new().video_mode()
+    }
+
+    /// Gets the number of visible lines in each field.
+    pub const fn lines(&self) -> i16 {
+        match self {
+            VideoMode::NTSC => 240,
+            VideoMode::PAL => 256,
+        }
+    }
+
+    /// Gets the scanline at the center of the visible vertical range.
+    const fn vertical_center(&self) -> i16 {
+        match self {
+            VideoMode::NTSC => 0x88,
+            VideoMode::PAL => 0xA3,
+        }
+    }
+}
+
 /// Color depth.
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Depth {
@@ -121,6 +191,19 @@ pub enum Depth {
     Bits24,
 }
 
+impl Depth {
+    /// Gets the number of VRAM halfwords taken by `width` pixels at this color
+    /// depth.
+    ///
+    /// Each 24-bit pixel takes 1.5 halfwords so this rounds up for odd widths.
+    pub const fn vram_width(&self, width: i16) -> i16 {
+        match self {
+            Depth::Bits15 => width,
+            Depth::Bits24 => (width * 3 + 1) / 2,
+        }
+    }
+}
+
 /// Bits per pixel.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Bpp {
@@ -132,6 +215,33 @@ pub enum Bpp {
     Bits15,
 }
 
+/// Semi-transparency blending mode.
+///
+/// This specifies how a semi-transparent foreground pixel `F` is combined with
+/// the background pixel `B` in VRAM.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    /// `B/2 + F/2`
+    Average = 0,
+    /// `B + F`
+    Add,
+    /// `B - F`
+    Subtract,
+    /// `B + F/4`
+    AddQuarter,
+}
+
+impl BlendMode {
+    pub(crate) const fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            0 => BlendMode::Average,
+            1 => BlendMode::Add,
+            2 => BlendMode::Subtract,
+            _ => BlendMode::AddQuarter,
+        }
+    }
+}
+
 /// A physical address in memory.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -142,8 +252,8 @@ pub struct PhysAddr([u8; 3]);
 ///
 /// This is essentially a `T` with a pointer to the next packet in the linked
 /// list, if any. Newly created `Packet`s always point to the end of the list.
-/// To link an array of `Packet`s together use [`link_list`] or the [`dma::OTC`
-/// channel][`crate::dma::OTC`]. Note that linked `Packet`s don't have to be
+/// To link an array of `Packet`s together use [`link_list`] or insert them
+/// into an [`OrderingTable`]. Note that linked `Packet`s don't have to be
 /// contiguous in memory and that [`Packet::insert_packet`] may be used for more
 /// fine-grained control over packet linking.
 ///
@@ -185,17 +295,46 @@ pub struct DispEnv {
 }
 
 impl DispEnv {
-    /// Creates a new display buffer at `offset` in VRAM with the specified
-    /// `size`.
+    /// Creates a new NTSC display buffer at `offset` in VRAM with the
+    /// specified `size`.
     pub fn new(offset: (i16, i16), size: (i16, i16)) -> Result<Self, VertexError> {
+        Self::with_mode(offset, size, VideoMode::NTSC)
+    }
+
+    /// Creates a new display buffer at `offset` in VRAM with the specified
+    /// `size` and video mode.
+    ///
+    /// The horizontal range matches the width for the GPU's horizontal
+    /// resolutions of 256, 320, 368, 512 and 640. Like [`DispEnv::new`] always
+    /// has, other widths are accepted and use the 320-pixel dot clock's
+    /// timing. Heights larger than a single field are displayed interlaced.
+    pub fn with_mode(
+        offset: (i16, i16), size: (i16, i16), mode: VideoMode,
+    ) -> Result<Self, VertexError> {
         let offset = Vertex::new(offset);
         let size = Vertex::new(size);
         let offset = PackedVertex::try_from(offset)?;
-        let ntsc_vrange = Vertex(0x88 - (240 / 2), 0x88 + (240 / 2));
-        let hrange = Vertex(0x260, 0x260 + (size.0 * 8));
+        // The horizontal range is in GPU clock cycles and each pixel takes a
+        // different number of cycles depending on the dot clock
+        let cycles_per_pixel = match size.0 {
+            256 => 10,
+            320 => 8,
+            368 => 7,
+            512 => 5,
+            640 => 4,
+            _ => 8,
+        };
+        let hrange = Vertex(0x260, 0x260 + (size.0 * cycles_per_pixel));
+        let lines = if size.1 > mode.lines() {
+            size.1 / 2
+        } else {
+            size.1
+        };
+        let center = mode.vertical_center();
+        let vrange = Vertex(center - (lines / 2), center + (lines / 2));
 
         let horizontal_range = PackedVertex::try_from(hrange)?;
-        let vertical_range = PackedVertex::try_from(ntsc_vrange)?;
+        let vertical_range = PackedVertex::try_from(vrange)?;
         Ok(DispEnv {
             horizontal_range,
             vertical_range,
@@ -204,13 +343,46 @@ impl DispEnv {
     }
 }
 
-/// Draw buffer parameters.
+// The largest number of lines cleared by a single VRAM fill or rectangle. Both
+// only use 9 bits for the height so 512-line buffers are cleared in two parts.
+const CLEAR_LINES: i16 = 256;
+
+// A VRAM fill or rectangle which clears part of a draw buffer.
 #[repr(C)]
 #[derive(Debug)]
+struct Clear {
+    color: Color,
+    cmd: Command,
+    offset: Vertex,
+    size: Vertex,
+}
+
+impl Clear {
+    const NOP: Command = 0x00;
+    const FILL: Command = 0x02;
+    const RECT: Command = 0x60;
+
+    // Empty clears are replaced with NOPs
+    fn set(&mut self, cmd: Command, offset: Vertex, size: Vertex) {
+        // The parameters must also be NOPs since their upper bytes may look
+        // like other commands
+        let (cmd, offset, size) = if cmd != Self::NOP && size.1 > 0 {
+            (cmd, offset, size)
+        } else {
+            (Self::NOP, Vertex(0, 0), Vertex(0, 0))
+        };
+        self.cmd = cmd;
+        self.offset = offset;
+        self.size = size;
+    }
+}
+
+/// Draw buffer parameters.
+#[repr(C, align(4))]
+#[derive(Debug)]
 pub struct DrawEnv {
-    texpage: u16,
-    _pad: u8,
-    texpage_cmd: Command,
+    /// The buffer's draw mode.
+    pub draw_mode: DrawMode,
 
     upper_left: PackedVertex<3, 10, 9>,
     upper_left_cmd: Command,
@@ -221,11 +393,7 @@ pub struct DrawEnv {
     offset: PackedVertex<3, 11, 11>,
     offset_cmd: Command,
 
-    /// The buffer's background color.
-    pub bg_color: Color,
-    bg_color_cmd: Command,
-    bg_offset: Vertex,
-    bg_size: Vertex,
+    bg: [Clear; 2],
 }
 
 impl DrawEnv {
@@ -238,26 +406,105 @@ impl DrawEnv {
         let size = Vertex::new(size);
         let bg_color = bg_color.unwrap_or(colors::BLACK);
         let upper_left = PackedVertex::try_from(offset)?;
-        let lower_right = PackedVertex::try_from(offset + size)?;
-        Ok(DrawEnv {
-            texpage_cmd: 0xE1,
+        // The lower-right corner is inclusive so this fits a buffer ending at
+        // the bottom of VRAM
+        let lower_right = PackedVertex::try_from(offset + size - Vertex(1, 1))?;
+        let mut draw_mode = DrawMode::new();
+        draw_mode
+            .set_tex_page(TexPage::try_from(Vertex(10, 0))?)
+            .set_draw_to_display(true);
+        let clear = || Clear {
+            color: bg_color,
+            cmd: Clear::NOP,
+            offset: Vertex(0, 0),
+            size: Vertex(0, 0),
+        };
+        let mut env = DrawEnv {
+            draw_mode,
             upper_left_cmd: 0xE3,
             lower_right_cmd: 0xE4,
             offset_cmd: 0xE5,
-            bg_color_cmd: 0x02,
-            texpage: (1 << 10) | 10,
 
             upper_left,
             lower_right,
 
             offset: PackedVertex::try_from(offset)?,
 
-            bg_color,
-            bg_offset: offset,
-            bg_size: size,
+            bg: [clear(), clear()],
+        };
+        env.set_clear(true);
+        Ok(env)
+    }
+}
 
-            _pad: 0,
-        })
+impl DrawEnv {
+    /// Gets the offset and size of the draw buffer in VRAM.
+    pub(crate) fn area(&self) -> (Vertex, Vertex) {
+        let upper_left = Vertex::from(self.upper_left);
+        let size = Vertex::from(self.lower_right) - upper_left + Vertex(1, 1);
+        (upper_left, size)
+    }
+
+    /// Prohibits drawing to the lines of the field being displayed.
+    ///
+    /// This also clears the background with rectangles instead of VRAM fills
+    /// since only the former skip the displayed lines.
+    pub(crate) fn mask_display(&mut self) -> &mut Self {
+        self.draw_mode.set_draw_to_display(false);
+        if self.get_clear() {
+            self.set_bg(Clear::RECT);
+        }
+        self
+    }
+
+    /// Sets the commands which clear the background, at most `CLEAR_LINES`
+    /// lines at a time.
+    fn set_bg(&mut self, cmd: Command) {
+        let (offset, size) = self.area();
+        // Rectangles are relative to the drawing offset
+        let offset = if cmd == Clear::RECT {
+            Vertex(0, 0)
+        } else {
+            offset
+        };
+        let top = size.1.min(CLEAR_LINES);
+        self.bg[0].set(cmd, offset, Vertex(size.0, top));
+        self.bg[1].set(cmd, offset + Vertex(0, top), Vertex(size.0, size.1 - top));
+    }
+
+    /// Gets the buffer's background color.
+    pub fn get_bg_color(&self) -> Color {
+        self.bg[0].color
+    }
+
+    /// Sets the buffer's background color.
+    pub fn set_bg_color(&mut self, color: Color) -> &mut Self {
+        for bg in &mut self.bg {
+            bg.color = color;
+        }
+        self
+    }
+
+    /// Checks if the buffer is cleared to the background color.
+    pub fn get_clear(&self) -> bool {
+        self.bg[0].cmd != Clear::NOP
+    }
+
+    /// Enables or disables clearing the buffer to the background color.
+    ///
+    /// Skipping the clear saves GPU time when the scene covers the whole
+    /// buffer. The clear is replaced with NOPs so the size of the environment
+    /// doesn't change. Buffers taller than 256 lines are cleared in two parts.
+    pub fn set_clear(&mut self, clear: bool) -> &mut Self {
+        let cmd = if !clear {
+            Clear::NOP
+        } else if self.draw_mode.get_draw_to_display() {
+            Clear::FILL
+        } else {
+            Clear::RECT
+        };
+        self.set_bg(cmd);
+        self
     }
 }
 
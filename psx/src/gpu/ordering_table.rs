@@ -0,0 +1,123 @@
+use crate::dma;
+use crate::dma::LinkedList;
+use crate::gpu::Packet;
+use core::slice;
+
+/// A depth-sorted ordering table with `N` entries.
+///
+/// Each entry is an empty [`Packet`] which marks the start of the bucket of
+/// packets at a given depth. The table is linked from its last entry to its
+/// first so packets inserted at larger depths are sent first when the table is
+/// sent over the [`dma::GPU` channel][`crate::dma::GPU`]. Packets inserted in
+/// the same bucket are sent in the reverse order of insertion.
+///
+/// The table must be cleared with the [`dma::OTC` channel][`crate::dma::OTC`]
+/// before inserting packets each frame. Like [`Packet`], this does not track
+/// the lifetimes of the inserted packets so it's the user's responsibility to
+/// ensure they remain valid until the table is sent.
+#[repr(C)]
+#[derive(Debug)]
+pub struct OrderingTable<const N: usize> {
+    entries: [Packet<()>; N],
+}
+
+impl<const N: usize> OrderingTable<N> {
+    const EMPTY: Packet<()> = Packet::new(());
+
+    const VALID_LEN: () = {
+        if N == 0 {
+            panic!("Ordering tables must have at least one entry");
+        }
+        if N > 0x1_0000 {
+            panic!("Ordering table is too large to be cleared by the OTC DMA channel");
+        }
+    };
+
+    /// Creates a new ordering table.
+    ///
+    /// The table's entries are not linked until it's cleared.
+    #[allow(path_statements)]
+    pub const fn new() -> Self {
+        Self::VALID_LEN;
+        OrderingTable {
+            entries: [Self::EMPTY; N],
+        }
+    }
+
+    /// Clears the ordering table using the OTC DMA channel.
+    ///
+    /// This unlinks all previously inserted packets and links the entries from
+    /// last to first.
+    pub fn clear(&mut self, otc: &mut dma::OTC) -> &mut Self {
+        let ptr = self.entries.as_mut_ptr() as *mut u32;
+        // SAFETY: `Packet<()>` only contains a 4-byte header.
+        let words = unsafe { slice::from_raw_parts_mut(ptr, N) };
+        // SAFETY: `VALID_LEN` ensures the table fits in a single DMA block.
+        unsafe { otc.clear(words).unwrap_unchecked() };
+        self
+    }
+
+    /// Inserts `packet` at the start of the bucket at depth `z`.
+    ///
+    /// Returns `None` if `z` is not a valid depth for this table.
+    pub fn insert<T>(&mut self, z: usize, packet: &mut Packet<T>) -> Option<&mut Self> {
+        self.entries.get_mut(z)?.insert_packet(packet);
+        Some(self)
+    }
+
+    /// Sends the ordering table through the GPU DMA channel and call `f` while
+    /// the transfer completes.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value.
+    pub fn send_and<F: FnOnce() -> R, R>(&self, gpu_dma: &mut dma::GPU, f: F) -> R {
+        gpu_dma.send_list_and(self, f)
+    }
+
+    /// Sends the ordering table through the GPU DMA channel.
+    pub fn send(&self, gpu_dma: &mut dma::GPU) {
+        gpu_dma.send_list(self)
+    }
+}
+
+impl<const N: usize> LinkedList for OrderingTable<N> {
+    fn address(&self) -> Option<&u32> {
+        self.entries.last().map(|p| p.header_address())
+    }
+}
+
+// These tests clear the ordering table with DMA
+#[cfg(all(test, target_arch = "mips"))]
+mod tests {
+    use super::OrderingTable;
+    use crate::dma;
+    use crate::gpu::primitives::Tile1;
+    use crate::gpu::Packet;
+
+    fn address<T>(t: &T) -> u32 {
+        t as *const T as u32 & 0x00FF_FFFF
+    }
+
+    #[test_case]
+    fn clear() {
+        let mut ot = OrderingTable::<16>::new();
+        ot.clear(&mut dma::OTC::new());
+        assert!(ot.entries[0].header() == 0x00FF_FFFF);
+        for i in 1..16 {
+            assert!(ot.entries[i].header() == address(&ot.entries[i - 1]));
+        }
+    }
+
+    #[test_case]
+    fn insert() {
+        let mut ot = OrderingTable::<16>::new();
+        let mut a = Packet::new(Tile1::new());
+        let mut b = Packet::new(Tile1::new());
+        ot.clear(&mut dma::OTC::new());
+        assert!(ot.insert(16, &mut a).is_none());
+        ot.insert(4, &mut a).unwrap().insert(4, &mut b).unwrap();
+        assert!(ot.entries[4].header() == address(&b));
+        assert!(b.header() & 0x00FF_FFFF == address(&a));
+        assert!(a.header() & 0x00FF_FFFF == address(&ot.entries[3]));
+    }
+}
@@ -3,7 +3,7 @@ use crate::gpu::GPU_BUFFER_SIZE;
 use crate::gpu::{Packet, PhysAddr};
 use crate::hw::gpu::GP0Command;
 use core::hint::black_box;
-use core::mem::{size_of, transmute};
+use core::mem::{align_of, size_of, transmute};
 use core::slice;
 
 impl<'a, T> From<&'a mut T> for PhysAddr {
@@ -41,6 +41,15 @@ impl<T> Packet<T> {
         }
     };
 
+    pub(crate) const WORD_ALIGNED: () = {
+        if align_of::<Self>() != align_of::<u32>() {
+            panic!("Packet contents must not require more than 4-byte alignment.");
+        }
+    };
+
+    // The number of words the packet takes in a `PrimitiveBuffer`
+    pub(crate) const WORDS: usize = size_of::<Self>() / size_of::<u32>();
+
     /// Creates a new packet guaranteed to fit in the GPU buffer.
     #[allow(path_statements)]
     pub const fn new(t: T) -> Self {
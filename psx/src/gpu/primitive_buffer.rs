@@ -0,0 +1,200 @@
+use crate::dma;
+use crate::gpu::{OrderingTable, Packet};
+use crate::hw::gpu::GP0Command;
+
+/// A bump allocator for [`Packet`]s backed by `N` 32-bit words.
+///
+/// This hands out packets containing any [`GP0Command`] from a fixed region of
+/// memory which is reclaimed all at once with [`PrimitiveBuffer::reset`].
+/// Allocated packets are typically inserted into an [`OrderingTable`] which
+/// must be sent before the buffer is reset.
+#[repr(C)]
+#[derive(Debug)]
+pub struct PrimitiveBuffer<const N: usize> {
+    data: [u32; N],
+    next: usize,
+}
+
+impl<const N: usize> PrimitiveBuffer<N> {
+    /// Creates a new empty primitive buffer.
+    pub const fn new() -> Self {
+        PrimitiveBuffer {
+            data: [0; N],
+            next: 0,
+        }
+    }
+
+    /// Allocates a [`Packet`] containing `t` in the buffer.
+    ///
+    /// Returns `None` if the buffer doesn't have enough room left.
+    pub fn alloc<T: GP0Command>(&mut self, t: T) -> Option<&mut Packet<T>> {
+        #[allow(path_statements)]
+        Packet::<T>::WORD_ALIGNED;
+
+        let start = self.next;
+        let end = start + Packet::<T>::WORDS;
+        if end > N {
+            return None
+        }
+        self.next = end;
+        let ptr = self.data[start..end].as_mut_ptr() as *mut Packet<T>;
+        // SAFETY: The allocated words are in bounds, suitably aligned and aren't
+        // handed out again until the buffer is reset.
+        unsafe {
+            ptr.write(Packet::new(t));
+            ptr.as_mut()
+        }
+    }
+
+    /// Allocates a [`Packet`] containing `t` and inserts it into `ot` at depth
+    /// `z`.
+    ///
+    /// Returns `None` if `z` is not a valid depth for `ot` or if the buffer
+    /// doesn't have enough room left.
+    pub fn insert<T: GP0Command, const Z: usize>(
+        &mut self, ot: &mut OrderingTable<Z>, z: usize, t: T,
+    ) -> Option<&mut Packet<T>> {
+        if z >= Z {
+            return None
+        }
+        let packet = self.alloc(t)?;
+        ot.insert(z, packet);
+        Some(packet)
+    }
+
+    /// Reclaims all packets allocated in the buffer.
+    pub fn reset(&mut self) -> &mut Self {
+        self.next = 0;
+        self
+    }
+
+    /// Returns the number of unallocated words left in the buffer.
+    pub fn remaining(&self) -> usize {
+        N - self.next
+    }
+}
+
+/// A double-buffered pair of [`PrimitiveBuffer`]s and [`OrderingTable`]s.
+///
+/// One primitive buffer and ordering table are built while the others are
+/// sent to the GPU. The typical usage is to call [`DoubleBuffer::swap`] once at
+/// the start of each frame then build the next frame's packets in the closure
+/// passed to [`DoubleBuffer::send_and`].
+///
+/// `N` is the size of each primitive buffer in 32-bit words and `Z` is the
+/// number of entries in each ordering table.
+#[derive(Debug)]
+pub struct DoubleBuffer<const N: usize, const Z: usize> {
+    prims: [PrimitiveBuffer<N>; 2],
+    ots: [OrderingTable<Z>; 2],
+    swapped: bool,
+}
+
+impl<const N: usize, const Z: usize> DoubleBuffer<N, Z> {
+    /// Creates a new pair of primitive buffers and ordering tables.
+    pub const fn new() -> Self {
+        DoubleBuffer {
+            prims: [PrimitiveBuffer::new(), PrimitiveBuffer::new()],
+            ots: [OrderingTable::new(), OrderingTable::new()],
+            swapped: false,
+        }
+    }
+
+    /// Gets the primitive buffer and ordering table currently being built.
+    pub fn draw_list(&mut self) -> (&mut PrimitiveBuffer<N>, &mut OrderingTable<Z>) {
+        let idx = self.swapped as usize;
+        (&mut self.prims[idx], &mut self.ots[idx])
+    }
+
+    /// Swaps the buffers, then resets the new primitive buffer and clears its
+    /// ordering table.
+    pub fn swap(&mut self, otc: &mut dma::OTC) -> &mut Self {
+        self.swapped = !self.swapped;
+        let (prims, ot) = self.draw_list();
+        prims.reset();
+        ot.clear(otc);
+        self
+    }
+
+    /// Sends the last completed ordering table through the GPU DMA channel and
+    /// calls `f` with the primitive buffer and ordering table for the next
+    /// one while the transfer completes.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value.
+    pub fn send_and<F, R>(&mut self, gpu_dma: &mut dma::GPU, f: F) -> R
+    where F: FnOnce(&mut PrimitiveBuffer<N>, &mut OrderingTable<Z>) -> R {
+        let [prims_a, prims_b] = &mut self.prims;
+        let [ot_a, ot_b] = &mut self.ots;
+        let (draw, disp) = if self.swapped {
+            ((prims_b, ot_b), ot_a)
+        } else {
+            ((prims_a, ot_a), ot_b)
+        };
+        disp.send_and(gpu_dma, || f(draw.0, draw.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrimitiveBuffer;
+    use crate::gpu::primitives::{PolyF3, Tile1};
+    use crate::gpu::OrderingTable;
+    use crate::gpu::Packet;
+    use crate::hw::gpu::GP0Command;
+    use core::mem::size_of;
+
+    #[test_case]
+    fn alloc() {
+        const TILE1_WORDS: usize = size_of::<Packet<Tile1>>() / size_of::<u32>();
+        let mut prims = PrimitiveBuffer::<{ 4 * TILE1_WORDS }>::new();
+        for _ in 0..4 {
+            let tile = prims.alloc(Tile1::new()).unwrap();
+            assert!(tile.contents.data() == Tile1::new().data());
+        }
+        assert!(prims.remaining() == 0);
+        assert!(prims.alloc(Tile1::new()).is_none());
+        prims.reset();
+        assert!(prims.remaining() == 4 * TILE1_WORDS);
+        assert!(prims.alloc(Tile1::new()).is_some());
+    }
+
+    #[test_case]
+    fn insert() {
+        let mut prims = PrimitiveBuffer::<64>::new();
+        let mut ot = OrderingTable::<8>::new();
+        assert!(prims.insert(&mut ot, 8, PolyF3::new()).is_none());
+        assert!(prims.remaining() == 64);
+        let tri = prims.insert(&mut ot, 0, PolyF3::new()).unwrap();
+        let header = tri.header();
+        assert!(header >> 24 == (size_of::<PolyF3>() / size_of::<u32>()) as u32);
+        // The uncleared entry's terminator is passed on to the packet
+        assert!(header & 0xFF_FFFF == 0xFF_FFFF);
+    }
+
+    // Walking the ordering table needs the console's 24-bit addresses
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn insert_order() {
+        use crate::dma;
+        use crate::gpu::decode::{Decoded, PacketIter};
+        use crate::gpu::Vertex;
+
+        let mut prims = PrimitiveBuffer::<64>::new();
+        let mut ot = OrderingTable::<8>::new();
+        ot.clear(&mut dma::OTC::new());
+        let mut tile = Tile1::new();
+        tile.set_offset(Vertex(1, 2));
+        assert!(prims.insert(&mut ot, 3, PolyF3::new()).is_some());
+        assert!(prims.insert(&mut ot, 3, tile).is_some());
+        assert!(prims.insert(&mut ot, 5, Tile1::new()).is_some());
+        // SAFETY: The ordering table and packets aren't modified while iterating.
+        let mut commands = unsafe { PacketIter::new(&ot) }.flat_map(|p| p.commands());
+        // Deeper buckets are sent first and each bucket is sent in the reverse
+        // order of insertion
+        assert!(commands.next() == Some(Decoded::Tile1(Tile1::new())));
+        assert!(commands.next() == Some(Decoded::Tile1(tile)));
+        assert!(commands.next() == Some(Decoded::PolyF3(PolyF3::new())));
+        assert!(commands.next().is_none());
+    }
+}
@@ -19,18 +19,95 @@ macro_rules! impl_primitive {
                 primitive.cmd = $cmd;
                 primitive
             }
+
+            /// Checks if the primitive is semi-transparent.
+            pub fn get_semi_transparent(&self) -> bool {
+                self.cmd & SEMI_TRANSPARENT != 0
+            }
+
+            /// Sets whether the primitive is semi-transparent.
+            ///
+            /// Semi-transparent primitives are blended with the draw buffer
+            /// using the blend mode in the texture page attribute for textured
+            /// polygons or the current draw mode otherwise.
+            pub fn set_semi_transparent(&mut self, semi_transparent: bool) -> &mut Self {
+                if semi_transparent {
+                    self.cmd |= SEMI_TRANSPARENT;
+                } else {
+                    self.cmd &= !SEMI_TRANSPARENT;
+                }
+                self
+            }
         }
         impl GP0Command for $name {}
     };
+    ($name:ident, $cmd:expr,textured) => {
+        impl_primitive!($name, $cmd);
+        impl $name {
+            /// Checks if the primitive's texture is drawn without blending it
+            /// with the primitive's color.
+            pub fn get_raw_texture(&self) -> bool {
+                self.cmd & RAW_TEXTURE != 0
+            }
+
+            /// Sets whether the primitive's texture is drawn without blending
+            /// it with the primitive's color.
+            pub fn set_raw_texture(&mut self, raw_texture: bool) -> &mut Self {
+                if raw_texture {
+                    self.cmd |= RAW_TEXTURE;
+                } else {
+                    self.cmd &= !RAW_TEXTURE;
+                }
+                self
+            }
+        }
+    };
+    // Poly-lines have a variable number of vertices so they define `empty` to
+    // create one with the terminator and `cmd` and `cmd_mut` to access their
+    // command byte
     ($name:ident < N > , $cmd:expr) => {
         impl<const N: usize> $name<N> {
+            const AT_LEAST_TWO_VERTICES: () = {
+                if N < 2 {
+                    panic!("Poly-lines must have at least two vertices.");
+                }
+            };
+
+            /// Resets a primitive's command.
+            ///
+            /// This is useful when a primitive is a variant of an untagged union which
+            /// must be set to `Self` without modifying its other fields.
+            pub const fn reset_cmd(&mut self) -> &mut Self {
+                *self.cmd_mut() = $cmd;
+                self
+            }
+
             /// Creates a new primitive
+            #[allow(path_statements)]
             pub const fn new() -> Self {
-                let buf = [0u8; size_of::<Self>()];
-                let mut primitive = unsafe { transmute::<_, Self>(buf) };
-                primitive.cmd = $cmd;
+                Self::AT_LEAST_TWO_VERTICES;
+                let mut primitive = Self::empty();
+                primitive.reset_cmd();
                 primitive
             }
+
+            /// Checks if the primitive is semi-transparent.
+            pub fn get_semi_transparent(&self) -> bool {
+                self.cmd() & SEMI_TRANSPARENT != 0
+            }
+
+            /// Sets whether the primitive is semi-transparent.
+            ///
+            /// Semi-transparent primitives are blended with the draw buffer
+            /// using the blend mode in the current draw mode.
+            pub fn set_semi_transparent(&mut self, semi_transparent: bool) -> &mut Self {
+                if semi_transparent {
+                    *self.cmd_mut() |= SEMI_TRANSPARENT;
+                } else {
+                    *self.cmd_mut() &= !SEMI_TRANSPARENT;
+                }
+                self
+            }
         }
         impl<const N: usize> GP0Command for $name<N> {}
     };
@@ -315,14 +392,145 @@ macro_rules! tex_page_fn {
     () => {
         /// Gets the primitive's texture page.
         pub fn get_tex_page(&self) -> TexPage {
-            self.tpage
+            let bits = u16::from_le_bytes(self.tpage.data) & TEX_PAGE_MASK;
+            TexPage {
+                data: bits.to_le_bytes(),
+            }
         }
 
         /// Sets the primitive's texture page.
+        ///
+        /// This doesn't change the primitive's blend mode.
         pub fn set_tex_page<T>(&mut self, tpage: T) -> &mut Self
         where TexPage: From<T> {
-            self.tpage = tpage.into();
+            let tpage = TexPage::from(tpage);
+            let attrs = u16::from_le_bytes(self.tpage.data) & !TEX_PAGE_MASK;
+            let bits = u16::from_le_bytes(tpage.data) & TEX_PAGE_MASK;
+            self.tpage.data = (attrs | bits).to_le_bytes();
+            self
+        }
+
+        /// Gets the blend mode used if the primitive is semi-transparent.
+        pub fn get_blend_mode(&self) -> BlendMode {
+            let bits = u16::from_le_bytes(self.tpage.data) >> BLEND_MODE_SHIFT;
+            BlendMode::from_bits(bits as u32)
+        }
+
+        /// Sets the blend mode used if the primitive is semi-transparent.
+        pub fn set_blend_mode(&mut self, blend_mode: BlendMode) -> &mut Self {
+            let bits = u16::from_le_bytes(self.tpage.data) & !(0b11 << BLEND_MODE_SHIFT);
+            let bits = bits | (blend_mode as u16) << BLEND_MODE_SHIFT;
+            self.tpage.data = bits.to_le_bytes();
             self
         }
+
+        /// Gets the primitive's texture colors.
+        pub fn get_bpp(&self) -> Bpp {
+            match (u16::from_le_bytes(self.tpage.data) >> BPP_SHIFT) & 0b11 {
+                0 => Bpp::Bits4,
+                1 => Bpp::Bits8,
+                _ => Bpp::Bits15,
+            }
+        }
+
+        /// Sets the primitive's texture colors.
+        pub fn set_bpp(&mut self, bpp: Bpp) -> &mut Self {
+            let bits = u16::from_le_bytes(self.tpage.data) & !(0b11 << BPP_SHIFT);
+            let bits = bits | (bpp as u16) << BPP_SHIFT;
+            self.tpage.data = bits.to_le_bytes();
+            self
+        }
+    };
+}
+
+macro_rules! clip_fn {
+    ($n:tt $(, $attr:ident)*) => {
+        /// Tests the polygon's vertices against `rect`.
+        pub fn get_visibility(&self, rect: &ClipRect) -> Visibility {
+            rect.test(&self.get_vertices())
+        }
+
+        /// Splits the polygon into pieces small enough for the GPU to draw,
+        /// calling `f` with each piece which is visible in `rect`.
+        ///
+        /// Pieces are made by repeatedly subdividing the polygon along the
+        /// midpoints of its edges, interpolating its colors and texcoords.
+        /// Hidden polygons produce no pieces and visible polygons are passed to
+        /// `f` unchanged.
+        pub fn split<F: FnMut(&Self)>(&self, rect: &ClipRect, mut f: F) {
+            // Flat-shaded, untextured polygons only have vertices to split
+            #[allow(unused_mut)]
+            let mut points = self.get_vertices().map(Point::new);
+            $(clip_attr!(get, $attr, self, points);)*
+            clip::split(points, rect, &mut |points: [Point; $n]| {
+                let mut poly = *self;
+                poly.set_vertices(points.map(|p| p.vertex));
+                $(clip_attr!(set, $attr, poly, points);)*
+                f(&poly);
+            });
+        }
+    };
+}
+
+macro_rules! clip_attr {
+    (get,colors, $poly:expr, $points:ident) => {
+        for (p, c) in $points.iter_mut().zip($poly.get_colors()) {
+            p.color = [c.red, c.green, c.blue];
+        }
+    };
+    (set,colors, $poly:expr, $points:ident) => {
+        for (p, c) in $points.iter().zip($poly.get_colors_mut()) {
+            [c.red, c.green, c.blue] = p.color;
+        }
+    };
+    (get,tex_coords, $poly:expr, $points:ident) => {
+        for (p, t) in $points.iter_mut().zip($poly.get_tex_coords()) {
+            p.tex_coord = t;
+        }
+    };
+    (set,tex_coords, $poly:expr, $points:ident) => {
+        for (p, t) in $points.iter().zip($poly.get_tex_coords_mut()) {
+            *t = p.tex_coord;
+        }
+    };
+}
+
+macro_rules! subdivide_fn {
+    ($($attr:ident),*) => {
+        /// Tessellates the quad into an `n`x`n` grid of smaller quads if any of
+        /// its vertices are closer than `threshold`, calling `f` with each
+        /// piece.
+        ///
+        /// `vertices` are the quad's untransformed vertices in the same order
+        /// as its texcoords and `project` maps them to screen vertices, for
+        /// example with [`GTE::rtps`][crate::gte::GTE::rtps]. The projected
+        /// depths are compared with `threshold`. The grid is interpolated
+        /// before projecting so each piece's texture is only warped within
+        /// the piece and each grid point is only projected once. `n` is capped
+        /// at [`MAX_SUBDIVISIONS`][crate::gpu::MAX_SUBDIVISIONS]. The quad's
+        /// colors and texcoords are interpolated across the grid and its
+        /// vertices are ignored. Quads that aren't close enough are projected
+        /// and passed to `f` as a single piece.
+        pub fn subdivide<P, F>(
+            &self, vertices: [[i16; 3]; 4], threshold: u16, n: usize, mut project: P, mut f: F,
+        ) where
+            P: FnMut([i16; 3]) -> ScreenVertex,
+            F: FnMut(&Self), {
+            let mut corners = [Point::new(Vertex(0, 0)); 4];
+            $(clip_attr!(get, $attr, self, corners);)*
+            subdivide::tessellate(
+                corners,
+                vertices,
+                threshold,
+                n,
+                &mut project,
+                &mut |points: [Point; 4]| {
+                    let mut poly = *self;
+                    poly.set_vertices(points.map(|p| p.vertex));
+                    $(clip_attr!(set, $attr, poly, points);)*
+                    f(&poly);
+                },
+            );
+        }
     };
 }
@@ -1,12 +1,31 @@
-use crate::gpu::{Clut, Color, Command, TexColor, TexCoord, TexPage, Vertex};
+use crate::gpu::clip::{self, ClipRect, Point, Visibility};
+use crate::gpu::subdivide;
+use crate::gpu::{BlendMode, Bpp, Clut, Color, Command, TexColor, TexCoord, TexPage, Vertex};
+use crate::gte::ScreenVertex;
 use crate::hw::gpu::GP0Command;
 use core::mem::{size_of, transmute};
 
 #[macro_use]
 mod macros;
 
+// Primitives are word-aligned since `GP0Command::data` reads them as words,
+// even when they aren't in a `Packet`. See `GP0Command` for details.
+
+// Flags in a primitive's command byte
+const RAW_TEXTURE: Command = 1 << 0;
+const SEMI_TRANSPARENT: Command = 1 << 1;
+
+// The bits of a polygon's texture page attribute holding the `TexPage` and the
+// offsets of its blend mode and texture colors
+const TEX_PAGE_MASK: u16 = 0x1F;
+const BLEND_MODE_SHIFT: u16 = 5;
+const BPP_SHIFT: u16 = 7;
+
+// The word after a poly-line's last vertex
+const POLY_LINE_END: u32 = 0x5555_5555;
+
 /// Flat-shaded, non-textured triangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyF3 {
     color: Color,
@@ -17,7 +36,7 @@ pub struct PolyF3 {
 }
 
 /// Flat-shaded, non-textured quad.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyF4 {
     color: Color,
@@ -29,7 +48,7 @@ pub struct PolyF4 {
 }
 
 /// Flat-shaded, textured triangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyFT3 {
     color: TexColor,
@@ -46,7 +65,7 @@ pub struct PolyFT3 {
 }
 
 /// Flat-shaded, textured quad.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyFT4 {
     color: TexColor,
@@ -66,7 +85,7 @@ pub struct PolyFT4 {
 }
 
 /// Gouraud-shaded, non-textured triangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyG3 {
     color0: Color,
@@ -81,7 +100,7 @@ pub struct PolyG3 {
 }
 
 /// Gouraud-shaded, non-textured quad.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyG4 {
     color0: Color,
@@ -99,7 +118,7 @@ pub struct PolyG4 {
 }
 
 /// Gouraud-shaded, textured triangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyGT3 {
     color0: TexColor,
@@ -120,7 +139,7 @@ pub struct PolyGT3 {
 }
 
 /// Gouraud-shaded, textured quad.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct PolyGT4 {
     color0: TexColor,
@@ -146,7 +165,7 @@ pub struct PolyGT4 {
 }
 
 /// Flat-shaded line.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LineF2 {
     color: Color,
@@ -156,7 +175,7 @@ pub struct LineF2 {
 }
 
 /// Flat-shaded poly-line.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LineFN<const N: usize> {
     color: Color,
@@ -166,7 +185,7 @@ pub struct LineFN<const N: usize> {
 }
 
 /// Gouraud-shaded line.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LineG2 {
     color0: Color,
@@ -181,12 +200,13 @@ pub struct LineG2 {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 struct ColoredVertex {
     c: Color,
-    _pad: u8,
+    // Only the first vertex's command byte is used
+    cmd: Command,
     v: Vertex,
 }
 
 /// Gouraud-shaded poly-line.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LineGN<const N: usize> {
     colored_vertices: [ColoredVertex; N],
@@ -194,7 +214,7 @@ pub struct LineGN<const N: usize> {
 }
 
 /// Monochrome rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Tile {
     color: Color,
@@ -204,7 +224,7 @@ pub struct Tile {
 }
 
 /// Monochrome 1x1 rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Tile1 {
     color: Color,
@@ -213,7 +233,7 @@ pub struct Tile1 {
 }
 
 /// Monochrome 8x8 rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Tile8 {
     color: Color,
@@ -222,7 +242,7 @@ pub struct Tile8 {
 }
 
 /// Monochrome 16x16 rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Tile16 {
     color: Color,
@@ -231,7 +251,7 @@ pub struct Tile16 {
 }
 
 /// Textured rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Sprt {
     color: TexColor,
@@ -243,7 +263,7 @@ pub struct Sprt {
 }
 
 /// Textured 8x8 rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Sprt8 {
     color: TexColor,
@@ -254,7 +274,7 @@ pub struct Sprt8 {
 }
 
 /// Textured 16x16 rectangle.
-#[repr(C)]
+#[repr(C, align(4))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Sprt16 {
     color: TexColor,
@@ -268,59 +288,157 @@ impl_primitive!(PolyF3, 0x20);
 impl PolyF3 {
     vertices_fn!(3);
     color_fn!();
+    clip_fn!(3);
 }
 impl_primitive!(PolyF4, 0x28);
 impl PolyF4 {
     vertices_fn!(4);
     color_fn!();
+    clip_fn!(4);
 }
-impl_primitive!(PolyFT3, 0x24);
+impl_primitive!(PolyFT3, 0x24, textured);
 impl PolyFT3 {
     vertices_fn!(3);
     color_fn!(textured);
     clut_fn!();
     tex_page_fn!();
     tex_coord_fn!(3);
+    clip_fn!(3, tex_coords);
 }
-impl_primitive!(PolyFT4, 0x2C);
+impl_primitive!(PolyFT4, 0x2C, textured);
 impl PolyFT4 {
     vertices_fn!(4);
     color_fn!(textured);
     clut_fn!();
     tex_page_fn!();
     tex_coord_fn!(4);
+    clip_fn!(4, tex_coords);
+    subdivide_fn!(tex_coords);
 }
 impl_primitive!(PolyG3, 0x30);
 impl PolyG3 {
     vertices_fn!(3);
     gouraud_fn!(3);
+    clip_fn!(3, colors);
 }
 impl_primitive!(PolyG4, 0x38);
 impl PolyG4 {
     vertices_fn!(4);
     gouraud_fn!(4);
+    clip_fn!(4, colors);
 }
-impl_primitive!(PolyGT3, 0x34);
+impl_primitive!(PolyGT3, 0x34, textured);
 impl PolyGT3 {
     vertices_fn!(3);
     gouraud_fn!(3, textured);
     clut_fn!();
     tex_page_fn!();
     tex_coord_fn!(3);
+    clip_fn!(3, colors, tex_coords);
 }
-impl_primitive!(PolyGT4, 0x3C);
+impl_primitive!(PolyGT4, 0x3C, textured);
 impl PolyGT4 {
     vertices_fn!(4);
     gouraud_fn!(4, textured);
     clut_fn!();
     tex_page_fn!();
     tex_coord_fn!(4);
+    clip_fn!(4, colors, tex_coords);
+    subdivide_fn!(colors, tex_coords);
 }
 impl_primitive!(LineF2, 0x40);
-//impl_primitive!(LineFN<N>, 0x48);
+impl_primitive!(LineFN<N>, 0x48);
+impl<const N: usize> LineFN<N> {
+    const fn empty() -> Self {
+        LineFN {
+            color: Color::new(0, 0, 0),
+            cmd: 0,
+            vertices: [Vertex(0, 0); N],
+            term: POLY_LINE_END,
+        }
+    }
+
+    const fn cmd(&self) -> Command {
+        self.cmd
+    }
+
+    const fn cmd_mut(&mut self) -> &mut Command {
+        &mut self.cmd
+    }
+
+    color_fn!();
+
+    /// Gets the poly-line's vertices.
+    pub fn get_vertices(&self) -> [Vertex; N] {
+        self.vertices
+    }
+
+    /// Gets a mutable reference to the poly-line's vertices.
+    pub fn get_vertices_mut(&mut self) -> &mut [Vertex; N] {
+        &mut self.vertices
+    }
+
+    /// Sets the poly-line's vertices.
+    pub fn set_vertices(&mut self, vertices: [Vertex; N]) -> &mut Self {
+        self.vertices = vertices;
+        self
+    }
+}
 impl_primitive!(LineG2, 0x50);
-//impl_primitive!(LineGN<N>, 0x58);
+impl_primitive!(LineGN<N>, 0x58);
+impl<const N: usize> LineGN<N> {
+    const fn empty() -> Self {
+        let colored_vertex = ColoredVertex {
+            c: Color::new(0, 0, 0),
+            cmd: 0,
+            v: Vertex(0, 0),
+        };
+        LineGN {
+            colored_vertices: [colored_vertex; N],
+            term: POLY_LINE_END,
+        }
+    }
+
+    const fn cmd(&self) -> Command {
+        self.colored_vertices[0].cmd
+    }
+
+    const fn cmd_mut(&mut self) -> &mut Command {
+        &mut self.colored_vertices[0].cmd
+    }
+
+    /// Gets the poly-line's vertices.
+    pub fn get_vertices(&self) -> [Vertex; N] {
+        self.colored_vertices.map(|cv| cv.v)
+    }
+
+    /// Sets the poly-line's vertices.
+    pub fn set_vertices(&mut self, vertices: [Vertex; N]) -> &mut Self {
+        for (cv, v) in self.colored_vertices.iter_mut().zip(vertices) {
+            cv.v = v;
+        }
+        self
+    }
+
+    /// Gets the poly-line's colors.
+    pub fn get_colors(&self) -> [Color; N] {
+        self.colored_vertices.map(|cv| cv.c)
+    }
+
+    /// Sets the poly-line's colors.
+    pub fn set_colors(&mut self, colors: [Color; N]) -> &mut Self {
+        for (cv, c) in self.colored_vertices.iter_mut().zip(colors) {
+            cv.c = c;
+        }
+        self
+    }
+}
 impl_primitive!(Tile, 0x60);
+impl Tile {
+    color_fn!();
+    offset_fn!();
+    size_fn!();
+}
 impl_primitive!(Tile1, 0x68);
 impl_primitive!(Tile8, 0x70);
 impl Tile8 {
@@ -328,7 +446,7 @@ impl Tile8 {
     offset_fn!();
 }
 impl_primitive!(Tile16, 0x78);
-impl_primitive!(Sprt, 0x64);
+impl_primitive!(Sprt, 0x64, textured);
 impl Sprt {
     color_fn!(textured);
     offset_fn!();
@@ -336,17 +454,90 @@ impl Sprt {
     clut_fn!();
     tex_coord_fn!(1);
 }
-impl_primitive!(Sprt8, 0x74);
+impl_primitive!(Sprt8, 0x74, textured);
 impl Sprt8 {
     color_fn!(textured);
     offset_fn!();
     clut_fn!();
     tex_coord_fn!(1);
 }
-impl_primitive!(Sprt16, 0x7C);
+impl_primitive!(Sprt16, 0x7C, textured);
 impl Sprt16 {
     color_fn!(textured);
     offset_fn!();
     clut_fn!();
     tex_coord_fn!(1);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LineFN, LineGN, PolyFT4, Sprt, Tile};
+    use crate::gpu::colors::{BLUE, RED};
+    use crate::gpu::{BlendMode, TexPage, Vertex};
+    use crate::hw::gpu::GP0Command;
+
+    #[test_case]
+    fn semi_transparent() {
+        let mut tile = Tile::new();
+        tile.set_semi_transparent(true);
+        assert!(tile.get_semi_transparent());
+        assert!(tile.data()[0] >> 24 == 0x62);
+        tile.set_semi_transparent(false);
+        assert!(tile.data()[0] >> 24 == 0x60);
+    }
+
+    #[test_case]
+    fn poly_lines() {
+        let mut line = LineFN::<3>::new();
+        line.set_vertices([Vertex(0, 0), Vertex(16, 16), Vertex(0, 32)])
+            .set_color(RED)
+            .set_semi_transparent(true);
+        assert!(line.get_semi_transparent());
+        let words = [
+            0x4A00_00FF,
+            0x0000_0000,
+            0x0010_0010,
+            0x0020_0000,
+            0x5555_5555,
+        ];
+        assert!(line.data() == words);
+
+        let mut line = LineGN::<2>::new();
+        line.set_vertices([Vertex(1, 2), Vertex(3, 4)])
+            .set_colors([RED, BLUE]);
+        assert!(!line.get_semi_transparent());
+        let words = [
+            0x5800_00FF,
+            0x0002_0001,
+            0x00FF_0000,
+            0x0004_0003,
+            0x5555_5555,
+        ];
+        assert!(line.data() == words);
+    }
+
+    #[test_case]
+    fn raw_texture() {
+        let mut sprt = Sprt::new();
+        sprt.set_raw_texture(true).set_semi_transparent(true);
+        assert!(sprt.get_raw_texture());
+        assert!(sprt.data()[0] >> 24 == 0x67);
+        sprt.reset_cmd();
+        assert!(!sprt.get_raw_texture());
+        assert!(!sprt.get_semi_transparent());
+    }
+
+    #[test_case]
+    fn blend_mode() {
+        let tex_page = TexPage::try_from(Vertex(5, 1)).unwrap();
+        let mut quad = PolyFT4::new();
+        quad.set_blend_mode(BlendMode::Subtract)
+            .set_tex_page(tex_page);
+        assert!(quad.get_tex_page() == tex_page);
+        assert!(quad.get_blend_mode() == BlendMode::Subtract);
+        assert!(quad.data()[4] >> 16 == 0x15 | 2 << 5);
+        quad.set_blend_mode(BlendMode::Add);
+        assert!(quad.get_tex_page() == tex_page);
+        assert!(quad.data()[4] >> 16 == 0x15 | 1 << 5);
+    }
+}
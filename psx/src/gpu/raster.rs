@@ -0,0 +1,852 @@
+//! Software GPU rasterizer.
+//!
+//! This is a reference implementation of the GP0 drawing commands which draws
+//! into an in-memory copy of VRAM instead of the GPU. Since it doesn't touch
+//! any hardware registers, it may be used to render display lists built from
+//! [`Packet`][crate::gpu::Packet]s and [`GP0Command`]s in golden-image tests
+//! and dump the results with [`Rasterizer::write_png`].
+
+use crate::dma::LinkedList;
+use crate::format::png;
+use crate::gpu::decode::{Decoded, Decoder, PacketIter};
+use crate::gpu::env::{DrawMode, TexWindow};
+use crate::gpu::{BlendMode, Bpp, Color, TexCoord, Vertex};
+use crate::hw::gpu::GP0Command;
+
+// Flags in a primitive's command byte
+const RAW_TEXTURE: u32 = 1 << 24;
+const SEMI_TRANSPARENT: u32 = 1 << 25;
+const TEXTURED: u32 = 1 << 26;
+const QUAD: u32 = 1 << 27;
+const GOURAUD: u32 = 1 << 28;
+
+// Bits in a polygon's texture page attribute which are copied to the draw mode
+const TEX_PAGE_BITS: u32 = 0x09FF;
+
+const MASK_BIT: u16 = 0x8000;
+
+fn color(word: u32) -> [i32; 3] {
+    [
+        (word & 0xFF) as i32,
+        ((word >> 8) & 0xFF) as i32,
+        ((word >> 16) & 0xFF) as i32,
+    ]
+}
+
+/// Sign-extends an 11-bit vertex coordinate.
+fn coord(bits: u32) -> i32 {
+    ((bits << 21) as i32) >> 21
+}
+
+fn vertex(word: u32) -> (i32, i32) {
+    (coord(word), coord(word >> 16))
+}
+
+fn tex_coord(word: u32) -> [i32; 2] {
+    [(word & 0xFF) as i32, ((word >> 8) & 0xFF) as i32]
+}
+
+/// Converts an 8-bit per channel color to a 15-bit VRAM pixel.
+fn rgb15([r, g, b]: [i32; 3]) -> u16 {
+    ((r >> 3) | (g >> 3) << 5 | (b >> 3) << 10) as u16
+}
+
+/// Converts a 15-bit VRAM pixel to an 8-bit per channel color.
+fn rgb24(pixel: u16) -> [u8; 3] {
+    let channel = |shift: u16| {
+        let c = ((pixel >> shift) & 0x1F) as u8;
+        c << 3 | c >> 2
+    };
+    [channel(0), channel(5), channel(10)]
+}
+
+/// Blends 15-bit pixels `fg` and `bg` according to `mode`.
+fn blend(bg: u16, fg: u16, mode: BlendMode) -> u16 {
+    let mut res = 0;
+    for shift in [0, 5, 10] {
+        let b = ((bg >> shift) & 0x1F) as i32;
+        let f = ((fg >> shift) & 0x1F) as i32;
+        let c = match mode {
+            BlendMode::Average => (b + f) / 2,
+            BlendMode::Add => b + f,
+            BlendMode::Subtract => b - f,
+            BlendMode::AddQuarter => b + f / 4,
+        };
+        res |= (c.clamp(0, 0x1F) as u16) << shift;
+    }
+    res
+}
+
+/// A vertex with its interpolated attributes.
+#[derive(Clone, Copy, Debug)]
+struct Point {
+    x: i32,
+    y: i32,
+    color: [i32; 3],
+    tex: [i32; 2],
+}
+
+/// The texture state for a textured primitive.
+#[derive(Clone, Copy, Debug)]
+struct Texture {
+    clut: (usize, usize),
+    raw: bool,
+}
+
+/// An in-memory GPU which draws GP0 commands into a `W`x`H` VRAM.
+///
+/// The GPU has 1024x512 halfwords of VRAM, but smaller sizes may be used when
+/// all commands fit in them. Coordinates wrap around the edges of VRAM like on
+/// the GPU. Dithering isn't emulated.
+///
+/// VRAM is borrowed from the caller since a full-size VRAM takes 1 MiB, which
+/// is more than fits on the stack.
+pub struct Rasterizer<'a, const W: usize = 1024, const H: usize = 512> {
+    vram: &'a mut [[u16; W]; H],
+    draw_mode: DrawMode,
+    tex_window: TexWindow,
+    area: (i32, i32, i32, i32),
+    offset: (i32, i32),
+    set_mask: bool,
+    check_mask: bool,
+}
+
+impl<'a, const W: usize, const H: usize> Rasterizer<'a, W, H> {
+    /// Creates a new rasterizer which draws into `vram`.
+    ///
+    /// `vram`'s current contents are used as the initial VRAM, e.g. to draw
+    /// with textures which were already loaded. The draw area initially covers
+    /// all of VRAM.
+    pub fn new(vram: &'a mut [[u16; W]; H]) -> Self {
+        Rasterizer {
+            vram,
+            draw_mode: DrawMode::new(),
+            tex_window: TexWindow::new(),
+            area: (0, 0, W as i32 - 1, H as i32 - 1),
+            offset: (0, 0),
+            set_mask: false,
+            check_mask: false,
+        }
+    }
+
+    /// Gets the rasterizer's VRAM.
+    pub fn vram(&self) -> &[[u16; W]; H] {
+        self.vram
+    }
+
+    /// Gets the pixel in VRAM at `(x, y)`.
+    pub fn pixel(&self, x: usize, y: usize) -> u16 {
+        self.vram[y % H][x % W]
+    }
+
+    /// Draws a single GP0 command.
+    pub fn send_command<C: GP0Command + ?Sized>(&mut self, cmd: &C) -> &mut Self {
+        self.execute(cmd.data())
+    }
+
+    /// Draws the packets in a linked list.
+    ///
+    /// # Safety
+    ///
+    /// All packets in the list must be valid, as required by
+    /// [`PacketIter::new`].
+    pub unsafe fn send_list<L: LinkedList + ?Sized>(&mut self, list: &L) -> &mut Self {
+        for packet in PacketIter::new(list) {
+            self.execute(packet.words);
+        }
+        self
+    }
+
+    /// Draws a stream of GP0 words.
+    ///
+    /// Commands reading from VRAM to the CPU are ignored and the stream stops
+    /// at the first truncated command.
+    pub fn execute(&mut self, words: &[u32]) -> &mut Self {
+        let mut decoder = Decoder::new(words);
+        while let Some((decoded, words)) = decoder.next_with_words() {
+            match decoded {
+                Decoded::Fill {
+                    color,
+                    offset,
+                    size,
+                } => self.fill(color, offset, size),
+                Decoded::PolyF3(_) |
+                Decoded::PolyF4(_) |
+                Decoded::PolyFT3(_) |
+                Decoded::PolyFT4(_) |
+                Decoded::PolyG3(_) |
+                Decoded::PolyG4(_) |
+                Decoded::PolyGT3(_) |
+                Decoded::PolyGT4(_) => self.polygon(words),
+                Decoded::LineF2(_) => {
+                    let c = color(words[0]);
+                    let a = self.point(words[1], c, [0; 2]);
+                    let b = self.point(words[2], c, [0; 2]);
+                    self.line(a, b, words[0]);
+                },
+                Decoded::LineG2(_) => {
+                    let a = self.point(words[1], color(words[0]), [0; 2]);
+                    let b = self.point(words[3], color(words[2]), [0; 2]);
+                    self.line(a, b, words[0]);
+                },
+                Decoded::LineFN(_) => {
+                    let c = color(words[0]);
+                    let vertices = &words[1..words.len() - 1];
+                    for pair in vertices.windows(2) {
+                        let a = self.point(pair[0], c, [0; 2]);
+                        let b = self.point(pair[1], c, [0; 2]);
+                        self.line(a, b, words[0]);
+                    }
+                },
+                Decoded::LineGN(_) => {
+                    let vertices = &words[..words.len() - 1];
+                    let points = vertices.chunks_exact(2);
+                    for (a, b) in points.clone().zip(points.skip(1)) {
+                        let a = self.point(a[1], color(a[0]), [0; 2]);
+                        let b = self.point(b[1], color(b[0]), [0; 2]);
+                        self.line(a, b, words[0]);
+                    }
+                },
+                Decoded::Tile(_) |
+                Decoded::Tile1(_) |
+                Decoded::Tile8(_) |
+                Decoded::Tile16(_) |
+                Decoded::Sprt(_) |
+                Decoded::Sprt8(_) |
+                Decoded::Sprt16(_) => self.rectangle(words),
+                // 1x1 sprites are only decoded as unknown commands
+                Decoded::Unknown(_) if words[0] >> 29 == 0b011 => self.rectangle(words),
+                Decoded::CopyVRAM { src, dst, size } => self.copy(src, dst, size),
+                Decoded::CopyToVRAM { offset, size, data } => self.upload(offset, size, data.0),
+                Decoded::DrawMode(draw_mode) => self.draw_mode = draw_mode,
+                Decoded::TexWindow(tex_window) => self.tex_window = tex_window,
+                Decoded::DrawAreaTopLeft(corner) => {
+                    let Vertex(x, y) = corner.get_corner();
+                    self.area.0 = x as i32;
+                    self.area.1 = y as i32;
+                },
+                Decoded::DrawAreaBottomRight(corner) => {
+                    let Vertex(x, y) = corner.get_corner();
+                    self.area.2 = x as i32;
+                    self.area.3 = y as i32;
+                },
+                Decoded::DrawOffset(offset) => {
+                    let Vertex(x, y) = offset.get_offset();
+                    self.offset = (x as i32, y as i32);
+                },
+                Decoded::MaskBit(mask) => {
+                    self.set_mask = mask.get_set_mask();
+                    self.check_mask = mask.get_check_mask();
+                },
+                Decoded::Truncated(_) => break,
+                _ => {},
+            }
+        }
+        self
+    }
+
+    /// Writes a rectangle of VRAM as a PNG, passing the encoded bytes to
+    /// `out`.
+    pub fn write_png<F: FnMut(&[u8])>(&self, offset: Vertex, size: Vertex, out: F) {
+        let (x0, y0) = (offset.0 as u32 as usize, offset.1 as u32 as usize);
+        let pixel = |x: u32, y: u32| rgb24(self.pixel(x0 + x as usize, y0 + y as usize));
+        // VRAM rows are always small enough to encode
+        png::encode_rgb(size.0 as u32, size.1 as u32, pixel, out);
+    }
+
+    /// Creates a point from a vertex word, applying the drawing offset.
+    fn point(&self, word: u32, color: [i32; 3], tex: [i32; 2]) -> Point {
+        let (x, y) = vertex(word);
+        Point {
+            x: coord((x + self.offset.0) as u32),
+            y: coord((y + self.offset.1) as u32),
+            color,
+            tex,
+        }
+    }
+
+    /// Writes a pixel, applying the draw area, mask bits and semi-transparency.
+    fn put(&mut self, x: i32, y: i32, pixel: u16, semi_transparent: bool) {
+        let (x1, y1, x2, y2) = self.area;
+        if x < x1 || x > x2 || y < y1 || y > y2 {
+            return
+        }
+        let dst = &mut self.vram[y as usize % H][x as usize % W];
+        if self.check_mask && *dst & MASK_BIT != 0 {
+            return
+        }
+        let mut res = pixel;
+        if semi_transparent {
+            res = blend(*dst, pixel, self.draw_mode.get_blend_mode()) | (pixel & MASK_BIT);
+        }
+        if self.set_mask {
+            res |= MASK_BIT;
+        }
+        *dst = res;
+    }
+
+    /// Writes a pixel to VRAM, only applying the mask bits.
+    fn put_raw(&mut self, x: i32, y: i32, pixel: u16) {
+        let dst = &mut self.vram[y as usize % H][x as usize % W];
+        if self.check_mask && *dst & MASK_BIT != 0 {
+            return
+        }
+        *dst = pixel | if self.set_mask { MASK_BIT } else { 0 };
+    }
+
+    /// Looks up a texel in the current texture page.
+    fn texel(&self, [u, v]: [i32; 2], clut: (usize, usize)) -> u16 {
+        let TexCoord {
+            x: mask_x,
+            y: mask_y,
+        } = self.tex_window.get_mask();
+        let TexCoord { x: off_x, y: off_y } = self.tex_window.get_offset();
+        let u = (u as u8 & !mask_x) | (off_x & mask_x);
+        let v = (v as u8 & !mask_y) | (off_y & mask_y);
+        let Vertex(page_x, page_y) = Vertex::from(self.draw_mode.get_tex_page());
+        let x = page_x as usize * 64;
+        let y = page_y as usize * 256 + v as usize;
+        let (u, shift) = (u as usize, |bits: usize| (u as usize % (16 / bits)) * bits);
+        match self.draw_mode.get_bpp() {
+            Bpp::Bits4 => {
+                let idx = (self.pixel(x + u / 4, y) >> shift(4)) & 0xF;
+                self.pixel(clut.0 + idx as usize, clut.1)
+            },
+            Bpp::Bits8 => {
+                let idx = (self.pixel(x + u / 2, y) >> shift(8)) & 0xFF;
+                self.pixel(clut.0 + idx as usize, clut.1)
+            },
+            Bpp::Bits15 => self.pixel(x + u, y),
+        }
+    }
+
+    /// Shades a pixel of a primitive with the specified color and texture
+    /// coordinate.
+    ///
+    /// Returns the pixel and whether it should be semi-transparent if the
+    /// primitive is, or `None` for fully transparent texels.
+    fn shade(
+        &self, color: [i32; 3], tex: [i32; 2], texture: Option<Texture>,
+    ) -> Option<(u16, bool)> {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => return Some((rgb15(color), true)),
+        };
+        let texel = self.texel(tex, texture.clut);
+        if texel == 0 {
+            return None
+        }
+        let semi_transparent = texel & MASK_BIT != 0;
+        if texture.raw {
+            return Some((texel, semi_transparent))
+        }
+        let mut res = texel & MASK_BIT;
+        for (i, shift) in [0, 5, 10].into_iter().enumerate() {
+            let c = ((texel >> shift) & 0x1F) as i32 * color[i] / 0x80;
+            res |= (c.min(0x1F) as u16) << shift;
+        }
+        Some((res, semi_transparent))
+    }
+
+    fn fill(&mut self, color: Color, offset: Vertex, size: Vertex) {
+        let x = offset.0 as usize & 0x3F0;
+        let y = offset.1 as usize & 0x1FF;
+        let w = ((size.0 as usize & 0x3FF) + 0xF) & !0xF;
+        let h = size.1 as usize & 0x1FF;
+        let pixel = rgb15([color.red as i32, color.green as i32, color.blue as i32]);
+        for row in y..y + h {
+            for col in x..x + w {
+                self.vram[row % H][col % W] = pixel;
+            }
+        }
+    }
+
+    fn polygon(&mut self, words: &[u32]) {
+        let cmd = words[0];
+        let gouraud = cmd & GOURAUD != 0;
+        let textured = cmd & TEXTURED != 0;
+        let n = if cmd & QUAD != 0 { 4 } else { 3 };
+        let mut points = [Point {
+            x: 0,
+            y: 0,
+            color: color(cmd),
+            tex: [0; 2],
+        }; 4];
+        let mut clut = (0, 0);
+        let mut idx = 1;
+        for (i, point) in points.iter_mut().take(n).enumerate() {
+            let c = if gouraud && i != 0 {
+                idx += 1;
+                color(words[idx - 1])
+            } else {
+                color(cmd)
+            };
+            let vertex = words[idx];
+            idx += 1;
+            let mut tex = [0; 2];
+            if textured {
+                let attr = words[idx] >> 16;
+                match i {
+                    0 => clut = ((attr as usize & 0x3F) * 16, (attr as usize >> 6) & 0x1FF),
+                    1 => {
+                        let bits = u32::from(self.draw_mode) & !TEX_PAGE_BITS;
+                        let bits = bits | (attr & TEX_PAGE_BITS);
+                        if let Ok(draw_mode) = DrawMode::try_from(bits) {
+                            self.draw_mode = draw_mode;
+                        }
+                    },
+                    _ => {},
+                }
+                tex = tex_coord(words[idx]);
+                idx += 1;
+            }
+            *point = self.point(vertex, c, tex);
+        }
+        let texture = textured.then(|| Texture {
+            clut,
+            raw: cmd & RAW_TEXTURE != 0,
+        });
+        let semi_transparent = cmd & SEMI_TRANSPARENT != 0;
+        self.triangle([points[0], points[1], points[2]], texture, semi_transparent);
+        if n == 4 {
+            self.triangle([points[1], points[2], points[3]], texture, semi_transparent);
+        }
+    }
+
+    fn triangle(&mut self, mut p: [Point; 3], texture: Option<Texture>, semi_transparent: bool) {
+        // The GPU skips polygons which are too large
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            if (p[a].x - p[b].x).abs() > 1023 || (p[a].y - p[b].y).abs() > 511 {
+                return
+            }
+        }
+        let edge = |a: &Point, b: &Point, x: i32, y: i32| {
+            (b.x - a.x) as i64 * (y - a.y) as i64 - (b.y - a.y) as i64 * (x - a.x) as i64
+        };
+        let mut area = edge(&p[0], &p[1], p[2].x, p[2].y);
+        if area == 0 {
+            return
+        }
+        if area < 0 {
+            p.swap(1, 2);
+            area = -area;
+        }
+        // Pixels exactly on an edge are only drawn for top and left edges
+        let bias = |a: &Point, b: &Point| {
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            if dy < 0 || (dy == 0 && dx > 0) {
+                0
+            } else {
+                1
+            }
+        };
+        let biases = [bias(&p[1], &p[2]), bias(&p[2], &p[0]), bias(&p[0], &p[1])];
+        let (x1, y1, x2, y2) = self.area;
+        let min_x = p.iter().map(|p| p.x).min().unwrap_or(0).max(x1);
+        let max_x = p.iter().map(|p| p.x).max().unwrap_or(0).min(x2);
+        let min_y = p.iter().map(|p| p.y).min().unwrap_or(0).max(y1);
+        let max_y = p.iter().map(|p| p.y).max().unwrap_or(0).min(y2);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let w = [
+                    edge(&p[1], &p[2], x, y),
+                    edge(&p[2], &p[0], x, y),
+                    edge(&p[0], &p[1], x, y),
+                ];
+                if (0..3).any(|i| w[i] < biases[i]) {
+                    continue
+                }
+                let lerp = |f: fn(&Point) -> i32| {
+                    let sum: i64 = (0..3).map(|i| w[i] * f(&p[i]) as i64).sum();
+                    (sum / area) as i32
+                };
+                let color = [
+                    lerp(|p| p.color[0]),
+                    lerp(|p| p.color[1]),
+                    lerp(|p| p.color[2]),
+                ];
+                let tex = [lerp(|p| p.tex[0]), lerp(|p| p.tex[1])];
+                if let Some((pixel, semi)) = self.shade(color, tex, texture) {
+                    self.put(x, y, pixel, semi_transparent && semi);
+                }
+            }
+        }
+    }
+
+    fn line(&mut self, a: Point, b: Point, cmd: u32) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        if dx.abs() > 1023 || dy.abs() > 511 {
+            return
+        }
+        let steps = dx.abs().max(dy.abs());
+        let semi_transparent = cmd & SEMI_TRANSPARENT != 0;
+        for i in 0..=steps {
+            let lerp = |a: i32, b: i32| {
+                if steps == 0 {
+                    a
+                } else {
+                    a + ((b - a) * i * 2 + steps) / (2 * steps)
+                }
+            };
+            let color = [
+                lerp(a.color[0], b.color[0]),
+                lerp(a.color[1], b.color[1]),
+                lerp(a.color[2], b.color[2]),
+            ];
+            self.put(
+                lerp(a.x, b.x),
+                lerp(a.y, b.y),
+                rgb15(color),
+                semi_transparent,
+            );
+        }
+    }
+
+    fn rectangle(&mut self, words: &[u32]) {
+        let cmd = words[0];
+        let textured = cmd & TEXTURED != 0;
+        let mut idx = 2;
+        let mut texture = None;
+        let mut tex = [0; 2];
+        if textured {
+            let attr = words[idx] >> 16;
+            texture = Some(Texture {
+                clut: ((attr as usize & 0x3F) * 16, (attr as usize >> 6) & 0x1FF),
+                raw: cmd & RAW_TEXTURE != 0,
+            });
+            tex = tex_coord(words[idx]);
+            idx += 1;
+        }
+        let (w, h) = match (cmd >> 27) & 0b11 {
+            0 => {
+                let (w, h) = vertex(words[idx]);
+                (w & 0x3FF, h & 0x1FF)
+            },
+            1 => (1, 1),
+            2 => (8, 8),
+            _ => (16, 16),
+        };
+        let origin = self.point(words[1], color(cmd), [0; 2]);
+        let semi_transparent = cmd & SEMI_TRANSPARENT != 0;
+        let (du, dv) = (
+            if self.draw_mode.get_x_flip() { -1 } else { 1 },
+            if self.draw_mode.get_y_flip() { -1 } else { 1 },
+        );
+        for j in 0..h {
+            for i in 0..w {
+                let uv = [(tex[0] + i * du) & 0xFF, (tex[1] + j * dv) & 0xFF];
+                if let Some((pixel, semi)) = self.shade(origin.color, uv, texture) {
+                    self.put(origin.x + i, origin.y + j, pixel, semi_transparent && semi);
+                }
+            }
+        }
+    }
+
+    fn copy(&mut self, src: Vertex, dst: Vertex, size: Vertex) {
+        let (w, h) = vram_size(size);
+        let (sx, sy) = (src.0 as i32, src.1 as i32);
+        let (dx, dy) = (dst.0 as i32, dst.1 as i32);
+        for j in 0..h {
+            for i in 0..w {
+                let pixel = self.pixel((sx + i) as usize, (sy + j) as usize);
+                self.put_raw(dx + i, dy + j, pixel);
+            }
+        }
+    }
+
+    fn upload(&mut self, offset: Vertex, size: Vertex, data: &[u32]) {
+        let (w, h) = vram_size(size);
+        let (x, y) = (offset.0 as i32, offset.1 as i32);
+        let halfwords = data
+            .iter()
+            .flat_map(|&word| [word as u16, (word >> 16) as u16]);
+        for (i, pixel) in halfwords.enumerate().take((w * h) as usize) {
+            let i = i as i32;
+            self.put_raw(x + i % w, y + i / w, pixel);
+        }
+    }
+}
+
+/// Gets the size of a VRAM transfer, where a size of 0 wraps around to the
+/// maximum.
+fn vram_size(size: Vertex) -> (i32, i32) {
+    let w = ((size.0 as u16).wrapping_sub(1) & 0x3FF) as i32 + 1;
+    let h = ((size.1 as u16).wrapping_sub(1) & 0x1FF) as i32 + 1;
+    (w, h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rasterizer;
+    use crate::gpu::colors::{BLUE, GREEN, RED, WHITE};
+    use crate::gpu::env::{DrawAreaBottomRight, DrawMode, DrawOffset};
+    use crate::gpu::primitives::{LineF2, PolyF3, PolyF4, PolyFT3, PolyG3, Sprt8, Tile};
+    use crate::gpu::{BlendMode, Clut, Color, TexCoord, TexPage, Vertex};
+    use crate::hw::gpu::GP0Command;
+
+    struct Words<'a>(&'a [u32]);
+
+    impl GP0Command for Words<'_> {
+        fn data(&self) -> &[u32] {
+            self.0
+        }
+    }
+
+    type Small<'a> = Rasterizer<'a, 64, 64>;
+
+    #[test_case]
+    fn fill() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        gpu.execute(&[0x0200_00FF, 0x0002_0005, 0x0002_0005]);
+        // Fills are aligned to 16 halfwords and aren't clipped
+        assert!(gpu.pixel(0, 2) == 0x001F);
+        assert!(gpu.pixel(15, 3) == 0x001F);
+        assert!(gpu.pixel(16, 2) == 0);
+        assert!(gpu.pixel(0, 4) == 0);
+    }
+
+    #[test_case]
+    fn flat_quad() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let mut quad = PolyF4::new();
+        quad.set_vertices([Vertex(2, 2), Vertex(6, 2), Vertex(2, 6), Vertex(6, 6)])
+            .set_color(GREEN);
+        gpu.send_command(&quad);
+        let drawn = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| gpu.pixel(x, y) == 0x03E0)
+            .count();
+        // The right and bottom edges aren't drawn
+        assert!(drawn == 16);
+        assert!(gpu.pixel(2, 2) == 0x03E0);
+        assert!(gpu.pixel(6, 6) == 0);
+    }
+
+    #[test_case]
+    fn excluded_edges() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let mut tri = PolyF3::new();
+        tri.set_vertices([Vertex(0, 0), Vertex(4, 0), Vertex(0, 4)])
+            .set_color(RED);
+        let mut tile = Tile::new();
+        tile.set_offset(Vertex(8, 0))
+            .set_size(Vertex(3, 2))
+            .set_color(RED);
+        gpu.send_command(&tri).send_command(&tile);
+        for y in 0..8 {
+            for x in 0..16 {
+                // The triangle's hypotenuse is a right edge so it isn't drawn,
+                // but rectangles cover their whole size
+                let in_tri = x + y < 4;
+                let in_tile = (8..11).contains(&x) && y < 2;
+                let expected = if in_tri || in_tile { 0x001F } else { 0 };
+                assert!(gpu.pixel(x, y) == expected);
+            }
+        }
+    }
+
+    #[test_case]
+    fn gouraud() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let mut tri = PolyG3::new();
+        tri.set_vertices([Vertex(0, 0), Vertex(32, 0), Vertex(0, 32)])
+            .set_colors([RED, GREEN, BLUE]);
+        gpu.send_command(&tri);
+        assert!(gpu.pixel(0, 0) == 0x001F);
+        assert!(gpu.pixel(30, 0) & 0x001F < 0x0004);
+    }
+
+    #[test_case]
+    fn offset_and_clipping() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let words = [
+            u32::from(DrawOffset::new(Vertex(8, 8)).unwrap()),
+            u32::from(DrawAreaBottomRight::new(Vertex(11, 11)).unwrap()),
+            0x60FF_FFFF,
+            0x0000_0000,
+            0x0010_0010,
+        ];
+        gpu.execute(&words);
+        assert!(gpu.pixel(8, 8) == 0x7FFF);
+        assert!(gpu.pixel(11, 11) == 0x7FFF);
+        assert!(gpu.pixel(12, 11) == 0);
+        assert!(gpu.pixel(7, 8) == 0);
+    }
+
+    #[test_case]
+    fn semi_transparent() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        gpu.execute(&[0x0200_0080, 0, 0x0010_0010]);
+        let mut tile = Tile::new();
+        let mut words = [0; 3];
+        words.copy_from_slice(tile.set_semi_transparent(true).data());
+        words[0] |= 0x0080_0000;
+        words[2] = 0x0001_0001;
+        gpu.execute(&words);
+        // Averaging red 0x10 with blue 0x10
+        assert!(gpu.pixel(0, 0) == 0x2008);
+    }
+
+    #[test_case]
+    fn blend_modes() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        // The background is (20, 10, 4) and the foreground is (8, 16, 30) in
+        // 5-bit components
+        gpu.execute(&[0x0220_50A0, 0, 0x0001_0010]);
+        let modes = [
+            (BlendMode::Average, (14, 13, 17)),
+            (BlendMode::Add, (28, 26, 31)),
+            (BlendMode::Subtract, (12, 0, 0)),
+            (BlendMode::AddQuarter, (22, 14, 11)),
+        ];
+        for (x, (mode, (r, g, b))) in modes.into_iter().enumerate() {
+            let mut draw_mode = DrawMode::new();
+            draw_mode.set_blend_mode(mode);
+            let mut tile = Tile::new();
+            tile.set_offset(Vertex(x as i16, 0))
+                .set_size(Vertex(1, 1))
+                .set_color(Color::new(8 << 3, 16 << 3, 30 << 3))
+                .set_semi_transparent(true);
+            gpu.send_command(&draw_mode).send_command(&tile);
+            assert!(gpu.pixel(x, 0) == r | g << 5 | b << 10);
+        }
+        // Pixels outside the tiles keep the background
+        assert!(gpu.pixel(4, 0) == 20 | 10 << 5 | 4 << 10);
+    }
+
+    #[test_case]
+    fn clut_4bit() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        // Texels 0-7 use indices 0-7 starting from each halfword's low nibble
+        gpu.execute(&[0xA000_0000, 0, 0x0001_0002, 0x7654_3210]);
+        // CLUT entry `i` at (0, 32) is `0x21 * (i + 1)`
+        let mut upload = [0; 11];
+        upload[..3].copy_from_slice(&[0xA000_0000, 0x0020_0000, 0x0001_0010]);
+        for (i, word) in upload[3..].iter_mut().enumerate() {
+            let i = 2 * i as u32;
+            *word = 0x21 * (i + 1) | (0x21 * (i + 2)) << 16;
+        }
+        gpu.execute(&upload);
+        let mut sprt = Sprt8::new();
+        sprt.set_offset(Vertex(0, 16))
+            .set_clut(Clut::try_from(Vertex(0, 32)).unwrap())
+            .set_tex_coord(TexCoord { x: 0, y: 0 })
+            .set_raw_texture(true);
+        gpu.send_command(&sprt);
+        for x in 0..8 {
+            assert!(gpu.pixel(x, 16) == 0x21 * (x as u16 + 1));
+        }
+        // Starting at an odd texel picks the following nibbles
+        sprt.set_offset(Vertex(0, 24))
+            .set_tex_coord(TexCoord { x: 3, y: 0 });
+        gpu.send_command(&sprt);
+        for x in 0..5 {
+            assert!(gpu.pixel(x, 24) == 0x21 * (x as u16 + 4));
+        }
+    }
+
+    #[test_case]
+    fn textured() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        // A 4-bit texture at (0, 0) using CLUT entries 1 and 2 at (0, 32)
+        gpu.execute(&[0xA000_0000, 0x0000_0000, 0x0001_0001, 0x0000_2121]);
+        gpu.execute(&[
+            0xA000_0000,
+            0x0020_0000,
+            0x0001_0004,
+            0x001F_0000,
+            0x0000_03E0,
+        ]);
+        let mut sprt = Sprt8::new();
+        sprt.set_offset(Vertex(16, 16))
+            .set_clut(Clut::try_from(Vertex(0, 32)).unwrap())
+            .set_tex_coord(TexCoord { x: 0, y: 0 })
+            .set_raw_texture(true);
+        gpu.send_command(&sprt);
+        assert!(gpu.pixel(16, 16) == 0x001F);
+        assert!(gpu.pixel(17, 16) == 0x03E0);
+        assert!(gpu.pixel(18, 16) == 0x001F);
+        // Texels past the first halfword are 0 which are transparent
+        assert!(gpu.pixel(20, 16) == 0);
+
+        let mut tri = PolyFT3::new();
+        tri.set_vertices([Vertex(0, 40), Vertex(4, 40), Vertex(0, 44)])
+            .set_color(WHITE)
+            .set_clut(Clut::try_from(Vertex(0, 32)).unwrap())
+            .set_tex_page(TexPage::try_from(Vertex(0, 0)).unwrap())
+            .set_blend_mode(BlendMode::Add)
+            .set_tex_coords([TexCoord { x: 0, y: 0 }; 3]);
+        gpu.send_command(&tri);
+        // The texel is modulated by (0xFF/2) / 0x80 which is slightly under 1
+        assert!(gpu.pixel(0, 40) == 0x001E);
+    }
+
+    #[test_case]
+    fn line() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let mut line = LineF2::new();
+        let mut words = [0; 3];
+        words.copy_from_slice(line.set_semi_transparent(false).data());
+        words[0] |= 0xFF;
+        words[2] = 0x0003_0003;
+        gpu.send_command(&Words(&words));
+        for i in 0..4 {
+            assert!(gpu.pixel(i, i) == 0x001F);
+        }
+        assert!(gpu.pixel(1, 0) == 0);
+    }
+
+    #[test_case]
+    fn copy() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        gpu.execute(&[0x0200_00FF, 0, 0x0001_0010]);
+        gpu.execute(&[0x8000_0000, 0, 0x0010_0010, 0x0001_0004]);
+        assert!(gpu.pixel(16, 16) == 0x001F);
+        assert!(gpu.pixel(19, 16) == 0x001F);
+        assert!(gpu.pixel(20, 16) == 0);
+    }
+
+    #[test_case]
+    fn png() {
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        gpu.execute(&[0x0200_00FF, 0, 0x0001_0010]);
+        let mut len = 0;
+        gpu.write_png(Vertex(0, 0), Vertex(4, 2), |b| len += b.len());
+        assert!(len == 8 + 25 + (12 + 2 + 2 * (5 + 13) + 4) + 12);
+    }
+
+    #[test_case]
+    fn golden_image() {
+        // This image was inspected by hand when the test was written so changes
+        // to the rasterizer's output show up as a failure here
+        const GOLDEN: &[u8] = include_bytes!("../../test_files/raster.png");
+        let mut vram = [[0; 64]; 64];
+        let mut gpu = Small::new(&mut vram);
+        let mut tri = PolyG3::new();
+        tri.set_vertices([Vertex(0, 0), Vertex(31, 0), Vertex(0, 31)])
+            .set_colors([RED, GREEN, BLUE]);
+        let mut quad = PolyF4::new();
+        quad.set_vertices([Vertex(8, 8), Vertex(28, 8), Vertex(8, 28), Vertex(28, 28)])
+            .set_color(WHITE)
+            .set_semi_transparent(true);
+        gpu.send_command(&tri).send_command(&quad);
+        let mut pos = 0;
+        gpu.write_png(Vertex(0, 0), Vertex(32, 32), |bytes| {
+            assert!(GOLDEN.get(pos..pos + bytes.len()) == Some(bytes));
+            pos += bytes.len();
+        });
+        assert!(pos == GOLDEN.len());
+    }
+}
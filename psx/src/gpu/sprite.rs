@@ -0,0 +1,210 @@
+use crate::framebuffer::LoadedTIM;
+use crate::gpu::colors::WHITE;
+use crate::gpu::env::DrawMode;
+use crate::gpu::primitives::{PolyFT4, Sprt};
+use crate::gpu::{TexColor, TexCoord, Vertex};
+
+/// A named rectangle within a sprite sheet.
+///
+/// `offset` is the frame's upper-left texcoord relative to the sheet's bitmap
+/// and `pivot` is the point within the frame which is placed at the position
+/// the frame is drawn at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Frame {
+    /// The frame's upper-left texcoord relative to the sheet's bitmap.
+    pub offset: TexCoord,
+    /// The frame's width and height in texels.
+    pub size: Vertex,
+    /// The frame's origin relative to its upper-left corner.
+    pub pivot: Vertex,
+}
+
+/// The axes to mirror a [`Frame`] along when it's drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Flip {
+    /// Mirrors the frame horizontally.
+    pub x: bool,
+    /// Mirrors the frame vertically.
+    pub y: bool,
+}
+
+impl Flip {
+    /// Draws the frame as it appears in the sheet.
+    pub const NONE: Self = Flip { x: false, y: false };
+    /// Mirrors the frame horizontally.
+    pub const X: Self = Flip { x: true, y: false };
+    /// Mirrors the frame vertically.
+    pub const Y: Self = Flip { x: false, y: true };
+    /// Mirrors the frame both horizontally and vertically.
+    pub const XY: Self = Flip { x: true, y: true };
+}
+
+impl Frame {
+    /// Creates a frame with its pivot at the upper-left corner.
+    pub const fn new(offset: TexCoord, size: Vertex) -> Self {
+        Frame {
+            offset,
+            size,
+            pivot: Vertex(0, 0),
+        }
+    }
+
+    /// Sets the frame's pivot.
+    pub const fn with_pivot(mut self, pivot: Vertex) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    /// Gets the upper-left corner of the frame when its pivot is placed at
+    /// `pos`.
+    pub fn origin(&self, pos: Vertex) -> Vertex {
+        pos - self.pivot
+    }
+
+    /// Gets the frame's texcoords at its left/top and right/bottom edges.
+    // Quads don't draw their right and bottom edges so the far texcoords are
+    // one texel past the frame in the direction it's read. Flipped frames start
+    // at their last texel and end just before their first. These saturate for
+    // frames at the edges of a texture page, losing the last texel.
+    fn tex_bounds(&self, flip: Flip) -> ((u8, u8), (u8, u8)) {
+        let bounds = |start: u8, len: i16, flip: bool| {
+            let (near, far) = if flip {
+                (start as i16 + len - 1, start as i16 - 1)
+            } else {
+                (start as i16, start as i16 + len)
+            };
+            (near.clamp(0, 0xFF) as u8, far.clamp(0, 0xFF) as u8)
+        };
+        let x = bounds(self.offset.x, self.size.0, flip.x);
+        let y = bounds(self.offset.y, self.size.1, flip.y);
+        (x, y)
+    }
+}
+
+impl LoadedTIM {
+    /// Gets a draw mode selecting this TIM's texture page and bpp.
+    pub fn draw_mode(&self) -> DrawMode {
+        let mut draw_mode = DrawMode::new();
+        draw_mode.set_tex_page(self.tex_page).set_bpp(self.bpp);
+        draw_mode
+    }
+
+    /// Creates a sprite drawing `frame` from this TIM with its pivot at `pos`.
+    ///
+    /// Rectangles take their texture page and bpp from the current draw mode,
+    /// so [`LoadedTIM::draw_mode`] must be sent before the sprite is drawn.
+    /// Rectangles can't be mirrored through their texcoords so use
+    /// [`LoadedTIM::quad`] or the draw mode's texture flip bits for flipped
+    /// frames.
+    pub fn sprite(&self, frame: &Frame, pos: Vertex) -> Sprt {
+        let mut sprt = Sprt::new();
+        sprt.set_offset(frame.origin(pos))
+            .set_size(frame.size)
+            .set_tex_coord(self.page_coord(frame.offset))
+            .set_color(TexColor::from(WHITE));
+        if let Some(clut) = self.clut {
+            sprt.set_clut(clut);
+        }
+        sprt
+    }
+
+    /// Creates a quad drawing `frame` from this TIM with its pivot at `pos`.
+    ///
+    /// Flipping is done by reversing the quad's texcoords while the pivot stays
+    /// at the same point within the quad.
+    pub fn quad(&self, frame: &Frame, pos: Vertex, flip: Flip) -> PolyFT4 {
+        let frame = Frame {
+            offset: self.page_coord(frame.offset),
+            ..*frame
+        };
+        let Vertex(x, y) = frame.origin(pos);
+        let Vertex(w, h) = frame.size;
+        let ((u0, u1), (v0, v1)) = frame.tex_bounds(flip);
+        let mut quad = PolyFT4::new();
+        quad.set_vertices([
+            Vertex(x, y),
+            Vertex(x + w, y),
+            Vertex(x, y + h),
+            Vertex(x + w, y + h),
+        ])
+        .set_tex_coords([
+            TexCoord { x: u0, y: v0 },
+            TexCoord { x: u1, y: v0 },
+            TexCoord { x: u0, y: v1 },
+            TexCoord { x: u1, y: v1 },
+        ])
+        .set_tex_page(self.tex_page)
+        .set_bpp(self.bpp)
+        .set_color(TexColor::from(WHITE));
+        if let Some(clut) = self.clut {
+            quad.set_clut(clut);
+        }
+        quad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Flip, Frame};
+    use crate::framebuffer::LoadedTIM;
+    use crate::gpu::{Bpp, Clut, TexCoord, TexPage, Vertex};
+
+    fn sheet() -> LoadedTIM {
+        LoadedTIM {
+            tex_page: TexPage::try_from(Vertex(5, 0)).unwrap(),
+            tex_coord: TexCoord { x: 0, y: 0 },
+            bpp: Bpp::Bits4,
+            clut: Some(Clut::try_from(Vertex(0, 480)).unwrap()),
+        }
+    }
+
+    #[test_case]
+    fn sprite() {
+        let frame = Frame::new(TexCoord { x: 16, y: 32 }, Vertex(24, 8)).with_pivot(Vertex(12, 8));
+        let sheet = sheet();
+        let sprt = sheet.sprite(&frame, Vertex(100, 50));
+        assert!(sprt.get_offset() == Vertex(88, 42));
+        assert!(sprt.get_size() == Vertex(24, 8));
+        assert!(sprt.get_tex_coord() == TexCoord { x: 16, y: 32 });
+        assert!(sprt.get_clut() == sheet.clut.unwrap());
+    }
+
+    #[test_case]
+    fn quad_flip() {
+        let frame = Frame::new(TexCoord { x: 16, y: 32 }, Vertex(24, 8)).with_pivot(Vertex(12, 8));
+        let sheet = sheet();
+        let quad = sheet.quad(&frame, Vertex(100, 50), Flip::NONE);
+        assert!(quad.get_vertices()[0] == Vertex(88, 42));
+        assert!(quad.get_vertices()[3] == Vertex(112, 50));
+        assert!(quad.get_tex_coords()[0] == TexCoord { x: 16, y: 32 });
+        assert!(quad.get_tex_coords()[3] == TexCoord { x: 40, y: 40 });
+        assert!(quad.get_tex_page() == sheet.tex_page);
+        assert!(quad.get_bpp() == Bpp::Bits4);
+        let mut sheet8 = sheet;
+        sheet8.bpp = Bpp::Bits8;
+        let quad = sheet8.quad(&frame, Vertex(100, 50), Flip::NONE);
+        assert!(quad.get_bpp() == Bpp::Bits8 && quad.get_tex_page() == sheet.tex_page);
+        let draw_mode = sheet8.draw_mode();
+        assert!(draw_mode.get_tex_page() == sheet.tex_page && draw_mode.get_bpp() == Bpp::Bits8);
+
+        // Flipped quads start at the frame's last texel so the far edges are
+        // just before its first texel
+        let flipped = sheet.quad(&frame, Vertex(100, 50), Flip::XY);
+        assert!(flipped.get_vertices() == quad.get_vertices());
+        assert!(flipped.get_tex_coords()[0] == TexCoord { x: 39, y: 39 });
+        assert!(flipped.get_tex_coords()[1] == TexCoord { x: 15, y: 39 });
+        assert!(flipped.get_tex_coords()[3] == TexCoord { x: 15, y: 31 });
+    }
+
+    #[test_case]
+    fn page_edge() {
+        let frame = Frame::new(TexCoord { x: 240, y: 0 }, Vertex(16, 16));
+        let quad = sheet().quad(&frame, Vertex(0, 0), Flip::NONE);
+        assert!(quad.get_tex_coords()[3] == TexCoord { x: 255, y: 16 });
+        // Flipped frames at the start of the page saturate at 0
+        let frame = Frame::new(TexCoord { x: 0, y: 0 }, Vertex(16, 16));
+        let flipped = sheet().quad(&frame, Vertex(0, 0), Flip::XY);
+        assert!(flipped.get_tex_coords()[0] == TexCoord { x: 15, y: 15 });
+        assert!(flipped.get_tex_coords()[3] == TexCoord { x: 0, y: 0 });
+    }
+}
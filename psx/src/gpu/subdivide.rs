@@ -0,0 +1,226 @@
+use crate::gpu::clip::Point;
+use crate::gpu::TexCoord;
+use crate::gte::ScreenVertex;
+use core::array;
+
+/// Linearly interpolates from `a` to `b` by `num / den`.
+fn lerp(a: i32, b: i32, num: i32, den: i32) -> i32 {
+    a + (b - a) * num / den
+}
+
+fn lerp_point(a: &Point, b: &Point, num: i32, den: i32) -> Point {
+    let tex_coord = TexCoord {
+        x: lerp(a.tex_coord.x as i32, b.tex_coord.x as i32, num, den) as u8,
+        y: lerp(a.tex_coord.y as i32, b.tex_coord.y as i32, num, den) as u8,
+    };
+    Point {
+        vertex: a.vertex,
+        color: array::from_fn(|i| lerp(a.color[i] as i32, b.color[i] as i32, num, den) as u8),
+        tex_coord,
+    }
+}
+
+fn lerp_vertex(a: [i16; 3], b: [i16; 3], num: i32, den: i32) -> [i16; 3] {
+    array::from_fn(|i| lerp(a[i] as i32, b[i] as i32, num, den) as i16)
+}
+
+/// The largest number of rows and columns a quad may be subdivided into.
+pub const MAX_SUBDIVISIONS: usize = 16;
+
+/// Tessellates a quad into an `n`x`n` grid if any of its vertices are closer
+/// than `threshold`, calling `f` with the projected corners of each piece.
+///
+/// `corners` holds the quad's colors and texcoords and `vertices` holds its
+/// untransformed vertices, both in the GPU's vertex order. `project` returns a
+/// vertex's screen position and depth, which is compared with `threshold`.
+/// Each grid point is only projected once.
+pub(crate) fn tessellate(
+    corners: [Point; 4], vertices: [[i16; 3]; 4], threshold: u16, n: usize,
+    project: &mut dyn FnMut([i16; 3]) -> ScreenVertex, f: &mut dyn FnMut([Point; 4]),
+) {
+    let projected = vertices.map(|v| project(v));
+    if projected.iter().all(|v| v.z >= threshold) || n <= 1 {
+        let mut points = corners;
+        for (p, v) in points.iter_mut().zip(projected) {
+            p.vertex = v.xy;
+        }
+        f(points);
+        return
+    }
+    let n = n.min(MAX_SUBDIVISIONS) as i32;
+    // Gets the grid point `i` columns right and `j` rows down, reusing the
+    // projected corners
+    let mut grid = |i: i32, j: i32| {
+        let top = lerp_point(&corners[0], &corners[1], i, n);
+        let bottom = lerp_point(&corners[2], &corners[3], i, n);
+        let mut point = lerp_point(&top, &bottom, j, n);
+        point.vertex = match (i, j) {
+            (0, 0) => projected[0].xy,
+            (i, 0) if i == n => projected[1].xy,
+            (0, j) if j == n => projected[2].xy,
+            (i, j) if i == n && j == n => projected[3].xy,
+            _ => {
+                let top = lerp_vertex(vertices[0], vertices[1], i, n);
+                let bottom = lerp_vertex(vertices[2], vertices[3], i, n);
+                project(lerp_vertex(top, bottom, j, n)).xy
+            },
+        };
+        point
+    };
+    // The previous row of grid points
+    let mut row = [corners[0]; MAX_SUBDIVISIONS + 1];
+    for i in 0..=n {
+        row[i as usize] = grid(i, 0);
+    }
+    for j in 0..n {
+        let mut left = grid(0, j + 1);
+        for i in 0..n {
+            let right = grid(i + 1, j + 1);
+            let i = i as usize;
+            f([row[i], row[i + 1], left, right]);
+            row[i] = left;
+            left = right;
+        }
+        row[n as usize] = left;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gpu::primitives::{PolyFT4, PolyGT4};
+    use crate::gpu::{TexColor, TexCoord, Vertex};
+    use crate::gte::ScreenVertex;
+
+    fn project([x, y, z]: [i16; 3]) -> ScreenVertex {
+        ScreenVertex {
+            xy: Vertex(x, y),
+            z: z as u16,
+        }
+    }
+
+    const VERTICES: [[i16; 3]; 4] = [[0, 0, 2], [40, 0, 2], [0, 20, 8], [40, 20, 8]];
+
+    fn quad() -> PolyFT4 {
+        let mut quad = PolyFT4::new();
+        quad.set_tex_coords([
+            TexCoord { x: 0, y: 0 },
+            TexCoord { x: 64, y: 0 },
+            TexCoord { x: 0, y: 32 },
+            TexCoord { x: 64, y: 32 },
+        ]);
+        quad
+    }
+
+    #[test_case]
+    fn far() {
+        let mut pieces = 0;
+        quad().subdivide(VERTICES, 1, 4, project, |piece| {
+            assert!(
+                piece.get_vertices() ==
+                    [Vertex(0, 0), Vertex(40, 0), Vertex(0, 20), Vertex(40, 20)]
+            );
+            pieces += 1;
+        });
+        assert!(pieces == 1);
+    }
+
+    #[test_case]
+    fn near() {
+        let mut pieces = [PolyFT4::new(); 4];
+        let mut n = 0;
+        quad().subdivide(VERTICES, 4, 2, project, |piece| {
+            pieces[n] = *piece;
+            n += 1;
+        });
+        assert!(n == 4);
+        // Pieces are emitted row by row
+        let [_, upper_right, lower_left, _] = pieces;
+        assert!(
+            upper_right.get_vertices() ==
+                [Vertex(20, 0), Vertex(40, 0), Vertex(20, 10), Vertex(40, 10)]
+        );
+        assert!(
+            upper_right.get_tex_coords() ==
+                [
+                    TexCoord { x: 32, y: 0 },
+                    TexCoord { x: 64, y: 0 },
+                    TexCoord { x: 32, y: 16 },
+                    TexCoord { x: 64, y: 16 },
+                ]
+        );
+        assert!(lower_left.get_tex_coords()[3] == TexCoord { x: 32, y: 32 });
+    }
+
+    #[test_case]
+    fn view_space() {
+        // The threshold is compared with the projected depths rather than the
+        // vertices' z components
+        let mut pieces = 0;
+        let behind = |[x, y, z]: [i16; 3]| ScreenVertex {
+            z: z as u16 + 8,
+            ..project([x, y, z])
+        };
+        quad().subdivide(VERTICES, 4, 4, behind, |_| pieces += 1);
+        assert!(pieces == 1);
+
+        // Each of the 3x3 grid's points is projected once
+        let mut projections = 0;
+        let counted = |v| {
+            projections += 1;
+            project(v)
+        };
+        let mut pieces = 0;
+        quad().subdivide(VERTICES, 4, 2, counted, |_| pieces += 1);
+        assert!(pieces == 4);
+        assert!(projections == 9);
+    }
+
+    #[test_case]
+    fn gte() {
+        use crate::gte::GTE;
+
+        let mut gte = GTE::software();
+        gte.set_rotation([[0x1000, 0, 0], [0, 0x1000, 0], [0, 0, 0x1000]])
+            .set_translation([0, 0, 0])
+            .set_screen_offset(Vertex(160, 120))
+            .set_projection_distance(200);
+        // Vertices 400 units deep are projected at half scale
+        let vertices = [
+            [-40, -20, 400],
+            [40, -20, 400],
+            [-40, 20, 400],
+            [40, 20, 400],
+        ];
+        let mut pieces = 0;
+        quad().subdivide(
+            vertices,
+            300,
+            2,
+            |v| gte.rtps(v),
+            |piece| {
+                assert!(piece.get_vertices()[3] == Vertex(180, 130));
+                pieces += 1;
+            },
+        );
+        assert!(pieces == 1);
+    }
+
+    #[test_case]
+    fn colors() {
+        let mut quad = PolyGT4::new();
+        quad.set_colors([
+            TexColor::new(0, 0, 0),
+            TexColor::new(0x60, 0, 0),
+            TexColor::new(0, 0x30, 0),
+            TexColor::new(0x60, 0x30, 0),
+        ]);
+        let mut first = None;
+        quad.subdivide(VERTICES, 4, 3, project, |piece| {
+            first.get_or_insert(*piece);
+        });
+        let [_, c1, c2, c3] = first.unwrap().get_colors();
+        assert!(c1 == TexColor::new(0x20, 0, 0));
+        assert!(c2 == TexColor::new(0, 0x10, 0));
+        assert!(c3 == TexColor::new(0x20, 0x10, 0));
+    }
+}
@@ -0,0 +1,429 @@
+use crate::framebuffer::LoadedTIM;
+use crate::gpu::colors::WHITE;
+use crate::gpu::env::DrawMode;
+use crate::gpu::primitives::{Sprt16, Sprt8};
+use crate::gpu::{Clut, Flip, OrderingTable, Packet, PrimitiveBuffer, TexColor, TexCoord, Vertex};
+
+const INDEX_MASK: u16 = 0x3FF;
+const X_FLIP: u16 = 10;
+const Y_FLIP: u16 = 11;
+const PALETTE_SHIFT: u16 = 12;
+
+// Groups of tiles in the order they're inserted by `Tilemap::draw`. A group's
+// index is its x flip bit ORed with its y flip bit shifted left by 1.
+const FLIPS: [Flip; 4] = [Flip::NONE, Flip::X, Flip::Y, Flip::XY];
+
+/// A tile in a [`Tilemap`].
+///
+/// This is a halfword with the following layout.
+///
+/// bits `0` to `9`: index in the tileset
+///
+/// bit `10`: horizontal flip
+///
+/// bit `11`: vertical flip
+///
+/// bits `12` to `15`: palette, the CLUT row relative to the tileset's CLUT
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MapTile(pub u16);
+
+impl MapTile {
+    /// Creates an unflipped tile using the tileset's CLUT.
+    pub const fn new(index: u16) -> Self {
+        MapTile(index & INDEX_MASK)
+    }
+
+    /// Gets the tile's index in the tileset.
+    pub const fn index(self) -> u16 {
+        self.0 & INDEX_MASK
+    }
+
+    /// Gets the tile's flip.
+    pub const fn flip(self) -> Flip {
+        Flip {
+            x: self.0 & (1 << X_FLIP) != 0,
+            y: self.0 & (1 << Y_FLIP) != 0,
+        }
+    }
+
+    /// Sets the tile's flip.
+    pub const fn with_flip(self, flip: Flip) -> Self {
+        let flags = ((flip.x as u16) << X_FLIP) | ((flip.y as u16) << Y_FLIP);
+        MapTile(self.0 & !(0b11 << X_FLIP) | flags)
+    }
+
+    /// Gets the tile's palette.
+    pub const fn palette(self) -> u8 {
+        (self.0 >> PALETTE_SHIFT) as u8
+    }
+
+    /// Sets the tile's palette. Only the lower 4 bits of `palette` are used.
+    pub const fn with_palette(self, palette: u8) -> Self {
+        let palette = (palette as u16 & 0xF) << PALETTE_SHIFT;
+        MapTile(self.0 & !(0xF << PALETTE_SHIFT) | palette)
+    }
+}
+
+/// The size of the tiles in a [`Tilemap`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TileSize {
+    /// 8x8 tiles drawn with [`Sprt8`].
+    Size8 = 8,
+    /// 16x16 tiles drawn with [`Sprt16`].
+    Size16 = 16,
+}
+
+/// A scrollable background layer made of tiles from a tileset in VRAM.
+///
+/// The tileset is a grid of tiles in a [`LoadedTIM`]'s bitmap starting at
+/// [`Tilemap::set_origin`] and numbered left to right, top to bottom. Tiles
+/// are drawn with [`Sprt8`] or [`Sprt16`] so only the visible tiles are
+/// inserted into the packet list.
+#[derive(Debug)]
+pub struct Tilemap<'a> {
+    tiles: &'a [MapTile],
+    width: usize,
+    tileset: LoadedTIM,
+    tile_size: TileSize,
+    origin: TexCoord,
+    columns: u8,
+    wrap: bool,
+}
+
+impl<'a> Tilemap<'a> {
+    /// Creates a tilemap `width` tiles wide from row-major `tiles` drawn from
+    /// `tileset`.
+    ///
+    /// By default the tileset starts at the upper-left corner of the TIM's
+    /// bitmap, uses the rest of its texture page's width and the tilemap
+    /// doesn't wrap. Returns `None` if `width` is zero or doesn't evenly divide
+    /// the number of tiles, or if a tile's palette is past the bottom of VRAM.
+    pub fn new(
+        tiles: &'a [MapTile], width: usize, tileset: LoadedTIM, tile_size: TileSize,
+    ) -> Option<Self> {
+        if width == 0 || tiles.len() % width != 0 || !palettes_fit(&tileset, tiles) {
+            return None
+        }
+        Some(Tilemap {
+            tiles,
+            width,
+            tileset,
+            tile_size,
+            origin: TexCoord { x: 0, y: 0 },
+            columns: ((256 - tileset.tex_coord.x as u16) / tile_size as u16) as u8,
+            wrap: false,
+        })
+    }
+
+    /// Gets the size of the tilemap in tiles.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.tiles.len() / self.width)
+    }
+
+    /// Gets the tile at `(x, y)` if it's within the tilemap.
+    pub fn get_tile(&self, x: usize, y: usize) -> Option<MapTile> {
+        if x >= self.width {
+            return None
+        }
+        self.tiles.get(y * self.width + x).copied()
+    }
+
+    /// Sets the tiles, keeping the tilemap's width.
+    ///
+    /// Returns `None` without changing the tiles if the tilemap's width
+    /// doesn't evenly divide the number of tiles or if a tile's palette is past
+    /// the bottom of VRAM.
+    pub fn set_tiles(&mut self, tiles: &'a [MapTile]) -> Option<&mut Self> {
+        if tiles.len() % self.width != 0 || !palettes_fit(&self.tileset, tiles) {
+            return None
+        }
+        self.tiles = tiles;
+        Some(self)
+    }
+
+    /// Sets the texcoord of the tileset's first tile relative to the TIM's
+    /// bitmap and the number of tiles in each row of the tileset.
+    pub fn set_origin(&mut self, origin: TexCoord, columns: u8) -> &mut Self {
+        self.origin = origin;
+        self.columns = columns.max(1);
+        self
+    }
+
+    /// Checks if the tilemap repeats past its edges.
+    pub fn get_wrap(&self) -> bool {
+        self.wrap
+    }
+
+    /// Sets whether the tilemap repeats past its edges.
+    pub fn set_wrap(&mut self, wrap: bool) -> &mut Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Returns an iterator over the tiles visible on a `screen` sized view
+    /// with its upper-left corner at `camera`.
+    ///
+    /// Each tile is paired with its position relative to the view.
+    pub fn visible_tiles(
+        &self, camera: Vertex, screen: Vertex,
+    ) -> impl Iterator<Item = (Vertex, MapTile)> + '_ {
+        let size = self.tile_size as i32;
+        let (width, height) = self.size();
+        let (width, height) = (width as i32, height as i32);
+        let (cx, cy) = (camera.0 as i32, camera.1 as i32);
+        let first = (cx.div_euclid(size), cy.div_euclid(size));
+        let last = (
+            (cx + screen.0 as i32 - 1).div_euclid(size),
+            (cy + screen.1 as i32 - 1).div_euclid(size),
+        );
+        let wrap = self.wrap;
+        (first.1..=last.1)
+            .flat_map(move |ty| (first.0..=last.0).map(move |tx| (tx, ty)))
+            .filter_map(move |(tx, ty)| {
+                let (x, y) = if wrap {
+                    (tx.rem_euclid(width), ty.rem_euclid(height))
+                } else if (0..width).contains(&tx) && (0..height).contains(&ty) {
+                    (tx, ty)
+                } else {
+                    return None
+                };
+                let tile = self.get_tile(x as usize, y as usize)?;
+                let pos = Vertex((tx * size - cx) as i16, (ty * size - cy) as i16);
+                Some((pos, tile))
+            })
+    }
+
+    /// Inserts the tiles visible from `camera` on a `screen` sized view into
+    /// `ot` at depth `z`.
+    ///
+    /// Rectangles can't be flipped through their texcoords, so tiles are
+    /// grouped by flip with each group preceded by a [`DrawMode`] selecting the
+    /// tileset's texture page. The last group sent is the unflipped tiles so
+    /// the draw mode's flip bits are cleared when the tilemap is done. Returns
+    /// the number of tiles inserted or `None` without inserting anything if
+    /// `prims` doesn't have room for all the tiles or `z` is not a valid depth
+    /// for `ot`.
+    pub fn draw<const N: usize, const Z: usize>(
+        &self, camera: Vertex, screen: Vertex, prims: &mut PrimitiveBuffer<N>,
+        ot: &mut OrderingTable<Z>, z: usize,
+    ) -> Option<usize> {
+        if z >= Z {
+            return None
+        }
+        // Count the tiles in each group first so a partial tilemap is never
+        // left in `ot`
+        let mut groups = [0; 4];
+        for (_, tile) in self.visible_tiles(camera, screen) {
+            let flip = tile.flip();
+            groups[flip.x as usize | (flip.y as usize) << 1] += 1;
+        }
+        let tile_words = match self.tile_size {
+            TileSize::Size8 => Packet::<Sprt8>::WORDS,
+            TileSize::Size16 => Packet::<Sprt16>::WORDS,
+        };
+        let draw_modes = 1 + groups[1..].iter().filter(|&&group| group != 0).count();
+        let count = groups.iter().sum::<usize>();
+        if count * tile_words + draw_modes * Packet::<DrawMode>::WORDS > prims.remaining() {
+            return None
+        }
+        // Packets in the same bucket are sent in the reverse order of insertion
+        for (flip, group) in FLIPS.into_iter().zip(groups) {
+            for (pos, tile) in self.visible_tiles(camera, screen) {
+                if tile.flip() != flip {
+                    continue
+                }
+                let tex_coord = self.tex_coord(tile);
+                let clut = self.clut(tile);
+                let color = TexColor::from(WHITE);
+                match self.tile_size {
+                    TileSize::Size8 => {
+                        let mut sprt = Sprt8::new();
+                        sprt.set_offset(pos)
+                            .set_tex_coord(tex_coord)
+                            .set_color(color);
+                        if let Some(clut) = clut {
+                            sprt.set_clut(clut);
+                        }
+                        prims.insert(ot, z, sprt)?;
+                    },
+                    TileSize::Size16 => {
+                        let mut sprt = Sprt16::new();
+                        sprt.set_offset(pos)
+                            .set_tex_coord(tex_coord)
+                            .set_color(color);
+                        if let Some(clut) = clut {
+                            sprt.set_clut(clut);
+                        }
+                        prims.insert(ot, z, sprt)?;
+                    },
+                }
+            }
+            if group != 0 || flip == Flip::NONE {
+                let mut draw_mode = self.tileset.draw_mode();
+                draw_mode.set_x_flip(flip.x).set_y_flip(flip.y);
+                prims.insert(ot, z, draw_mode)?;
+            }
+        }
+        Some(count)
+    }
+
+    // Flipped rectangles step backwards through the texture from their
+    // texcoord, so it's moved to the far edge of the tile.
+    fn tex_coord(&self, tile: MapTile) -> TexCoord {
+        let size = self.tile_size as u8;
+        let index = tile.index();
+        let col = (index % self.columns as u16) as u8;
+        let row = (index / self.columns as u16) as u8;
+        let flip = tile.flip();
+        let origin = self.tileset.page_coord(self.origin);
+        let x = origin
+            .x
+            .wrapping_add(col.wrapping_mul(size))
+            .wrapping_add(if flip.x { size - 1 } else { 0 });
+        let y = origin
+            .y
+            .wrapping_add(row.wrapping_mul(size))
+            .wrapping_add(if flip.y { size - 1 } else { 0 });
+        TexCoord { x, y }
+    }
+
+    fn clut(&self, tile: MapTile) -> Option<Clut> {
+        let base = self.tileset.clut?;
+        let palette = Vertex::from(base) + Vertex(0, tile.palette() as i16);
+        // SAFETY: `Tilemap::new` and `Tilemap::set_tiles` check that every
+        // tile's palette is within VRAM.
+        Some(unsafe { Clut::try_from(palette).unwrap_unchecked() })
+    }
+}
+
+/// Checks if the CLUT of every tile's palette is within VRAM. Palettes are
+/// ignored if the tileset doesn't have a CLUT.
+fn palettes_fit(tileset: &LoadedTIM, tiles: &[MapTile]) -> bool {
+    let base = match tileset.clut {
+        Some(base) => base,
+        None => return true,
+    };
+    let last = tiles.iter().map(|tile| tile.palette()).max().unwrap_or(0);
+    Clut::try_from(Vertex::from(base) + Vertex(0, last as i16)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MapTile, TileSize, Tilemap};
+    use crate::framebuffer::LoadedTIM;
+    use crate::gpu::{Bpp, Clut, Flip, OrderingTable, PrimitiveBuffer, TexCoord, TexPage, Vertex};
+
+    fn tileset() -> LoadedTIM {
+        LoadedTIM {
+            tex_page: TexPage::try_from(Vertex(5, 0)).unwrap(),
+            tex_coord: TexCoord { x: 0, y: 0 },
+            bpp: Bpp::Bits4,
+            clut: Some(Clut::try_from(Vertex(0, 480)).unwrap()),
+        }
+    }
+
+    const TILES: [MapTile; 6] = [
+        MapTile(0),
+        MapTile(1),
+        MapTile(2),
+        MapTile(3),
+        MapTile(4),
+        MapTile(5),
+    ];
+
+    #[test_case]
+    fn map_tile() {
+        let tile = MapTile::new(37).with_flip(Flip::X).with_palette(3);
+        assert!(tile.index() == 37);
+        assert!(tile.flip() == Flip::X);
+        assert!(tile.palette() == 3);
+        let tile = tile.with_flip(Flip::Y);
+        assert!(tile.flip() == Flip::Y);
+        assert!(tile.0 == 37 | 1 << 11 | 3 << 12);
+    }
+
+    #[test_case]
+    fn visible() {
+        let map = Tilemap::new(&TILES, 3, tileset(), TileSize::Size8).unwrap();
+        assert!(map.size() == (3, 2));
+        let mut tiles = map.visible_tiles(Vertex(4, 8), Vertex(8, 8));
+        assert!(tiles.next() == Some((Vertex(-4, 0), MapTile(3))));
+        assert!(tiles.next() == Some((Vertex(4, 0), MapTile(4))));
+        assert!(tiles.next().is_none());
+        // Tiles past the edges are skipped
+        assert!(map.visible_tiles(Vertex(-8, -8), Vertex(16, 16)).count() == 1);
+    }
+
+    #[test_case]
+    fn wrap() {
+        let mut map = Tilemap::new(&TILES, 3, tileset(), TileSize::Size16).unwrap();
+        map.set_wrap(true);
+        let mut tiles = map.visible_tiles(Vertex(-16, -16), Vertex(16, 16));
+        assert!(tiles.next() == Some((Vertex(0, 0), MapTile(5))));
+        assert!(tiles.next().is_none());
+        assert!(map.visible_tiles(Vertex(40, 0), Vertex(64, 32)).count() == 5 * 2);
+    }
+
+    #[test_case]
+    fn tex_coord() {
+        let mut map = Tilemap::new(&TILES, 3, tileset(), TileSize::Size8).unwrap();
+        map.set_origin(TexCoord { x: 16, y: 32 }, 4);
+        assert!(map.tex_coord(MapTile::new(5)) == TexCoord { x: 24, y: 40 });
+        let flipped = MapTile::new(5).with_flip(Flip::XY);
+        assert!(map.tex_coord(flipped) == TexCoord { x: 31, y: 47 });
+        let clut = map.clut(MapTile::new(0).with_palette(2)).unwrap();
+        assert!(Vertex::from(clut) == Vertex(0, 482));
+        // Tiles are relative to the tileset's bitmap
+        let mut tileset = tileset();
+        tileset.tex_coord = TexCoord { x: 64, y: 16 };
+        let mut map = Tilemap::new(&TILES, 3, tileset, TileSize::Size8).unwrap();
+        assert!(map.tex_coord(MapTile::new(25)) == TexCoord { x: 72, y: 24 });
+        map.set_origin(TexCoord { x: 16, y: 32 }, 4);
+        assert!(map.tex_coord(MapTile::new(5)) == TexCoord { x: 88, y: 56 });
+    }
+
+    #[test_case]
+    fn palettes() {
+        // Every palette fits below a CLUT at (0, 480) but palette 15 is past the
+        // bottom of VRAM for one at (0, 500)
+        let tiles = [MapTile::new(0), MapTile::new(1).with_palette(15)];
+        assert!(Tilemap::new(&tiles, 2, tileset(), TileSize::Size8).is_some());
+        let mut tileset = tileset();
+        tileset.clut = Some(Clut::try_from(Vertex(0, 500)).unwrap());
+        assert!(Tilemap::new(&tiles, 2, tileset, TileSize::Size8).is_none());
+        let mut map = Tilemap::new(&tiles[..1], 1, tileset, TileSize::Size8).unwrap();
+        assert!(map.set_tiles(&tiles).is_none());
+        assert!(map.size() == (1, 1));
+        // Palettes are ignored without a CLUT
+        tileset.clut = None;
+        assert!(Tilemap::new(&tiles, 2, tileset, TileSize::Size8).is_some());
+    }
+
+    #[test_case]
+    fn draw() {
+        let tiles = [
+            MapTile::new(0),
+            MapTile::new(1).with_flip(Flip::X),
+            MapTile::new(2),
+            MapTile::new(3).with_flip(Flip::X),
+        ];
+        let map = Tilemap::new(&tiles, 2, tileset(), TileSize::Size8).unwrap();
+        let mut prims = PrimitiveBuffer::<64>::new();
+        let mut ot = OrderingTable::<4>::new();
+        assert!(map
+            .draw(Vertex(0, 0), Vertex(16, 16), &mut prims, &mut ot, 4)
+            .is_none());
+        assert!(prims.remaining() == 64);
+        // Nothing is inserted unless all the packets fit
+        let mut small = PrimitiveBuffer::<19>::new();
+        assert!(map
+            .draw(Vertex(0, 0), Vertex(16, 16), &mut small, &mut ot, 1)
+            .is_none());
+        assert!(small.remaining() == 19);
+        let count = map.draw(Vertex(0, 0), Vertex(16, 16), &mut prims, &mut ot, 1);
+        assert!(count == Some(4));
+        // Four `Sprt8` packets and two `DrawMode` packets
+        assert!(prims.remaining() == 64 - 4 * 4 - 2 * 2);
+    }
+}
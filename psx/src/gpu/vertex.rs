@@ -116,7 +116,8 @@ impl<const N: usize, const X: usize, const Y: usize> From<PackedVertex<N, X, Y>>
     }
 }
 
-#[cfg(test)]
+// These tests use the fuzz macros
+#[cfg(all(test, target_arch = "mips"))]
 mod tests {
 
     use super::PackedVertex;
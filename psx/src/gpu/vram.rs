@@ -0,0 +1,317 @@
+use crate::framebuffer::Framebuffer;
+use crate::gpu::{Bpp, Clut, TexCoord, TexPage, Vertex};
+use core::cell::Cell;
+use core::fmt;
+
+const VRAM_WIDTH: i16 = 1024;
+const VRAM_HEIGHT: i16 = 512;
+// VRAM is tracked in columns of 16 halfwords since CLUTs must be aligned to
+// 16 entries.
+const COLUMN_WIDTH: i16 = 16;
+const COLUMNS: i16 = VRAM_WIDTH / COLUMN_WIDTH;
+const PAGE_WIDTH: i16 = 64;
+const PAGE_HEIGHT: i16 = 256;
+
+/// Gets the number of texels in each halfword for a given `Bpp`.
+const fn texels_per_halfword(bpp: Bpp) -> i16 {
+    match bpp {
+        Bpp::Bits4 => 4,
+        Bpp::Bits8 => 2,
+        Bpp::Bits15 => 1,
+    }
+}
+
+/// Gets the texcoord of `offset` within its texture page for textures with the
+/// specified bits per pixel.
+pub(crate) fn page_tex_coord(offset: Vertex, bpp: Bpp) -> TexCoord {
+    TexCoord {
+        x: ((offset.0 % PAGE_WIDTH) * texels_per_halfword(bpp)) as u8,
+        y: (offset.1 % PAGE_HEIGHT) as u8,
+    }
+}
+
+/// Gets a bitmask of the columns spanned by `width` halfwords starting at `x`.
+fn column_mask(x: i16, width: i16) -> u64 {
+    let first = x / COLUMN_WIDTH;
+    let last = (x + width + COLUMN_WIDTH - 1) / COLUMN_WIDTH;
+    let columns = (last - first) as u32;
+    let mask = if columns >= 64 {
+        u64::MAX
+    } else {
+        (1 << columns) - 1
+    };
+    mask << first
+}
+
+/// An allocator for rectangular regions of VRAM.
+///
+/// This tracks VRAM in 16-halfword wide columns, one line at a time. Textures
+/// are placed so they can be addressed from a single 64x256 halfword texture
+/// page and CLUTs are aligned to 16 entries as required by [`Clut`]. Allocated
+/// [`VRAMRegion`]s are freed when dropped.
+pub struct VRAMAllocator {
+    rows: [Cell<u64>; VRAM_HEIGHT as usize],
+}
+
+impl VRAMAllocator {
+    /// Creates an allocator where all of VRAM is free.
+    pub fn empty() -> Self {
+        VRAMAllocator {
+            rows: core::array::from_fn(|_| Cell::new(0)),
+        }
+    }
+
+    /// Creates an allocator which never hands out the regions used by
+    /// `fb`'s display buffers.
+    pub fn new<const B: usize>(fb: &Framebuffer<B>) -> Self {
+        let vram = Self::empty();
+        for (offset, size) in fb.buffers() {
+            vram.mark(offset, size, true);
+        }
+        vram
+    }
+
+    /// Marks the columns spanned by a rectangle as used or free.
+    fn mark(&self, offset: Vertex, size: Vertex, used: bool) {
+        let mask = column_mask(offset.0, size.0);
+        for row in &self.rows[offset.1 as usize..(offset.1 + size.1) as usize] {
+            let bits = if used {
+                row.get() | mask
+            } else {
+                row.get() & !mask
+            };
+            row.set(bits);
+        }
+    }
+
+    /// Gets the columns used in any of the `height` rows starting at `y`.
+    fn used_columns(&self, y: i16, height: i16) -> u64 {
+        self.rows[y as usize..(y + height) as usize]
+            .iter()
+            .fold(0, |used, row| used | row.get())
+    }
+
+    /// Allocates the first free region of `size` with its y coordinate in
+    /// `ys` and a column for which `valid_column` is true.
+    fn find(
+        &self, size: Vertex, ys: impl Iterator<Item = i16>, valid_column: impl Fn(i16) -> bool,
+    ) -> Option<VRAMRegion> {
+        let columns = (size.0 + COLUMN_WIDTH - 1) / COLUMN_WIDTH;
+        for y in ys {
+            let used = self.used_columns(y, size.1);
+            for column in (0..=COLUMNS - columns).filter(|&c| valid_column(c)) {
+                let offset = Vertex(column * COLUMN_WIDTH, y);
+                if used & column_mask(offset.0, size.0) == 0 {
+                    return Some(self.claim(offset, size))
+                }
+            }
+        }
+        None
+    }
+
+    fn claim(&self, offset: Vertex, size: Vertex) -> VRAMRegion {
+        self.mark(offset, size, true);
+        VRAMRegion {
+            vram: self,
+            offset,
+            size,
+        }
+    }
+
+    /// Reserves the region at `offset` of `size` halfwords.
+    ///
+    /// Returns `None` if the region is empty, out of bounds or overlaps an
+    /// allocated region. The region is extended to the nearest 16-halfword
+    /// columns.
+    pub fn reserve(&self, offset: Vertex, size: Vertex) -> Option<VRAMRegion> {
+        if offset.0 < 0 || offset.1 < 0 || size.0 <= 0 || size.1 <= 0 {
+            return None
+        }
+        if offset.0 + size.0 > VRAM_WIDTH || offset.1 + size.1 > VRAM_HEIGHT {
+            return None
+        }
+        if self.used_columns(offset.1, size.1) & column_mask(offset.0, size.0) != 0 {
+            return None
+        }
+        Some(self.claim(offset, size))
+    }
+
+    /// Allocates a region for a texture of `size` halfwords with the specified
+    /// bits per pixel.
+    ///
+    /// The region is placed so that the whole texture can be addressed from
+    /// [`VRAMRegion::tex_page`] starting at [`VRAMRegion::tex_coord`]. Returns
+    /// `None` if the texture is too large for a texture page or there isn't a
+    /// free region large enough.
+    pub fn alloc_texture(&self, size: Vertex, bpp: Bpp) -> Option<VRAMRegion> {
+        // Texture coordinates can address up to 256 texels from the page's
+        // upper left corner
+        let max_width = 256 / texels_per_halfword(bpp);
+        if size.0 <= 0 || size.1 <= 0 || size.0 > max_width || size.1 > PAGE_HEIGHT {
+            return None
+        }
+        let ys = (0..=VRAM_HEIGHT - size.1).filter(|y| y % PAGE_HEIGHT + size.1 <= PAGE_HEIGHT);
+        let valid_column = |c: i16| (c * COLUMN_WIDTH) % PAGE_WIDTH + size.0 <= max_width;
+        self.find(size, ys, valid_column)
+    }
+
+    /// Allocates a region for a CLUT for textures with the specified bits per
+    /// pixel.
+    ///
+    /// CLUTs are allocated from the bottom of VRAM to avoid fragmenting the
+    /// space used by textures. Returns `None` for 15-bit textures which don't
+    /// use a CLUT or if there isn't a free region large enough.
+    pub fn alloc_clut(&self, bpp: Bpp) -> Option<VRAMRegion> {
+        let entries = match bpp {
+            Bpp::Bits4 => 16,
+            Bpp::Bits8 => 256,
+            Bpp::Bits15 => return None,
+        };
+        self.find(Vertex(entries, 1), (0..VRAM_HEIGHT).rev(), |_| true)
+    }
+}
+
+impl fmt::Debug for VRAMAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self
+            .rows
+            .iter()
+            .map(|row| row.get().count_ones())
+            .sum::<u32>();
+        f.debug_struct("VRAMAllocator")
+            .field("used_halfwords", &(used * COLUMN_WIDTH as u32))
+            .finish()
+    }
+}
+
+/// A region of VRAM allocated by a [`VRAMAllocator`].
+///
+/// The region is freed when this is dropped. Note that this does not track
+/// anything the GPU may still be reading from the region.
+#[derive(Debug)]
+pub struct VRAMRegion<'a> {
+    vram: &'a VRAMAllocator,
+    offset: Vertex,
+    size: Vertex,
+}
+
+impl VRAMRegion<'_> {
+    /// Gets the region's offset in VRAM.
+    pub fn offset(&self) -> Vertex {
+        self.offset
+    }
+
+    /// Gets the region's size in halfwords.
+    pub fn size(&self) -> Vertex {
+        self.size
+    }
+
+    /// Gets the texture page containing the region's upper left corner.
+    pub fn tex_page(&self) -> TexPage {
+        let page = Vertex(self.offset.0 / PAGE_WIDTH, self.offset.1 / PAGE_HEIGHT);
+        // SAFETY: The region is in bounds so its texture page is valid.
+        unsafe { TexPage::try_from(page).unwrap_unchecked() }
+    }
+
+    /// Gets the texture coordinate of the region's upper left corner within its
+    /// texture page for textures with the specified bits per pixel.
+    pub fn tex_coord(&self, bpp: Bpp) -> TexCoord {
+        page_tex_coord(self.offset, bpp)
+    }
+
+    /// Gets the region's CLUT attribute.
+    ///
+    /// Returns `None` if the region isn't aligned to 16 entries.
+    pub fn clut(&self) -> Option<Clut> {
+        if self.offset.0 % COLUMN_WIDTH != 0 {
+            return None
+        }
+        Clut::try_from(Vertex(self.offset.0 / COLUMN_WIDTH, self.offset.1)).ok()
+    }
+}
+
+impl Drop for VRAMRegion<'_> {
+    fn drop(&mut self) {
+        self.vram.mark(self.offset, self.size, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VRAMAllocator;
+    #[cfg(target_arch = "mips")]
+    use crate::framebuffer::Framebuffer;
+    use crate::gpu::{Bpp, Clut, TexCoord, TexPage, Vertex};
+    #[cfg(target_arch = "mips")]
+    use crate::include_tim;
+
+    #[test_case]
+    fn reserve() {
+        let vram = VRAMAllocator::empty();
+        let a = vram.reserve(Vertex(0, 0), Vertex(32, 32)).unwrap();
+        assert!(vram.reserve(Vertex(16, 16), Vertex(32, 32)).is_none());
+        assert!(vram.reserve(Vertex(1000, 0), Vertex(32, 32)).is_none());
+        assert!(vram.reserve(Vertex(32, 0), Vertex(32, 32)).is_some());
+        drop(a);
+        assert!(vram.reserve(Vertex(16, 16), Vertex(16, 16)).is_some());
+    }
+
+    // Creating a framebuffer configures the GPU
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn framebuffer() {
+        let fb = Framebuffer::default();
+        let vram = VRAMAllocator::new(&fb);
+        assert!(vram.reserve(Vertex(0, 0), Vertex(16, 16)).is_none());
+        assert!(vram.reserve(Vertex(304, 464), Vertex(16, 16)).is_none());
+        let tex = vram.alloc_texture(Vertex(64, 256), Bpp::Bits4).unwrap();
+        assert!(tex.offset() == Vertex(320, 0));
+    }
+
+    #[test_case]
+    fn alloc_texture() {
+        let vram = VRAMAllocator::empty();
+        let a = vram.alloc_texture(Vertex(48, 200), Bpp::Bits4).unwrap();
+        let b = vram.alloc_texture(Vertex(32, 100), Bpp::Bits4).unwrap();
+        // The second texture can't share the first's page horizontally
+        assert!(b.offset() == Vertex(64, 0));
+        assert!(b.tex_page() == TexPage::try_from(Vertex(1, 0)).unwrap());
+        let c = vram.alloc_texture(Vertex(64, 56), Bpp::Bits8).unwrap();
+        assert!(c.offset() == Vertex(96, 0));
+        assert!(c.tex_coord(Bpp::Bits8) == TexCoord { x: 64, y: 0 });
+        let d = vram.alloc_texture(Vertex(16, 16), Bpp::Bits4).unwrap();
+        assert!(d.tex_coord(Bpp::Bits4) == TexCoord { x: 192, y: 0 });
+        assert!(vram.alloc_texture(Vertex(65, 16), Bpp::Bits4).is_none());
+        assert!(vram.alloc_texture(Vertex(16, 257), Bpp::Bits15).is_none());
+        drop(a);
+        let e = vram.alloc_texture(Vertex(48, 200), Bpp::Bits4).unwrap();
+        assert!(e.offset() == Vertex(0, 0));
+    }
+
+    #[test_case]
+    fn alloc_clut() {
+        let vram = VRAMAllocator::empty();
+        let a = vram.alloc_clut(Bpp::Bits4).unwrap();
+        let b = vram.alloc_clut(Bpp::Bits8).unwrap();
+        assert!(a.clut() == Some(Clut::try_from(Vertex(0, 511)).unwrap()));
+        assert!(b.clut() == Some(Clut::try_from(Vertex(1, 511)).unwrap()));
+        assert!(b.size() == Vertex(256, 1));
+        assert!(vram.alloc_clut(Bpp::Bits15).is_none());
+    }
+
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn load_tim_into() {
+        let mut fb = Framebuffer::default();
+        let vram = VRAMAllocator::new(&fb);
+        let font = include_tim!("../../font.tim");
+        let bmp = vram.alloc_texture(font.bmp.size, font.bpp).unwrap();
+        let clut = vram.alloc_clut(font.bpp).unwrap();
+        assert!(fb.load_tim_into(font, &bmp, None).is_none());
+        let font = include_tim!("../../font.tim");
+        let loaded = fb.load_tim_into(font, &bmp, Some(&clut)).unwrap();
+        assert!(loaded.tex_page == bmp.tex_page());
+        assert!(loaded.tex_coord == bmp.tex_coord(Bpp::Bits4) && loaded.bpp == Bpp::Bits4);
+        assert!(loaded.clut == clut.clut());
+    }
+}
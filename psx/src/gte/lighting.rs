@@ -0,0 +1,411 @@
+use super::{pack, Backend, Command, Matrix, Translation, Vector, GTE};
+use crate::gpu::colors::BLACK;
+use crate::gpu::Color;
+use crate::hw::gte::*;
+
+/// The number of directional lights the GTE supports.
+pub const MAX_LIGHTS: usize = 3;
+
+/// Converts a color component to a 1.3.12 intensity where `0xFF` is about
+/// `1.0`.
+fn intensity(c: u8) -> i16 {
+    (c as i16) << 4
+}
+
+fn to_color(rgb: u32) -> Color {
+    Color::new(rgb as u8, (rgb >> 8) as u8, (rgb >> 16) as u8)
+}
+
+/// A directional light.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Light {
+    /// A unit vector pointing towards the light in 1.3.12 fixed-point.
+    pub direction: [i16; 3],
+    /// The light's color.
+    pub color: Color,
+}
+
+/// A lighting configuration for the GTE's lighting commands.
+///
+/// Surfaces are lit by up to [`MAX_LIGHTS`] directional lights and an ambient
+/// color. Depth cueing blends lit colors towards the far color, which may be
+/// used for fog.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lighting {
+    lights: [Option<Light>; MAX_LIGHTS],
+    ambient: Color,
+    far_color: Color,
+    fog: Option<(u16, u16)>,
+}
+
+impl Lighting {
+    /// Creates a lighting configuration with no lights and a black ambient and
+    /// far color.
+    pub const fn new() -> Self {
+        Lighting {
+            lights: [None; MAX_LIGHTS],
+            ambient: BLACK,
+            far_color: BLACK,
+            fog: None,
+        }
+    }
+
+    /// Gets the light in slot `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than [`MAX_LIGHTS`].
+    pub fn get_light(&self, idx: usize) -> Option<Light> {
+        self.lights[idx]
+    }
+
+    /// Sets or removes the light in slot `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than [`MAX_LIGHTS`].
+    pub fn set_light(&mut self, idx: usize, light: Option<Light>) -> &mut Self {
+        self.lights[idx] = light;
+        self
+    }
+
+    /// Gets the ambient color.
+    pub fn get_ambient(&self) -> Color {
+        self.ambient
+    }
+
+    /// Sets the ambient color added to all lit surfaces.
+    pub fn set_ambient(&mut self, color: Color) -> &mut Self {
+        self.ambient = color;
+        self
+    }
+
+    /// Gets the far color.
+    pub fn get_far_color(&self) -> Color {
+        self.far_color
+    }
+
+    /// Sets the far color that depth cueing blends towards.
+    pub fn set_far_color(&mut self, color: Color) -> &mut Self {
+        self.far_color = color;
+        self
+    }
+
+    /// Fades colors to the far color between the screen depths `near` and
+    /// `far`.
+    ///
+    /// Without fog depth cueing is left to
+    /// [`GTE::set_depth_cueing`][crate::gte::GTE::set_depth_cueing].
+    pub fn set_fog(&mut self, color: Color, near: u16, far: u16) -> &mut Self {
+        self.far_color = color;
+        self.fog = if near < far { Some((near, far)) } else { None };
+        self
+    }
+
+    /// Gets the light matrix whose rows are the lights' directions.
+    pub fn light_matrix(&self) -> [[i16; 3]; 3] {
+        self.lights
+            .map(|light| light.map_or([0; 3], |light| light.direction))
+    }
+
+    /// Gets the light color matrix whose columns are the lights' colors.
+    pub fn color_matrix(&self) -> [[i16; 3]; 3] {
+        let colors = self
+            .lights
+            .map(|light| light.map_or(BLACK, |light| light.color));
+        [
+            colors.map(|c| intensity(c.red)),
+            colors.map(|c| intensity(c.green)),
+            colors.map(|c| intensity(c.blue)),
+        ]
+    }
+
+    /// Gets the depth cueing coefficient and offset for the fog given the
+    /// projection plane distance `h`.
+    ///
+    /// The depth cueing factor is linear in `h / z` so it's zero at the near
+    /// depth and `0x1000` at the far depth. The coefficient
+    /// `-(near * far << 8) / (h * (far - near))` must fit in an `i16` and the
+    /// offset `(far << 24) / (far - near)` must fit in an `i32`, so the fog
+    /// can't start too close to the camera for `h` and `far - near` must be
+    /// more than `far / 128`. Returns `None` if fog is disabled or either
+    /// value is out of range.
+    pub fn depth_cueing(&self, h: u16) -> Option<(i16, i32)> {
+        let (near, far) = self.fog?;
+        let (h, near, far) = (h.max(1) as i64, near as i64, far as i64);
+        // DQA has 8 fractional bits and is multiplied by h / z with 16
+        // fractional bits. DQB has 24 fractional bits.
+        let dqa = -((near * far) << 8) / (h * (far - near));
+        let dqb = (far << 24) / (far - near);
+        Some((i16::try_from(dqa).ok()?, i32::try_from(dqb).ok()?))
+    }
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Backend> GTE<B> {
+    /// Loads a lighting configuration.
+    ///
+    /// If fog is enabled this also sets the depth cueing parameters using the
+    /// current projection plane distance, so that should be set first. They're
+    /// left unchanged if [`Lighting::depth_cueing`] is out of range.
+    pub fn set_lighting(&mut self, lighting: &Lighting) -> &mut Self {
+        self.set_light_matrix(lighting.light_matrix())
+            .set_color_matrix(lighting.color_matrix())
+            .set_background_color(lighting.ambient)
+            .set_far_color(lighting.far_color);
+        let h = self.load::<H>();
+        if let Some((dqa, dqb)) = lighting.depth_cueing(h) {
+            self.set_depth_cueing(dqa, dqb);
+        }
+        self
+    }
+
+    /// Sets the light matrix.
+    pub fn set_light_matrix(&mut self, m: [[i16; 3]; 3]) -> &mut Self {
+        self.store::<L11_12>(pack(m[0][0], m[0][1]));
+        self.store::<L13_21>(pack(m[0][2], m[1][0]));
+        self.store::<L22_23>(pack(m[1][1], m[1][2]));
+        self.store::<L31_32>(pack(m[2][0], m[2][1]));
+        self.store::<L33>(m[2][2]);
+        self
+    }
+
+    /// Sets the light color matrix.
+    pub fn set_color_matrix(&mut self, m: [[i16; 3]; 3]) -> &mut Self {
+        self.store::<LR11_12>(pack(m[0][0], m[0][1]));
+        self.store::<LR13_21>(pack(m[0][2], m[1][0]));
+        self.store::<LR22_23>(pack(m[1][1], m[1][2]));
+        self.store::<LR31_32>(pack(m[2][0], m[2][1]));
+        self.store::<LR33>(m[2][2]);
+        self
+    }
+
+    /// Sets the background color added by lighting commands.
+    pub fn set_background_color(&mut self, color: Color) -> &mut Self {
+        self.store::<RBK>(intensity(color.red) as i32);
+        self.store::<GBK>(intensity(color.green) as i32);
+        self.store::<BBK>(intensity(color.blue) as i32);
+        self
+    }
+
+    /// Sets the far color used by depth cueing.
+    pub fn set_far_color(&mut self, color: Color) -> &mut Self {
+        self.store::<RFC>(intensity(color.red) as i32);
+        self.store::<GFC>(intensity(color.green) as i32);
+        self.store::<BFC>(intensity(color.blue) as i32);
+        self
+    }
+
+    /// Sets the color `RGBC` used by lighting and depth cueing commands.
+    pub fn set_color(&mut self, color: Color) -> &mut Self {
+        self.store::<RGBC>(u32::from(color));
+        self
+    }
+
+    fn colors(&self) -> [Color; 3] {
+        [
+            to_color(self.load::<RGB0>()),
+            to_color(self.load::<RGB1>()),
+            to_color(self.load::<RGB2>()),
+        ]
+    }
+
+    fn color(&self) -> Color {
+        to_color(self.load::<RGB2>())
+    }
+
+    /// Lights a surface with normal `normal` using NCS.
+    pub fn ncs(&mut self, normal: [i16; 3]) -> Color {
+        self.set_v0(normal).run::<{ Command::NCS.to_bits() }>();
+        self.color()
+    }
+
+    /// Lights three surfaces with normals `normals` using NCT.
+    pub fn nct(&mut self, normals: [[i16; 3]; 3]) -> [Color; 3] {
+        self.set_vectors(normals)
+            .run::<{ Command::NCT.to_bits() }>();
+        self.colors()
+    }
+
+    /// Lights a surface of color `color` with normal `normal` using NCCS.
+    pub fn nccs(&mut self, normal: [i16; 3], color: Color) -> Color {
+        self.set_v0(normal)
+            .set_color(color)
+            .run::<{ Command::NCCS.to_bits() }>();
+        self.color()
+    }
+
+    /// Lights three surfaces of color `color` with normals `normals` using
+    /// NCCT.
+    pub fn ncct(&mut self, normals: [[i16; 3]; 3], color: Color) -> [Color; 3] {
+        self.set_vectors(normals)
+            .set_color(color)
+            .run::<{ Command::NCCT.to_bits() }>();
+        self.colors()
+    }
+
+    /// Lights a surface of color `color` with normal `normal` and applies
+    /// depth cueing using NCDS.
+    ///
+    /// This uses the depth cueing factor of the last perspective
+    /// transformation.
+    pub fn ncds(&mut self, normal: [i16; 3], color: Color) -> Color {
+        self.set_v0(normal)
+            .set_color(color)
+            .run::<{ Command::NCDS.to_bits() }>();
+        self.color()
+    }
+
+    /// Lights three surfaces of color `color` with normals `normals` and
+    /// applies depth cueing using NCDT.
+    ///
+    /// This uses the depth cueing factor of the last perspective
+    /// transformation.
+    pub fn ncdt(&mut self, normals: [[i16; 3]; 3], color: Color) -> [Color; 3] {
+        self.set_vectors(normals)
+            .set_color(color)
+            .run::<{ Command::NCDT.to_bits() }>();
+        self.colors()
+    }
+
+    /// Lights the last projected triangle with CC if it faces the camera.
+    ///
+    /// This culls the triangle with [`GTE::nclip`] first and returns `None` if
+    /// it faces away. Otherwise `normal` is multiplied by the light matrix and
+    /// the resulting intensities are applied to `color`.
+    pub fn nclip_cc(&mut self, normal: [i16; 3], color: Color) -> Option<Color> {
+        if self.nclip() <= 0 {
+            return None
+        }
+        self.light_intensities(normal)
+            .set_color(color)
+            .run::<{ Command::CC.to_bits() }>();
+        Some(self.color())
+    }
+
+    /// Lights the last projected triangle with CDP if it faces the camera.
+    ///
+    /// This is the same as [`GTE::nclip_cc`] but also applies depth cueing
+    /// using the depth cueing factor of the last perspective transformation.
+    pub fn nclip_cdp(&mut self, normal: [i16; 3], color: Color) -> Option<Color> {
+        if self.nclip() <= 0 {
+            return None
+        }
+        self.light_intensities(normal)
+            .set_color(color)
+            .run::<{ Command::CDP.to_bits() }>();
+        Some(self.color())
+    }
+
+    /// Multiplies `normal` by the light matrix, leaving the result in `IR`.
+    fn light_intensities(&mut self, normal: [i16; 3]) -> &mut Self {
+        const CMD: Command = Command::mvmva(Matrix::Light, Vector::V0, Translation::None).lm(true);
+        self.set_v0(normal).run::<{ CMD.to_bits() }>()
+    }
+
+    /// Applies depth cueing to `color` with DPCS.
+    ///
+    /// This uses the depth cueing factor in `IR0`.
+    pub fn dpcs(&mut self, color: Color) -> Color {
+        self.set_color(color).run::<{ Command::DPCS.to_bits() }>();
+        self.color()
+    }
+
+    /// Applies depth cueing to three colors with DPCT.
+    ///
+    /// This uses the depth cueing factor in `IR0`.
+    pub fn dpct(&mut self, colors: [Color; 3]) -> [Color; 3] {
+        self.store::<RGB0>(u32::from(colors[0]));
+        self.store::<RGB1>(u32::from(colors[1]));
+        self.store::<RGB2>(u32::from(colors[2]));
+        self.run::<{ Command::DPCT.to_bits() }>();
+        self.colors()
+    }
+
+    /// Interpolates between the 1.3.12 intensities `v` and the far color with
+    /// INTPL.
+    ///
+    /// This uses the depth cueing factor in `IR0`.
+    pub fn intpl(&mut self, v: [i16; 3]) -> Color {
+        self.set_ir(v).run::<{ Command::INTPL.to_bits() }>();
+        self.color()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Light, Lighting};
+    use crate::gpu::colors::{BLACK, BLUE, RED, WHITE};
+    use crate::gpu::Color;
+    use crate::gte::tests::new_gte;
+
+    const UP: [i16; 3] = [0, 0, 0x1000];
+
+    #[test_case]
+    fn matrices() {
+        let mut lighting = Lighting::new();
+        lighting
+            .set_light(
+                0,
+                Some(Light {
+                    direction: UP,
+                    color: RED,
+                }),
+            )
+            .set_light(
+                2,
+                Some(Light {
+                    direction: [0x1000, 0, 0],
+                    color: BLUE,
+                }),
+            );
+        assert!(lighting.light_matrix() == [UP, [0; 3], [0x1000, 0, 0]]);
+        assert!(lighting.color_matrix() == [[0xFF0, 0, 0], [0; 3], [0, 0, 0xFF0]]);
+    }
+
+    #[test_case]
+    fn fog() {
+        let mut lighting = Lighting::new();
+        assert!(lighting.depth_cueing(256).is_none());
+        lighting.set_fog(WHITE, 1000, 2000);
+        // (DQB + DQA * (H / SZ << 16)) >> 12 is zero at a depth of 1000 and
+        // 0x1000 at 2000
+        assert!(lighting.depth_cueing(256) == Some((-2000, 2 << 24)));
+        // DQA doesn't fit in an i16 when the fog starts too close for h
+        assert!(lighting.depth_cueing(1).is_none());
+        // DQB doesn't fit in an i32 when the fog's range is too short
+        lighting.set_fog(WHITE, 1000, 1001);
+        assert!(lighting.depth_cueing(0xFFFF).is_none());
+    }
+
+    #[test_case]
+    fn normal_color() {
+        let mut lighting = Lighting::new();
+        lighting.set_light(
+            0,
+            Some(Light {
+                direction: UP,
+                color: WHITE,
+            }),
+        );
+        let mut gte = new_gte();
+        gte.set_lighting(&lighting);
+        assert!(gte.ncs(UP) == Color::new(0xFF, 0xFF, 0xFF));
+        // Negative intensities are clamped to zero
+        assert!(gte.ncs([0, 0, -0x1000]) == BLACK);
+        let half_lit = gte.nccs([0, 0, 0x800], Color::new(128, 64, 32));
+        assert!(half_lit == Color::new(63, 31, 15));
+    }
+
+    #[test_case]
+    fn depth_cue() {
+        let mut gte = new_gte();
+        gte.set_far_color(Color::new(0xFF, 0x80, 0)).set_ir0(0x800);
+        assert!(gte.dpcs(BLACK) == Color::new(0x7F, 0x40, 0));
+    }
+}
@@ -0,0 +1,557 @@
+//! Geometry Transformation Engine commands
+//!
+//! This module issues GTE commands using the registers in
+//! [`hw::gte`][crate::hw::gte]. Matrices use 1.3.12 fixed-point entries,
+//! vectors use 16-bit components and translations use 32-bit components.
+//!
+//! Commands may also run on [`Software`], a bit-exact model of the GTE which
+//! doesn't use cop2. This is the only backend when not building for MIPS, so
+//! code using the GTE can be tested on other hosts.
+use crate::gpu::Vertex;
+#[cfg(target_arch = "mips")]
+use crate::hw::cop0;
+use crate::hw::gte::*;
+#[cfg(target_arch = "mips")]
+use crate::hw::Register;
+use crate::math::Transform;
+#[cfg(target_arch = "mips")]
+use core::arch::asm;
+
+mod lighting;
+mod software;
+
+pub use lighting::{Light, Lighting, MAX_LIGHTS};
+pub use software::Software;
+
+// The cop2 opcode with the bit which marks the instruction as a command
+const COMMAND: u32 = 0x4A00_0000;
+const COMMAND_MASK: u32 = 0xFE00_0000;
+
+// Rejects instructions other than GTE commands since `CMD` is placed directly
+// in the instruction stream.
+struct ValidCommand<const CMD: u32>;
+
+impl<const CMD: u32> ValidCommand<CMD> {
+    const IS_GTE_COMMAND: () = {
+        if CMD & COMMAND_MASK != COMMAND {
+            panic!("GTE::run must be given a GTE command.");
+        }
+    };
+}
+
+/// The matrix multiplied by [`Command::mvmva`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Matrix {
+    /// The rotation matrix
+    Rotation = 0,
+    /// The light source direction matrix
+    Light = 1,
+    /// The light source color matrix
+    LightColor = 2,
+}
+
+/// The vector multiplied by [`Command::mvmva`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Vector {
+    /// `VXY0` and `VZ0`
+    V0 = 0,
+    /// `VXY1` and `VZ1`
+    V1 = 1,
+    /// `VXY2` and `VZ2`
+    V2 = 2,
+    /// `IR1`, `IR2` and `IR3`
+    IR = 3,
+}
+
+/// The vector added by [`Command::mvmva`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Translation {
+    /// The translation vector
+    TR = 0,
+    /// The background color
+    BK = 1,
+    /// The far color
+    FC = 2,
+    /// No translation
+    None = 3,
+}
+
+/// An encoded GTE command.
+///
+/// Commands are built with `const fn`s so they can be passed to [`GTE::run`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Command(u32);
+
+impl Command {
+    /// Perspective transformation of `V0`
+    pub const RTPS: Self = Self::new(0x01).sf(true);
+    /// Normal clipping of the screen XY coordinate FIFO
+    pub const NCLIP: Self = Self::new(0x06);
+    /// Outer product of the rotation matrix's diagonal and `IR`
+    pub const OP: Self = Self::new(0x0C).sf(true);
+    /// Square of `IR`
+    pub const SQR: Self = Self::new(0x28).sf(true);
+    /// Average of the last three screen Z coordinates
+    pub const AVSZ3: Self = Self::new(0x2D);
+    /// Average of the four screen Z coordinates
+    pub const AVSZ4: Self = Self::new(0x2E);
+    /// Perspective transformation of `V0`, `V1` and `V2`
+    pub const RTPT: Self = Self::new(0x30).sf(true);
+    /// General purpose interpolation of `IR0` and `IR`
+    pub const GPF: Self = Self::new(0x3D).sf(true);
+    /// General purpose interpolation of `IR0` and `IR` with base `MAC`
+    pub const GPL: Self = Self::new(0x3E).sf(true);
+    /// Depth cueing of `RGBC`
+    pub const DPCS: Self = Self::new(0x10).sf(true);
+    /// Depth cueing of the color FIFO
+    pub const DPCT: Self = Self::new(0x2A).sf(true);
+    /// Interpolation of `IR` and the far color
+    pub const INTPL: Self = Self::new(0x11).sf(true);
+    /// Normal color of `V0`
+    pub const NCS: Self = Self::new(0x1E).sf(true).lm(true);
+    /// Normal color of `V0`, `V1` and `V2`
+    pub const NCT: Self = Self::new(0x20).sf(true).lm(true);
+    /// Normal color of `V0` multiplied by `RGBC`
+    pub const NCCS: Self = Self::new(0x1B).sf(true).lm(true);
+    /// Normal color of `V0`, `V1` and `V2` multiplied by `RGBC`
+    pub const NCCT: Self = Self::new(0x3F).sf(true).lm(true);
+    /// Normal color of `V0` multiplied by `RGBC` with depth cueing
+    pub const NCDS: Self = Self::new(0x13).sf(true).lm(true);
+    /// Normal color of `V0`, `V1` and `V2` multiplied by `RGBC` with depth
+    /// cueing
+    pub const NCDT: Self = Self::new(0x16).sf(true).lm(true);
+    /// Light intensities in `IR` multiplied by `RGBC`
+    pub const CC: Self = Self::new(0x1C).sf(true).lm(true);
+    /// Light intensities in `IR` multiplied by `RGBC` with depth cueing
+    pub const CDP: Self = Self::new(0x14).sf(true).lm(true);
+
+    const fn new(opcode: u32) -> Self {
+        Command(COMMAND | opcode)
+    }
+
+    /// Multiplies a matrix by a vector and adds a translation.
+    ///
+    /// The fraction is shifted out of the result by default.
+    pub const fn mvmva(matrix: Matrix, vector: Vector, translation: Translation) -> Self {
+        let cmd = Self::new(0x12).sf(true);
+        Command(cmd.0 | (matrix as u32) << 17 | (vector as u32) << 15 | (translation as u32) << 13)
+    }
+
+    /// Sets whether the 12-bit fraction is shifted out of results (the `sf`
+    /// bit).
+    pub const fn sf(self, shift: bool) -> Self {
+        Command(self.0 & !(1 << 19) | (shift as u32) << 19)
+    }
+
+    /// Sets whether results in `IR1`, `IR2` and `IR3` are saturated to zero
+    /// instead of `-0x8000` (the `lm` bit).
+    pub const fn lm(self, saturate: bool) -> Self {
+        Command(self.0 & !(1 << 10) | (saturate as u32) << 10)
+    }
+
+    /// Gets the command's instruction word.
+    pub const fn to_bits(self) -> u32 {
+        self.0
+    }
+}
+
+/// A vertex projected onto the screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScreenVertex {
+    /// The vertex's screen coordinates.
+    pub xy: Vertex,
+    /// The vertex's depth.
+    pub z: u16,
+}
+
+/// Packs two 16-bit values into a GTE register.
+fn pack(lo: i16, hi: i16) -> u32 {
+    lo as u16 as u32 | (hi as u16 as u32) << 16
+}
+
+fn unpack_xy(xy: u32) -> Vertex {
+    Vertex(xy as i16, (xy >> 16) as i16)
+}
+
+/// The registers and command execution used by [`GTE`].
+pub trait Backend {
+    /// Reads the register `R`.
+    fn load<R: GTERegister>(&self) -> R::Value;
+
+    /// Writes `value` to the register `R`.
+    fn store<R: GTERegister>(&mut self, value: R::Value);
+
+    /// Runs the GTE command `CMD`.
+    fn run<const CMD: u32>(&mut self);
+}
+
+/// The GTE coprocessor.
+///
+/// This is only a [`Backend`] when building for MIPS.
+#[derive(Debug)]
+pub struct Hardware {
+    _private: (),
+}
+
+#[cfg(target_arch = "mips")]
+impl Backend for Hardware {
+    fn load<R: GTERegister>(&self) -> R::Value {
+        R::read()
+    }
+
+    fn store<R: GTERegister>(&mut self, value: R::Value) {
+        R::write(value)
+    }
+
+    fn run<const CMD: u32>(&mut self) {
+        ValidCommand::<CMD>::IS_GTE_COMMAND;
+        // SAFETY: `CMD` is a GTE command and those only modify GTE registers.
+        unsafe {
+            asm! {
+                "nop",
+                "nop",
+                ".word {cmd}",
+                cmd = const CMD,
+            }
+        }
+    }
+}
+
+/// A handle to the GTE.
+///
+/// Commands read their inputs from and write their results to GTE registers.
+/// Methods which issue a command load its inputs first and read its results
+/// afterwards, though the results of one command may be used by the next
+/// without going through the CPU, e.g. [`GTE::rtpt`] followed by
+/// [`GTE::nclip`] and [`GTE::avsz3`].
+#[derive(Debug)]
+pub struct GTE<B = Hardware> {
+    backend: B,
+}
+
+#[cfg(target_arch = "mips")]
+impl GTE {
+    /// Enables the GTE and creates a handle to it.
+    pub fn new() -> Self {
+        cop0::Status::new().enable_gte().store();
+        GTE {
+            backend: Hardware { _private: () },
+        }
+    }
+}
+
+impl GTE<Software> {
+    /// Creates a handle to a software GTE with all registers zeroed.
+    pub fn software() -> Self {
+        GTE {
+            backend: Software::new(),
+        }
+    }
+
+    /// Gets the software GTE's registers.
+    pub fn registers(&self) -> &Software {
+        &self.backend
+    }
+}
+
+impl<B: Backend> GTE<B> {
+    fn load<R: GTERegister>(&self) -> R::Value {
+        self.backend.load::<R>()
+    }
+
+    fn store<R: GTERegister>(&mut self, value: R::Value) {
+        self.backend.store::<R>(value)
+    }
+
+    /// Runs the GTE command `CMD`.
+    ///
+    /// `CMD` should be created with [`Command::to_bits`]. Values which aren't
+    /// GTE commands fail to compile. The two instructions before the command
+    /// let writes to GTE registers complete and the CPU stalls on reads of GTE
+    /// registers until the command completes.
+    pub fn run<const CMD: u32>(&mut self) -> &mut Self {
+        ValidCommand::<CMD>::IS_GTE_COMMAND;
+        self.backend.run::<CMD>();
+        self
+    }
+
+    /// Sets the rotation matrix.
+    pub fn set_rotation(&mut self, m: [[i16; 3]; 3]) -> &mut Self {
+        self.store::<RT11_12>(pack(m[0][0], m[0][1]));
+        self.store::<RT13_21>(pack(m[0][2], m[1][0]));
+        self.store::<RT22_23>(pack(m[1][1], m[1][2]));
+        self.store::<RT31_32>(pack(m[2][0], m[2][1]));
+        self.store::<RT33>(m[2][2]);
+        self
+    }
+
+    /// Sets the translation vector.
+    pub fn set_translation(&mut self, [x, y, z]: [i32; 3]) -> &mut Self {
+        self.store::<TRX>(x);
+        self.store::<TRY>(y);
+        self.store::<TRZ>(z);
+        self
+    }
+
+    /// Sets the rotation matrix and translation vector.
+    pub fn set_transform(&mut self, transform: &Transform) -> &mut Self {
+        self.set_rotation(transform.rotation.into())
+            .set_translation(transform.translation)
+    }
+
+    /// Sets the screen offset added to projected vertices.
+    pub fn set_screen_offset(&mut self, offset: Vertex) -> &mut Self {
+        self.store::<OFX>((offset.0 as i32) << 16);
+        self.store::<OFY>((offset.1 as i32) << 16);
+        self
+    }
+
+    /// Sets the distance from the camera to the projection plane.
+    pub fn set_projection_distance(&mut self, h: u16) -> &mut Self {
+        self.store::<H>(h);
+        self
+    }
+
+    /// Sets the depth cueing coefficient and offset used by perspective
+    /// transformations.
+    pub fn set_depth_cueing(&mut self, dqa: i16, dqb: i32) -> &mut Self {
+        self.store::<DQA>(dqa);
+        self.store::<DQB>(dqb);
+        self
+    }
+
+    /// Sets the scale factors used by [`GTE::avsz3`] and [`GTE::avsz4`].
+    ///
+    /// These are usually the number of ordering table entries divided by
+    /// three or four times the largest screen Z coordinate, in 1.3.12
+    /// fixed-point.
+    pub fn set_z_scales(&mut self, zsf3: i16, zsf4: i16) -> &mut Self {
+        self.store::<ZSF3>(zsf3);
+        self.store::<ZSF4>(zsf4);
+        self
+    }
+
+    /// Sets the input vectors `V0`, `V1` and `V2`.
+    pub fn set_vectors(&mut self, [v0, v1, v2]: [[i16; 3]; 3]) -> &mut Self {
+        self.set_v0(v0);
+        self.store::<VXY1>(pack(v1[0], v1[1]));
+        self.store::<VZ1>(v1[2]);
+        self.store::<VXY2>(pack(v2[0], v2[1]));
+        self.store::<VZ2>(v2[2]);
+        self
+    }
+
+    /// Sets the input vector `V0`.
+    pub fn set_v0(&mut self, [x, y, z]: [i16; 3]) -> &mut Self {
+        self.store::<VXY0>(pack(x, y));
+        self.store::<VZ0>(z);
+        self
+    }
+
+    /// Sets `IR0`.
+    pub fn set_ir0(&mut self, ir0: i16) -> &mut Self {
+        self.store::<IR0>(ir0);
+        self
+    }
+
+    /// Sets `IR1`, `IR2` and `IR3`.
+    pub fn set_ir(&mut self, [x, y, z]: [i16; 3]) -> &mut Self {
+        self.store::<IR1>(x);
+        self.store::<IR2>(y);
+        self.store::<IR3>(z);
+        self
+    }
+
+    /// Gets `IR1`, `IR2` and `IR3`.
+    pub fn ir(&self) -> [i16; 3] {
+        [self.load::<IR1>(), self.load::<IR2>(), self.load::<IR3>()]
+    }
+
+    /// Sets `MAC1`, `MAC2` and `MAC3`.
+    pub fn set_mac(&mut self, [x, y, z]: [i32; 3]) -> &mut Self {
+        self.store::<MAC1>(x);
+        self.store::<MAC2>(y);
+        self.store::<MAC3>(z);
+        self
+    }
+
+    /// Gets `MAC1`, `MAC2` and `MAC3`.
+    pub fn mac(&self) -> [i32; 3] {
+        [
+            self.load::<MAC1>(),
+            self.load::<MAC2>(),
+            self.load::<MAC3>(),
+        ]
+    }
+
+    /// Gets the depth cueing interpolation factor of the last perspective
+    /// transformation.
+    ///
+    /// This is `IR0` where `0x1000` is the far color.
+    pub fn depth_cue(&self) -> i16 {
+        self.load::<IR0>()
+    }
+
+    /// Gets the calculation error flags of the last command.
+    pub fn flag(&self) -> u32 {
+        self.load::<FLAG>()
+    }
+
+    /// Projects `v` onto the screen with RTPS.
+    pub fn rtps(&mut self, v: [i16; 3]) -> ScreenVertex {
+        self.set_v0(v).run::<{ Command::RTPS.to_bits() }>();
+        ScreenVertex {
+            xy: unpack_xy(self.load::<SXY2>()),
+            z: self.load::<SZ3>(),
+        }
+    }
+
+    /// Projects the vertices of a triangle onto the screen with RTPT.
+    pub fn rtpt(&mut self, vs: [[i16; 3]; 3]) -> [ScreenVertex; 3] {
+        self.set_vectors(vs).run::<{ Command::RTPT.to_bits() }>();
+        [
+            ScreenVertex {
+                xy: unpack_xy(self.load::<SXY0>()),
+                z: self.load::<SZ1>(),
+            },
+            ScreenVertex {
+                xy: unpack_xy(self.load::<SXY1>()),
+                z: self.load::<SZ2>(),
+            },
+            ScreenVertex {
+                xy: unpack_xy(self.load::<SXY2>()),
+                z: self.load::<SZ3>(),
+            },
+        ]
+    }
+
+    /// Computes twice the signed area of the last three projected vertices
+    /// with NCLIP.
+    ///
+    /// This is positive if the vertices are clockwise on screen and negative
+    /// if they're counterclockwise, so faces with a negative result may be
+    /// culled.
+    pub fn nclip(&mut self) -> i32 {
+        self.run::<{ Command::NCLIP.to_bits() }>();
+        self.load::<MAC0>()
+    }
+
+    /// Gets the scaled average depth of the last three projected vertices with
+    /// AVSZ3.
+    pub fn avsz3(&mut self) -> u16 {
+        self.run::<{ Command::AVSZ3.to_bits() }>();
+        self.load::<OTZ>()
+    }
+
+    /// Gets the scaled average depth of the last four projected vertices with
+    /// AVSZ4.
+    pub fn avsz4(&mut self) -> u16 {
+        self.run::<{ Command::AVSZ4.to_bits() }>();
+        self.load::<OTZ>()
+    }
+
+    /// Rotates and translates `v` without projecting it using MVMVA.
+    pub fn transform(&mut self, v: [i16; 3]) -> [i32; 3] {
+        const CMD: Command = Command::mvmva(Matrix::Rotation, Vector::V0, Translation::TR);
+        self.set_v0(v).run::<{ CMD.to_bits() }>().mac()
+    }
+
+    /// Squares each component of a 1.3.12 vector with SQR.
+    pub fn sqr(&mut self, v: [i16; 3]) -> [i32; 3] {
+        self.set_ir(v).run::<{ Command::SQR.to_bits() }>().mac()
+    }
+
+    /// Computes the cross product of the rotation matrix's diagonal and `v`
+    /// with OP.
+    pub fn op(&mut self, v: [i16; 3]) -> [i32; 3] {
+        self.set_ir(v).run::<{ Command::OP.to_bits() }>().mac()
+    }
+
+    /// Scales `v` by the 1.3.12 factor `t` with GPF.
+    pub fn gpf(&mut self, t: i16, v: [i16; 3]) -> [i32; 3] {
+        self.set_ir0(t)
+            .set_ir(v)
+            .run::<{ Command::GPF.to_bits() }>()
+            .mac()
+    }
+
+    /// Scales `v` by the 1.3.12 factor `t` and adds `base` with GPL.
+    ///
+    /// `base` has the same fractional bits as the result.
+    pub fn gpl(&mut self, t: i16, v: [i16; 3], base: [i32; 3]) -> [i32; 3] {
+        self.set_ir0(t)
+            .set_ir(v)
+            .set_mac(base)
+            .run::<{ Command::GPL.to_bits() }>()
+            .mac()
+    }
+}
+
+#[cfg(target_arch = "mips")]
+impl Default for GTE {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Command, Matrix, Translation, Vector, GTE};
+    use crate::gpu::Vertex;
+
+    const IDENTITY: [[i16; 3]; 3] = [[0x1000, 0, 0], [0, 0x1000, 0], [0, 0, 0x1000]];
+
+    // Tests use the GTE when building for MIPS and the software model
+    // elsewhere
+    #[cfg(target_arch = "mips")]
+    pub(super) fn new_gte() -> GTE {
+        GTE::new()
+    }
+
+    #[cfg(not(target_arch = "mips"))]
+    pub(super) fn new_gte() -> GTE<super::Software> {
+        GTE::software()
+    }
+
+    fn gte() -> GTE<impl Backend> {
+        let mut gte = new_gte();
+        gte.set_rotation(IDENTITY)
+            .set_translation([0, 0, 0])
+            .set_screen_offset(Vertex(160, 120))
+            .set_projection_distance(200)
+            .set_z_scales(0x1000 / 3, 0x1000 / 4);
+        gte
+    }
+
+    #[test_case]
+    fn encoding() {
+        assert!(Command::RTPS.to_bits() == 0x4A08_0001);
+        assert!(Command::NCLIP.to_bits() == 0x4A00_0006);
+        assert!(Command::SQR.sf(false).to_bits() == 0x4A00_0028);
+        let mvmva = Command::mvmva(Matrix::Light, Vector::IR, Translation::None);
+        assert!(mvmva.to_bits() == 0x4A0B_E012);
+        assert!(mvmva.lm(true).sf(false).to_bits() == 0x4A03_E412);
+    }
+
+    #[test_case]
+    fn project() {
+        let mut gte = gte();
+        // H / SZ is 0.5 so the fractional parts keep off-by-one errors in the
+        // division from changing the result
+        let v = gte.rtps([101, 51, 400]);
+        assert!(v.xy == Vertex(210, 145) && v.z == 400);
+        let tri = gte.rtpt([[101, 51, 400], [301, 51, 400], [101, 251, 400]]);
+        assert!(tri[1].xy == Vertex(310, 145) && tri[2].xy == Vertex(210, 245));
+        assert!(gte.nclip() == 100 * 100);
+        assert!(gte.avsz3() == 399);
+    }
+
+    #[test_case]
+    fn arithmetic() {
+        let mut gte = gte();
+        assert!(gte.transform([1, -2, 3]) == [1, -2, 3]);
+        assert!(gte.sqr([0x800, -0x800, 0x1000]) == [0x400, 0x400, 0x1000]);
+        assert!(gte.gpf(0x800, [0x100, -0x200, 0]) == [0x80, -0x100, 0]);
+        assert!(gte.gpl(0x800, [0x100, 0, 0], [1, 2, 3]) == [0x81, 2, 3]);
+    }
+}
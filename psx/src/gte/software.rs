@@ -0,0 +1,686 @@
+use super::Backend;
+use crate::hw::gte::GTERegister;
+
+// Registers which read back as sign-extended or zero-extended 16-bit values
+const SIGNED_DATA: [usize; 7] = [1, 3, 5, 8, 9, 10, 11];
+const UNSIGNED_DATA: [usize; 5] = [7, 16, 17, 18, 19];
+const SIGNED_CONTROL: [usize; 7] = [4, 12, 20, 26, 27, 29, 30];
+
+// Data register numbers
+const RGBC: usize = 6;
+const OTZ: usize = 7;
+const IR0: usize = 8;
+const SXY0: usize = 12;
+const SXY2: usize = 14;
+const SXYP: usize = 15;
+const SZ0: usize = 16;
+const RGB0: usize = 20;
+const MAC0: usize = 24;
+const IRGB: usize = 28;
+const ORGB: usize = 29;
+const LZCS: usize = 30;
+const LZCR: usize = 31;
+
+// Control register numbers
+const RT: usize = 0;
+const TR: usize = 5;
+const L: usize = 8;
+const BK: usize = 13;
+const LR: usize = 16;
+const FC: usize = 21;
+const OFX: usize = 24;
+const OFY: usize = 25;
+const H: usize = 26;
+const DQA: usize = 27;
+const DQB: usize = 28;
+const ZSF3: usize = 29;
+const ZSF4: usize = 30;
+const FLAG: usize = 31;
+
+// FLAG bits
+const MAC_POSITIVE: [u32; 3] = [1 << 30, 1 << 29, 1 << 28];
+const MAC_NEGATIVE: [u32; 3] = [1 << 27, 1 << 26, 1 << 25];
+const IR_SATURATED: [u32; 3] = [1 << 24, 1 << 23, 1 << 22];
+const COLOR_SATURATED: [u32; 3] = [1 << 21, 1 << 20, 1 << 19];
+const Z_SATURATED: u32 = 1 << 18;
+const DIVIDE_OVERFLOW: u32 = 1 << 17;
+const MAC0_POSITIVE: u32 = 1 << 16;
+const MAC0_NEGATIVE: u32 = 1 << 15;
+const SX_SATURATED: u32 = 1 << 14;
+const SY_SATURATED: u32 = 1 << 13;
+const IR0_SATURATED: u32 = 1 << 12;
+const ERROR_BITS: u32 = 0x7F87_E000;
+const ERROR: u32 = 1 << 31;
+
+// The table of reciprocals used as the initial guess by the GTE's divider
+const UNR_TABLE: [u8; 0x101] = {
+    let mut table = [0; 0x101];
+    let mut i = 0;
+    while i < table.len() {
+        let x = (0x40000 / (i as i32 + 0x100) + 1) / 2 - 0x101;
+        table[i] = if x < 0 { 0 } else { x as u8 };
+        i += 1;
+    }
+    table
+};
+
+/// Divides `h` by `sz` with the GTE's Newton-Raphson divider.
+///
+/// This returns a 1.16 fixed-point result saturated to `0x1FFFF`. `h` must be
+/// less than twice `sz`.
+fn unr_divide(h: u16, sz: u16) -> u32 {
+    let shift = sz.leading_zeros();
+    let n = (h as u64) << shift;
+    let d = (sz as i32) << shift;
+    let u = UNR_TABLE[((d as usize & 0x7FFF) + 0x40) >> 7] as i32 + 0x101;
+    let d = (0x80 - d * u) >> 8;
+    let recip = ((u * (0x20000 + d)) + 0x80) >> 8;
+    let res = (n * recip as u64 + 0x8000) >> 16;
+    res.min(0x1FFFF) as u32
+}
+
+fn lo(x: u32) -> i16 {
+    x as i16
+}
+
+fn hi(x: u32) -> i16 {
+    (x >> 16) as i16
+}
+
+/// A software model of the GTE.
+///
+/// This runs commands with the same results as the GTE, including the
+/// calculation error flags, so it may be used wherever cop2 isn't available
+/// or to check the GTE's results. It's usually used through a
+/// [`GTE`][super::GTE] created with [`GTE::software`][super::GTE::software].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Software {
+    data: [u32; 32],
+    control: [u32; 32],
+}
+
+impl Software {
+    /// Creates a software GTE with all registers zeroed.
+    pub const fn new() -> Self {
+        Software {
+            data: [0; 32],
+            control: [0; 32],
+        }
+    }
+
+    /// Reads the register `reg` as `mfc2` or `cfc2` would. Control registers
+    /// are numbered 32 to 63.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not less than 64.
+    pub fn read(&self, reg: u32) -> u32 {
+        let idx = reg as usize;
+        match idx {
+            SXYP => self.data[SXY2],
+            IRGB | ORGB => {
+                let [r, g, b] = self.ir().map(|c| (c >> 7).clamp(0, 0x1F) as u32);
+                r | g << 5 | b << 10
+            },
+            0..=31 => self.data[idx],
+            _ => self.control[idx - 32],
+        }
+    }
+
+    /// Writes `value` to the register `reg` as `mtc2` or `ctc2` would. Control
+    /// registers are numbered 32 to 63.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not less than 64.
+    pub fn write(&mut self, reg: u32, value: u32) {
+        let idx = reg as usize;
+        match idx {
+            _ if SIGNED_DATA.contains(&idx) => self.data[idx] = value as i16 as u32,
+            _ if UNSIGNED_DATA.contains(&idx) => self.data[idx] = value as u16 as u32,
+            SXYP => self.push_xy(value),
+            IRGB => {
+                for (i, ir) in self.data[IR0 + 1..=IR0 + 3].iter_mut().enumerate() {
+                    *ir = ((value >> (5 * i)) & 0x1F) << 7;
+                }
+            },
+            ORGB | LZCR => (),
+            LZCS => {
+                self.data[LZCS] = value;
+                self.data[LZCR] = if (value as i32) < 0 {
+                    value.leading_ones()
+                } else {
+                    value.leading_zeros()
+                };
+            },
+            0..=31 => self.data[idx] = value,
+            _ => {
+                let idx = idx - 32;
+                self.control[idx] = if SIGNED_CONTROL.contains(&idx) {
+                    value as i16 as u32
+                } else if idx == FLAG {
+                    Self::with_error(value & 0x7FFF_F000)
+                } else {
+                    value
+                };
+            },
+        }
+    }
+
+    /// Runs the command `cmd`, as created by
+    /// [`Command::to_bits`][super::Command::to_bits].
+    pub fn execute(&mut self, cmd: u32) {
+        let shift = if cmd & (1 << 19) != 0 { 12 } else { 0 };
+        let lm = cmd & (1 << 10) != 0;
+        self.control[FLAG] = 0;
+        match cmd & 0x3F {
+            0x01 => self.rtp(0, shift, lm, true),
+            0x06 => self.nclip(),
+            0x0C => self.op(shift, lm),
+            0x10 => self.dpcs(self.color(), shift, lm),
+            0x11 => self.intpl(shift, lm),
+            0x12 => self.mvmva(cmd, shift, lm),
+            0x13 => self.ncds(0, shift, lm),
+            0x14 => self.cdp(shift, lm),
+            0x16 => (0..3).for_each(|v| self.ncds(v, shift, lm)),
+            0x1B => self.nccs(0, shift, lm),
+            0x1C => self.cc(shift, lm),
+            0x1E => self.ncs(0, shift, lm),
+            0x20 => (0..3).for_each(|v| self.ncs(v, shift, lm)),
+            0x28 => self.sqr(shift, lm),
+            0x2A => (0..3).for_each(|_| self.dpcs(self.rgb0(), shift, lm)),
+            0x2D => self.avsz(self.control[ZSF3], 1),
+            0x2E => self.avsz(self.control[ZSF4], 0),
+            0x30 => (0..3).for_each(|v| self.rtp(v, shift, lm, v == 2)),
+            0x3D => self.gpf(shift, lm),
+            0x3E => self.gpl(shift, lm),
+            0x3F => (0..3).for_each(|v| self.nccs(v, shift, lm)),
+            _ => (),
+        }
+        self.control[FLAG] = Self::with_error(self.control[FLAG]);
+    }
+
+    fn with_error(flag: u32) -> u32 {
+        if flag & ERROR_BITS != 0 {
+            flag | ERROR
+        } else {
+            flag
+        }
+    }
+
+    fn vector(&self, v: usize) -> [i16; 3] {
+        let xy = self.data[2 * v];
+        [lo(xy), hi(xy), self.data[2 * v + 1] as i16]
+    }
+
+    fn ir(&self) -> [i16; 3] {
+        [1, 2, 3].map(|i| self.data[IR0 + i] as i16)
+    }
+
+    fn ir0(&self) -> i16 {
+        self.data[IR0] as i16
+    }
+
+    fn color(&self) -> [u8; 3] {
+        let rgbc = self.data[RGBC];
+        [rgbc as u8, (rgbc >> 8) as u8, (rgbc >> 16) as u8]
+    }
+
+    fn rgb0(&self) -> [u8; 3] {
+        let rgb = self.data[RGB0];
+        [rgb as u8, (rgb >> 8) as u8, (rgb >> 16) as u8]
+    }
+
+    fn matrix(&self, start: usize) -> [[i16; 3]; 3] {
+        let m = &self.control[start..start + 5];
+        [
+            [lo(m[0]), hi(m[0]), lo(m[1])],
+            [hi(m[1]), lo(m[2]), hi(m[2])],
+            [lo(m[3]), hi(m[3]), lo(m[4])],
+        ]
+    }
+
+    fn translation(&self, start: usize) -> [i32; 3] {
+        [0, 1, 2].map(|i| self.control[start + i] as i32)
+    }
+
+    // Sets the overflow flags of the 44-bit accumulator `i` and truncates
+    // `value` to 44 bits.
+    fn check_mac(&mut self, i: usize, value: i64) -> i64 {
+        if value >= 1 << 43 {
+            self.control[FLAG] |= MAC_POSITIVE[i];
+        } else if value < -(1 << 43) {
+            self.control[FLAG] |= MAC_NEGATIVE[i];
+        }
+        (value << 20) >> 20
+    }
+
+    fn set_mac(&mut self, i: usize, value: i64, shift: u32) {
+        self.check_mac(i, value);
+        self.data[MAC0 + 1 + i] = (value >> shift) as u32;
+    }
+
+    fn set_ir(&mut self, i: usize, value: i32, lm: bool) {
+        let min = if lm { 0 } else { i16::MIN as i32 };
+        if value < min || value > i16::MAX as i32 {
+            self.control[FLAG] |= IR_SATURATED[i];
+        }
+        self.data[IR0 + 1 + i] = value.clamp(min, i16::MAX as i32) as i16 as u32;
+    }
+
+    fn set_mac_ir(&mut self, i: usize, value: i64, shift: u32, lm: bool) {
+        self.set_mac(i, value, shift);
+        self.set_ir(i, self.data[MAC0 + 1 + i] as i32, lm);
+    }
+
+    fn check_mac0(&mut self, value: i64) {
+        if value > i32::MAX as i64 {
+            self.control[FLAG] |= MAC0_POSITIVE;
+        } else if value < i32::MIN as i64 {
+            self.control[FLAG] |= MAC0_NEGATIVE;
+        }
+    }
+
+    fn set_mac0(&mut self, value: i64) {
+        self.check_mac0(value);
+        self.data[MAC0] = value as u32;
+    }
+
+    fn set_ir0(&mut self, value: i32) {
+        if !(0..=0x1000).contains(&value) {
+            self.control[FLAG] |= IR0_SATURATED;
+        }
+        self.data[IR0] = value.clamp(0, 0x1000) as u32;
+    }
+
+    fn push_xy(&mut self, xy: u32) {
+        self.data.copy_within(SXY0 + 1..=SXY2, SXY0);
+        self.data[SXY2] = xy;
+    }
+
+    fn push_z(&mut self, z: i32) {
+        if !(0..=0xFFFF).contains(&z) {
+            self.control[FLAG] |= Z_SATURATED;
+        }
+        self.data.copy_within(SZ0 + 1..SZ0 + 4, SZ0);
+        self.data[SZ0 + 3] = z.clamp(0, 0xFFFF) as u32;
+    }
+
+    fn push_color(&mut self) {
+        let mut rgbc = self.data[RGBC] & 0xFF00_0000;
+        for (i, saturated) in COLOR_SATURATED.into_iter().enumerate() {
+            let c = (self.data[MAC0 + 1 + i] as i32) >> 4;
+            if !(0..=0xFF).contains(&c) {
+                self.control[FLAG] |= saturated;
+            }
+            rgbc |= (c.clamp(0, 0xFF) as u32) << (8 * i);
+        }
+        self.data.copy_within(RGB0 + 1..RGB0 + 3, RGB0);
+        self.data[RGB0 + 2] = rgbc;
+    }
+
+    // Multiplies `m` by `v` and adds `t` with 12 fractional bits. The
+    // accumulator is truncated after each addition.
+    fn dot3(&mut self, i: usize, m: [i16; 3], v: [i16; 3], t: i32) -> i64 {
+        let mut acc = (t as i64) << 12;
+        for (m, v) in m.into_iter().zip(v) {
+            acc = self.check_mac(i, acc + m as i64 * v as i64);
+        }
+        acc
+    }
+
+    fn mul_mat_vec(&mut self, m: [[i16; 3]; 3], v: [i16; 3], t: [i32; 3], shift: u32, lm: bool) {
+        for i in 0..3 {
+            let value = self.dot3(i, m[i], v, t[i]);
+            self.set_mac_ir(i, value, shift, lm);
+        }
+    }
+
+    fn rtp(&mut self, v: usize, shift: u32, lm: bool, depth_cue: bool) {
+        let (m, t) = (self.matrix(RT), self.translation(TR));
+        let v = self.vector(v);
+        let mut mac = [0; 3];
+        for i in 0..3 {
+            mac[i] = self.dot3(i, m[i], v, t[i]);
+            self.set_mac(i, mac[i], shift);
+        }
+        for i in 0..2 {
+            self.set_ir(i, self.data[MAC0 + 1 + i] as i32, lm);
+        }
+        // IR3 is saturated normally but its flag is only set if the depth
+        // without its fraction is out of range
+        let z = (mac[2] >> 12) as i32;
+        if z < i16::MIN as i32 || z > i16::MAX as i32 {
+            self.control[FLAG] |= IR_SATURATED[2];
+        }
+        let min = if lm { 0 } else { i16::MIN as i32 };
+        let mac3 = self.data[MAC0 + 3] as i32;
+        self.data[IR0 + 3] = mac3.clamp(min, i16::MAX as i32) as i16 as u32;
+        self.push_z(z);
+
+        let h = self.control[H] as u16;
+        let sz = self.data[SZ0 + 3] as u16;
+        let n = if (h as u32) < 2 * sz as u32 {
+            unr_divide(h, sz)
+        } else {
+            self.control[FLAG] |= DIVIDE_OVERFLOW;
+            0x1FFFF
+        } as i64;
+        let [ir1, ir2, _] = self.ir();
+        let sx = n * ir1 as i64 + self.control[OFX] as i32 as i64;
+        let sy = n * ir2 as i64 + self.control[OFY] as i32 as i64;
+        // The screen coordinates set the MAC0 flags without being written to it
+        self.check_mac0(sx);
+        self.check_mac0(sy);
+        let (sx, sy) = ((sx >> 16) as i32, (sy >> 16) as i32);
+        if !(-0x400..=0x3FF).contains(&sx) {
+            self.control[FLAG] |= SX_SATURATED;
+        }
+        if !(-0x400..=0x3FF).contains(&sy) {
+            self.control[FLAG] |= SY_SATURATED;
+        }
+        let (sx, sy) = (sx.clamp(-0x400, 0x3FF), sy.clamp(-0x400, 0x3FF));
+        self.push_xy(sx as u16 as u32 | (sy as u32) << 16);
+        if depth_cue {
+            let dq = n * self.control[DQA] as i16 as i64 + self.control[DQB] as i32 as i64;
+            self.set_mac0(dq);
+            self.set_ir0((dq >> 12) as i32);
+        }
+    }
+
+    fn nclip(&mut self) {
+        let [(x0, y0), (x1, y1), (x2, y2)] = [0, 1, 2].map(|i| {
+            let xy = self.data[SXY0 + i];
+            (lo(xy) as i64, hi(xy) as i64)
+        });
+        self.set_mac0(x0 * y1 + x1 * y2 + x2 * y0 - x0 * y2 - x1 * y0 - x2 * y1);
+    }
+
+    fn avsz(&mut self, scale: u32, first: usize) {
+        let sum: i64 = self.data[SZ0 + first..SZ0 + 4]
+            .iter()
+            .map(|&z| z as i64)
+            .sum();
+        let res = scale as i16 as i64 * sum;
+        self.set_mac0(res);
+        let otz = (res >> 12) as i32;
+        if !(0..=0xFFFF).contains(&otz) {
+            self.control[FLAG] |= Z_SATURATED;
+        }
+        self.data[OTZ] = otz.clamp(0, 0xFFFF) as u32;
+    }
+
+    fn mvmva(&mut self, cmd: u32, shift: u32, lm: bool) {
+        let mut m = match (cmd >> 17) & 3 {
+            0 => self.matrix(RT),
+            1 => self.matrix(L),
+            2 => self.matrix(LR),
+            // The reserved matrix is made of garbage values
+            _ => {
+                let r = (self.color()[0] as i16) << 4;
+                let [rt13, rt22] = [1, 2].map(|i| lo(self.control[RT + i]));
+                [[-r, r, self.ir0()], [rt13; 3], [rt22; 3]]
+            },
+        };
+        let v = match (cmd >> 15) & 3 {
+            3 => self.ir(),
+            v => self.vector(v as usize),
+        };
+        let t = match (cmd >> 13) & 3 {
+            0 => self.translation(TR),
+            1 => self.translation(BK),
+            2 => {
+                // Translating by the far color sets the flags for the far color
+                // and the matrix's first column but leaves them out of the result
+                let fc = self.translation(FC);
+                for (i, row) in m.iter_mut().enumerate() {
+                    let value = ((fc[i] as i64) << 12) + row[0] as i64 * v[0] as i64;
+                    let value = self.check_mac(i, value);
+                    self.set_ir(i, (value >> shift) as i32, false);
+                    row[0] = 0;
+                }
+                [0; 3]
+            },
+            _ => [0; 3],
+        };
+        self.mul_mat_vec(m, v, t, shift, lm);
+    }
+
+    fn sqr(&mut self, shift: u32, lm: bool) {
+        for (i, ir) in self.ir().into_iter().enumerate() {
+            self.set_mac_ir(i, ir as i64 * ir as i64, shift, lm);
+        }
+    }
+
+    fn op(&mut self, shift: u32, lm: bool) {
+        let m = self.matrix(RT);
+        let [d1, d2, d3] = [m[0][0], m[1][1], m[2][2]].map(|d| d as i64);
+        let [ir1, ir2, ir3] = self.ir().map(|ir| ir as i64);
+        self.set_mac_ir(0, ir3 * d2 - ir2 * d3, shift, lm);
+        self.set_mac_ir(1, ir1 * d3 - ir3 * d1, shift, lm);
+        self.set_mac_ir(2, ir2 * d1 - ir1 * d2, shift, lm);
+    }
+
+    fn gpf(&mut self, shift: u32, lm: bool) {
+        let ir0 = self.ir0() as i64;
+        for (i, ir) in self.ir().into_iter().enumerate() {
+            self.set_mac_ir(i, ir0 * ir as i64, shift, lm);
+        }
+        self.push_color();
+    }
+
+    fn gpl(&mut self, shift: u32, lm: bool) {
+        let ir0 = self.ir0() as i64;
+        for (i, ir) in self.ir().into_iter().enumerate() {
+            let mac = self.data[MAC0 + 1 + i] as i32 as i64;
+            let base = self.check_mac(i, mac << shift);
+            self.set_mac_ir(i, base + ir0 * ir as i64, shift, lm);
+        }
+        self.push_color();
+    }
+
+    // Lights the normal `v` with the light and light color matrices and adds
+    // the background color
+    fn light(&mut self, v: usize, shift: u32, lm: bool) {
+        let v = self.vector(v);
+        self.mul_mat_vec(self.matrix(L), v, [0; 3], shift, lm);
+        self.mul_mat_vec(self.matrix(LR), self.ir(), self.translation(BK), shift, lm);
+    }
+
+    // Multiplies the light intensities in IR by the color in RGBC
+    fn color_mac(&self) -> [i64; 3] {
+        let (color, ir) = (self.color(), self.ir());
+        [0, 1, 2].map(|i| ((color[i] as i64) * ir[i] as i64) << 4)
+    }
+
+    // Interpolates between `mac` and the far color by IR0
+    fn interpolate(&mut self, mac: [i64; 3], shift: u32, lm: bool) {
+        let fc = self.translation(FC);
+        for i in 0..3 {
+            self.set_mac_ir(i, ((fc[i] as i64) << 12) - mac[i], shift, false);
+        }
+        let (ir0, ir) = (self.ir0() as i64, self.ir());
+        for i in 0..3 {
+            self.set_mac_ir(i, ir[i] as i64 * ir0 + mac[i], shift, lm);
+        }
+    }
+
+    fn ncs(&mut self, v: usize, shift: u32, lm: bool) {
+        self.light(v, shift, lm);
+        self.push_color();
+    }
+
+    fn nccs(&mut self, v: usize, shift: u32, lm: bool) {
+        self.light(v, shift, lm);
+        self.cc_mac(shift, lm);
+    }
+
+    fn ncds(&mut self, v: usize, shift: u32, lm: bool) {
+        self.light(v, shift, lm);
+        self.interpolate(self.color_mac(), shift, lm);
+        self.push_color();
+    }
+
+    fn cc_mac(&mut self, shift: u32, lm: bool) {
+        for (i, mac) in self.color_mac().into_iter().enumerate() {
+            self.set_mac_ir(i, mac, shift, lm);
+        }
+        self.push_color();
+    }
+
+    fn cc(&mut self, shift: u32, lm: bool) {
+        self.mul_mat_vec(self.matrix(LR), self.ir(), self.translation(BK), shift, lm);
+        self.cc_mac(shift, lm);
+    }
+
+    fn cdp(&mut self, shift: u32, lm: bool) {
+        self.mul_mat_vec(self.matrix(LR), self.ir(), self.translation(BK), shift, lm);
+        self.interpolate(self.color_mac(), shift, lm);
+        self.push_color();
+    }
+
+    fn dpcs(&mut self, color: [u8; 3], shift: u32, lm: bool) {
+        self.interpolate(color.map(|c| (c as i64) << 16), shift, lm);
+        self.push_color();
+    }
+
+    fn intpl(&mut self, shift: u32, lm: bool) {
+        self.interpolate(self.ir().map(|ir| (ir as i64) << 12), shift, lm);
+        self.push_color();
+    }
+}
+
+impl Default for Software {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for Software {
+    fn load<R: GTERegister>(&self) -> R::Value {
+        R::from_word(self.read(R::INDEX))
+    }
+
+    fn store<R: GTERegister>(&mut self, value: R::Value) {
+        self.write(R::INDEX, R::to_word(value))
+    }
+
+    fn run<const CMD: u32>(&mut self) {
+        self.execute(CMD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Software, DIVIDE_OVERFLOW, ERROR, SX_SATURATED, UNR_TABLE, Z_SATURATED};
+    #[cfg(target_arch = "mips")]
+    use crate::gpu::Color;
+    use crate::gpu::Vertex;
+    use crate::gte::GTE;
+    #[cfg(target_arch = "mips")]
+    use crate::gte::{Backend, Light, Lighting};
+
+    #[cfg(target_arch = "mips")]
+    const VERTICES: [[i16; 3]; 5] = [
+        [101, 51, 400],
+        [-300, 20, 150],
+        [1000, 0, 100],
+        [0, 0, -10],
+        [0x7FFF, -0x8000, 1],
+    ];
+    #[cfg(target_arch = "mips")]
+    const NORMALS: [[i16; 3]; 3] = [[0, 0, 0x1000], [0x800, -0x800, 0], [0, 0, -0x1000]];
+
+    #[cfg(target_arch = "mips")]
+    fn setup<B: Backend>(gte: &mut GTE<B>) {
+        let mut lighting = Lighting::new();
+        lighting
+            .set_light(
+                0,
+                Some(Light {
+                    direction: [0, 0, 0x1000],
+                    color: Color::new(0xFF, 0xC0, 0x80),
+                }),
+            )
+            .set_ambient(Color::new(0x20, 0x20, 0x20))
+            .set_fog(Color::new(0x40, 0x40, 0x40), 100, 1000);
+        gte.set_rotation([[0xF00, 0x400, 0], [-0x400, 0xF00, 0], [0, 0, 0x1000]])
+            .set_translation([10, -20, 300])
+            .set_screen_offset(Vertex(160, 120))
+            .set_projection_distance(200)
+            .set_z_scales(0x1000 / 3, 0x1000 / 4)
+            .set_lighting(&lighting);
+    }
+
+    #[test_case]
+    fn unr_table() {
+        assert!(UNR_TABLE[0] == 0xFF);
+        assert!(UNR_TABLE[9] == 0xEE);
+        assert!(UNR_TABLE[0x100] == 0);
+    }
+
+    #[test_case]
+    fn flags() {
+        let mut gte = GTE::software();
+        gte.set_rotation([[0x1000, 0, 0], [0, 0x1000, 0], [0, 0, 0x1000]])
+            .set_screen_offset(Vertex(160, 120))
+            .set_projection_distance(200);
+        let v = gte.rtps([0, 0, -10]);
+        assert!(v.xy == Vertex(160, 120) && v.z == 0);
+        assert!(gte.flag() == ERROR | Z_SATURATED | DIVIDE_OVERFLOW);
+        let v = gte.rtps([1000, 0, 100]);
+        assert!(v.xy == Vertex(0x3FF, 120) && v.z == 100);
+        assert!(gte.flag() == ERROR | DIVIDE_OVERFLOW | SX_SATURATED);
+    }
+
+    #[test_case]
+    fn registers() {
+        let mut gte = Software::new();
+        gte.write(30, 0x0000_F000);
+        assert!(gte.read(31) == 16);
+        gte.write(30, 0xFFF0_0000);
+        assert!(gte.read(31) == 12);
+        gte.write(28, 0x7FFF);
+        assert!(gte.read(9) == 0xF80 && gte.read(29) == 0x7FFF);
+        gte.write(59, 0xFFFF);
+        assert!(gte.read(59) == 0xFFFF_FFFF);
+    }
+
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn matches_hardware() {
+        use crate::hw::gte::{LZCR, LZCS, ORGB};
+        use crate::hw::Register;
+
+        let mut hw = GTE::new();
+        let mut sw = GTE::software();
+        setup(&mut hw);
+        setup(&mut sw);
+        for v in VERTICES {
+            assert!(hw.rtps(v) == sw.rtps(v));
+            assert!(hw.flag() == sw.flag() && hw.depth_cue() == sw.depth_cue());
+        }
+        let tri = [VERTICES[0], VERTICES[1], VERTICES[2]];
+        assert!(hw.rtpt(tri) == sw.rtpt(tri));
+        assert!(hw.flag() == sw.flag());
+        assert!(hw.nclip() == sw.nclip() && hw.flag() == sw.flag());
+        assert!(hw.avsz3() == sw.avsz3() && hw.avsz4() == sw.avsz4());
+        for v in VERTICES {
+            assert!(hw.transform(v) == sw.transform(v) && hw.flag() == sw.flag());
+        }
+        for n in NORMALS {
+            let color = Color::new(0x80, 0xFF, 0x10);
+            assert!(hw.nccs(n, color) == sw.nccs(n, color) && hw.flag() == sw.flag());
+            assert!(hw.ncds(n, color) == sw.ncds(n, color) && hw.flag() == sw.flag());
+            assert!(hw.ir() == sw.ir() && hw.mac() == sw.mac());
+        }
+        assert!(hw.nct(NORMALS) == sw.nct(NORMALS) && hw.flag() == sw.flag());
+        assert!(ORGB::new().to_bits() == sw.registers().read(29));
+        let mut sw = Software::new();
+        for x in [0x0000_F000, 0xFFF0_0000, 0, !0] {
+            LZCS::skip_load().assign(x).store();
+            sw.write(30, x);
+            assert!(LZCR::new().to_bits() == sw.read(31));
+        }
+    }
+}
@@ -1,4 +1,6 @@
 //! Coprocessor register definitions
+//!
+//! Coprocessor registers can only be loaded and stored when building for MIPS.
 use crate::hw::private::Primitive;
 
 /// A coprocessor register
@@ -18,7 +20,42 @@ impl<T: Primitive, const COP: u32, const REG: u32> AsMut<T> for CopRegister<T, C
     }
 }
 
+// Control registers are numbered 32 to 63. LLVM doesn't support the `cfc` and
+// `ctc` instructions yet (#7) so they're hand-encoded with $2 as the general
+// purpose register.
 macro_rules! define_cop {
+    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr; "c" $(,)?) => {
+        $(#[$($meta)*])*
+        pub type $name = crate::hw::cop::CopRegister<$ty, $cop, $reg>;
+
+        #[cfg(target_arch = "mips")]
+        impl Register<$ty> for crate::hw::cop::CopRegister<$ty, $cop, $reg> {
+            fn skip_load() -> Self {
+                Self { value: 0 }
+            }
+            fn load(&mut self) -> &mut Self {
+                unsafe {
+                    core::arch::asm! {
+                        // cfc $2, $reg
+                        concat!(".word ((0x10 + ", $cop, ") << 26) | (2 << 21) | (2 << 16) | ((", $reg, " - 32) << 11)"), "nop",
+                        out("$2") self.value
+                    }
+                }
+                self
+            }
+
+            fn store(&mut self) -> &mut Self {
+                unsafe {
+                    core::arch::asm! {
+                        // ctc $2, $reg
+                        concat!(".word ((0x10 + ", $cop, ") << 26) | (6 << 21) | (2 << 16) | ((", $reg, " - 32) << 11)"),
+                        in("$2") self.value
+                    }
+                }
+                self
+            }
+        }
+    };
     ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(,)?) => {
         define_cop!($(#[$($meta)*])* $name<$ty>; COP: $cop; R: $reg; "m");
     };
@@ -26,6 +63,7 @@ macro_rules! define_cop {
         $(#[$($meta)*])*
         pub type $name = crate::hw::cop::CopRegister<$ty, $cop, $reg>;
 
+        #[cfg(target_arch = "mips")]
         impl Register<$ty> for crate::hw::cop::CopRegister<$ty, $cop, $reg> {
             fn skip_load() -> Self {
                 Self { value: 0 }
@@ -50,7 +88,7 @@ macro_rules! define_cop {
             }
         }
     };
-    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(;$cop_ty:literal)?, $($others:tt)*) => {
+    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(;$cop_ty:tt)?, $($others:tt)*) => {
         define_cop!($(#[$($meta)*])* $name<$ty>; COP: $cop; R: $reg $(;$cop_ty)*);
         define_cop!($($others)*);
     };
@@ -26,6 +26,48 @@ impl GP0 {
         self
     }
 
+    /// Copies a rectangle within VRAM from `src` to `dst`.
+    pub fn copy_rectangle(&mut self, src: Vertex, dst: Vertex, size: Vertex) -> &mut Self {
+        self.assign(0x80 << 24)
+            .store()
+            .assign(u32::from(src))
+            .store()
+            .assign(u32::from(dst))
+            .store()
+            .assign(u32::from(size))
+            .store();
+        self
+    }
+
+    /// Starts copying a rectangle from the CPU to VRAM.
+    ///
+    /// The rectangle's data must then be written to GP0 one word at a time or
+    /// sent through the GPU DMA channel.
+    pub fn copy_to_vram(&mut self, offset: Vertex, size: Vertex) -> &mut Self {
+        self.assign(0xA0 << 24)
+            .store()
+            .assign(u32::from(offset))
+            .store()
+            .assign(u32::from(size))
+            .store();
+        self
+    }
+
+    /// Requests copying a rectangle in VRAM to the CPU.
+    ///
+    /// Once [`gpu::Status::vram_ready`][crate::hw::gpu::Status::vram_ready]
+    /// is set, the rectangle may be read from
+    /// [`gpu::Response`][crate::hw::gpu::Response] or the GPU DMA channel.
+    pub fn request_vram(&mut self, offset: Vertex, size: Vertex) -> &mut Self {
+        self.assign(0xC0 << 24)
+            .store()
+            .assign(u32::from(offset))
+            .store()
+            .assign(u32::from(size))
+            .store();
+        self
+    }
+
     /// Sends the GP0 command `cmd` to the GPU.
     ///
     /// # Safety
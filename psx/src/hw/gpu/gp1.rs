@@ -59,8 +59,11 @@ impl GP1 {
         self
     }
 
+    /// Sets the display mode.
+    ///
     /// The x resolution is restricted to 256, 320, 512, 640 or 368. The y
-    /// resolution is restricted to 240 or 480.
+    /// resolution is restricted to the lines in a single field (240 for NTSC
+    /// or 256 for PAL) or twice that for interlaced 480i or 512i.
     pub fn display_mode(
         &mut self, res: (i16, i16), mode: VideoMode, depth: Depth, interlace: bool,
     ) -> Result<&mut Self, VertexError> {
@@ -72,9 +75,10 @@ impl GP1 {
             368 => 1 << 6,
             _ => return Err(VertexError::InvalidX),
         };
+        let lines = mode.lines();
         let vres = match res.1 {
-            240 => 0,
-            480 => 1,
+            y if y > 0 && y <= lines => 0,
+            y if y > lines && y <= 2 * lines && interlace => 1,
             _ => return Err(VertexError::InvalidY),
         };
         let settings =
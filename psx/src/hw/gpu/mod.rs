@@ -1,6 +1,6 @@
 //! GPU registers
 use crate::hw::MemRegister;
-use core::mem::size_of;
+use core::mem::{align_of, size_of};
 use core::slice;
 
 mod gp0;
@@ -20,11 +20,27 @@ pub type Response = MemRegister<u32, 0x1F80_1810>;
 pub struct Status(MemRegister<u32, 0x1F80_1814>);
 
 /// A struct whose memory layout is a valid GP0 command.
+///
+/// Structs using the default [`GP0Command::data`] must be word-aligned. Most
+/// commands only contain `u8` and `u16` fields so they need `#[repr(C,
+/// align(4))]`, otherwise reading one outside of a `Packet` would create a
+/// misaligned `&[u32]`. This is checked at compile-time.
 pub trait GP0Command: Sized {
+    #[doc(hidden)]
+    const WORD_ALIGNED: () = {
+        if align_of::<Self>() < align_of::<u32>() {
+            panic!("GP0 commands read with the default `GP0Command::data` must be word-aligned.");
+        }
+    };
+
     /// Gets `self`'s memory as a slice of 32-bit words.
+    #[allow(path_statements)]
     fn data(&self) -> &[u32] {
+        Self::WORD_ALIGNED;
         let ptr = self as *const Self as *const u32;
         let len = size_of::<Self>() / size_of::<u32>();
+        // SAFETY: `Self` is word-aligned and its first `len` words are
+        // initialized since GP0 commands don't have padding.
         unsafe { slice::from_raw_parts(ptr, len) }
     }
 }
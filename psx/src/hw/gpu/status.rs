@@ -9,6 +9,7 @@ const INTERLACE: u32 = 22;
 const DISPLAY_ENABLE: u32 = 23;
 const IRQ: u32 = 24;
 const CMD_READY: u32 = 26;
+const VRAM_READY: u32 = 27;
 const DMA_READY: u32 = 28;
 const DMA_DIRECTION: u32 = 29;
 const LINE_PARITY: u32 = 31;
@@ -58,6 +59,11 @@ impl Status {
         self.0.all_set(1 << CMD_READY)
     }
 
+    /// Checks if the GPU is ready to send VRAM to the CPU.
+    pub fn vram_ready(&self) -> bool {
+        self.0.all_set(1 << VRAM_READY)
+    }
+
     /// Checks the DMA ready bit.
     pub fn dma_ready(&self) -> bool {
         self.0.all_set(1 << DMA_READY)
@@ -87,6 +93,15 @@ impl Status {
         self
     }
 
+    /// Waits until the GPU is ready to send VRAM to the CPU. This loops and
+    /// reloads the GPUSTAT register until it's done waiting.
+    pub fn wait_vram(&mut self) -> &mut Self {
+        while !self.vram_ready() {
+            self.0.load();
+        }
+        self
+    }
+
     /// Waits until the GPU DMA is ready. This loops and reloads the GPUSTAT
     /// register until it's done waiting.
     pub fn wait_dma(&mut self) -> &mut Self {
@@ -96,7 +111,7 @@ impl Status {
         self
     }
 
-    #[cfg(test)]
+    #[cfg(all(test, target_arch = "mips"))]
     pub(crate) fn averaged_bits(&self) -> u32 {
         self.0.to_bits() & !(1 << LINE_PARITY)
     }
@@ -111,6 +126,7 @@ impl Debug for Status {
             .field("display_enabled", &self.display_enabled())
             .field("irq_pending", &self.irq_pending())
             .field("cmd_ready", &self.cmd_ready())
+            .field("vram_ready", &self.vram_ready())
             .field("dma_ready", &self.dma_ready())
             .field("dma_enabled", &self.dma_enabled())
             .field("odd_line", &self.odd_line())
@@ -1,5 +1,9 @@
-#![cfg(test)]
-use crate::gpu::DMAMode;
+// These tests use the GPU
+#![cfg(all(test, target_arch = "mips"))]
+use crate::dma;
+use crate::framebuffer::Framebuffer;
+use crate::gpu::colors::{BLUE, RED};
+use crate::gpu::{DMAMode, Depth, Vertex, VideoMode};
 use crate::hw::gpu::{GP0, GP1};
 use crate::hw::{gpu, Register};
 
@@ -55,3 +59,73 @@ fn dma_mode() {
         assert!(dma());
     });
 }
+
+#[test_case]
+fn read_vram() {
+    let mut gp0 = GP0::new();
+    GP1::new().reset_gpu();
+    gp0.fill_rectangle(RED, Vertex(0, 0), Vertex(16, 2))
+        .copy_rectangle(Vertex(0, 0), Vertex(16, 0), Vertex(16, 2));
+    let mut status = gpu::Status::new();
+    while !status.cmd_ready() {
+        status.load();
+    }
+    gp0.request_vram(Vertex(8, 0), Vertex(16, 2));
+    status.wait_vram();
+    let mut gpu_read = gpu::Response::skip_load();
+    // Red is 0x001F as a 15-bit color
+    for _ in 0..16 {
+        assert!(gpu_read.load().to_bits() == 0x001F_001F);
+    }
+}
+
+#[test_case]
+fn dma_read_vram() {
+    let mut fb = Framebuffer::default();
+    let mut gpu_dma = dma::GPU::new();
+    let mut buf = [0; 32];
+    fb.gp0.fill_rectangle(BLUE, Vertex(0, 0), Vertex(16, 4));
+    fb.dma_read_vram(Vertex(0, 0), Vertex(16, 4), &mut buf, &mut gpu_dma)
+        .unwrap();
+    // Blue is 0x7C00 as a 15-bit color
+    assert!(buf.iter().all(|&w| w == 0x7C00_7C00));
+}
+
+#[test_case]
+fn display_mode() {
+    let mut gp1 = GP1::new();
+    let mut status = gpu::Status::new();
+    gp1.display_mode((320, 256), VideoMode::PAL, Depth::Bits15, false)
+        .unwrap();
+    status.load();
+    assert!(status.video_mode() == VideoMode::PAL);
+    assert!(!status.interlaced());
+    gp1.display_mode((640, 480), VideoMode::NTSC, Depth::Bits15, true)
+        .unwrap();
+    status.load();
+    assert!(status.video_mode() == VideoMode::NTSC);
+    assert!(status.interlaced());
+    assert!(gp1
+        .display_mode((640, 480), VideoMode::NTSC, Depth::Bits15, false)
+        .is_err());
+    assert!(gp1
+        .display_mode((300, 240), VideoMode::NTSC, Depth::Bits15, false)
+        .is_err());
+    gp1.reset_gpu();
+}
+
+#[test_case]
+fn load_image24() {
+    let mut fb = Framebuffer::default();
+    // Two pixels (0x11, 0x22, 0x33) and (0x44, 0x55, 0x66) take 3 halfwords
+    let image = [0x4433_2211, 0x0000_6655];
+    fb.load_image24(Vertex(512, 0), Vertex(2, 1), &image)
+        .unwrap();
+    assert!(fb
+        .load_image24(Vertex(512, 0), Vertex(3, 1), &image)
+        .is_err());
+    let mut buf = [0; 2];
+    fb.read_vram(Vertex(512, 0), Vertex(3, 1), &mut buf);
+    assert!(buf[0] == 0x4433_2211);
+    assert!(buf[1] & 0xFFFF == 0x6655);
+}
@@ -2,8 +2,60 @@
 //!
 //! This module provides access to GTE, or cop2, registers and instructions.
 
+use crate::hw::cop::CopRegister;
+use crate::hw::private::Word;
+#[cfg(target_arch = "mips")]
 use crate::hw::Register;
 
+/// A GTE register which may be accessed by its number.
+pub trait GTERegister {
+    /// The type of the register's value.
+    type Value: Word;
+    /// The register's number. Control registers are numbered 32 to 63.
+    const INDEX: u32;
+
+    /// Converts a 32-bit word moved to or from the register to its value.
+    fn from_word(word: u32) -> Self::Value {
+        Self::Value::from_word(word)
+    }
+
+    /// Converts the register's value to a 32-bit word. Signed values are sign
+    /// extended.
+    fn to_word(value: Self::Value) -> u32 {
+        value.to_word()
+    }
+
+    /// Reads the register's value from cop2.
+    #[cfg(target_arch = "mips")]
+    fn read() -> Self::Value;
+
+    /// Writes `value` to the register in cop2.
+    #[cfg(target_arch = "mips")]
+    fn write(value: Self::Value);
+}
+
+#[cfg(target_arch = "mips")]
+impl<T: Word, const REG: u32> GTERegister for CopRegister<T, 2, REG>
+where Self: Register<T>
+{
+    type Value = T;
+    const INDEX: u32 = REG;
+
+    fn read() -> T {
+        Self::new().to_bits()
+    }
+
+    fn write(value: T) {
+        Self::skip_load().assign(value).store();
+    }
+}
+
+#[cfg(not(target_arch = "mips"))]
+impl<T: Word, const REG: u32> GTERegister for CopRegister<T, 2, REG> {
+    type Value = T;
+    const INDEX: u32 = REG;
+}
+
 define_cop! {
     /// The 16-bit VX0 and VY0 vectors
     VXY0<u32>; COP: 2; R: 0,
@@ -18,9 +70,45 @@ define_cop! {
     /// The 16-bit VZ2 vector
     VZ2<i16>;  COP: 2; R: 5,
 
+    /// Color and GPU command code
+    RGBC<u32>; COP: 2; R: 6,
     /// Ordering table average Z value
     OTZ<u16>;  COP: 2; R: 7,
 
+    /// The 16-bit scalar accumulator
+    IR0<i16>; COP: 2; R: 8,
+    /// The first component of the 16-bit vector accumulator
+    IR1<i16>; COP: 2; R: 9,
+    /// The second component of the 16-bit vector accumulator
+    IR2<i16>; COP: 2; R: 10,
+    /// The third component of the 16-bit vector accumulator
+    IR3<i16>; COP: 2; R: 11,
+
+    /// The oldest entry in the screen XY coordinate FIFO
+    SXY0<u32>; COP: 2; R: 12,
+    /// The middle entry in the screen XY coordinate FIFO
+    SXY1<u32>; COP: 2; R: 13,
+    /// The newest entry in the screen XY coordinate FIFO
+    SXY2<u32>; COP: 2; R: 14,
+    /// Pushes onto the screen XY coordinate FIFO when written
+    SXYP<u32>; COP: 2; R: 15,
+
+    /// The oldest entry in the screen Z coordinate FIFO
+    SZ0<u16>; COP: 2; R: 16,
+    /// The second entry in the screen Z coordinate FIFO
+    SZ1<u16>; COP: 2; R: 17,
+    /// The third entry in the screen Z coordinate FIFO
+    SZ2<u16>; COP: 2; R: 18,
+    /// The newest entry in the screen Z coordinate FIFO
+    SZ3<u16>; COP: 2; R: 19,
+
+    /// The oldest entry in the color FIFO
+    RGB0<u32>; COP: 2; R: 20,
+    /// The middle entry in the color FIFO
+    RGB1<u32>; COP: 2; R: 21,
+    /// The newest entry in the color FIFO
+    RGB2<u32>; COP: 2; R: 22,
+
     /// Scalar math accumulator
     MAC0<i32>; COP: 2; R: 24,
 
@@ -31,44 +119,102 @@ define_cop! {
     /// The third component of the vector math accumulator
     MAC3<i32>; COP: 2; R: 27,
 
+    /// Converts a 5-bit color to `IR1`, `IR2` and `IR3` when written
+    IRGB<u32>; COP: 2; R: 28,
+    /// Converts `IR1`, `IR2` and `IR3` to a 5-bit color when read
+    ORGB<u32>; COP: 2; R: 29,
+
     /// Leading zeros count source
     LZCS<u32>; COP: 2; R: 30,
     /// Leading zeros count result
     LZCR<u32>; COP: 2; R: 31,
 
-    /*
-    TODO: LLVM doesn't support these coprocessor instructions yet (#7).
     /// Rotation matrix entries RT11 and RT12
     RT11_12<u32>; COP: 2; R: 32; "c",
     /// Rotation matrix entries RT13 and RT21
-    RT13_21<u32>; COP: 2; R: 33,
+    RT13_21<u32>; COP: 2; R: 33; "c",
     /// Rotation matrix entries RT22 and RT23
-    RT22_23<u32>; COP: 2; R: 34,
+    RT22_23<u32>; COP: 2; R: 34; "c",
     /// Rotation matrix entries RT31 and RT32
-    RT31_32<u32>; COP: 2; R: 35,
+    RT31_32<u32>; COP: 2; R: 35; "c",
     /// Rotation matrix entry RT33
-    RT33<i16>;    COP: 2; R: 36,
+    RT33<i16>;    COP: 2; R: 36; "c",
+
+    /// The X component of the translation vector
+    TRX<i32>; COP: 2; R: 37; "c",
+    /// The Y component of the translation vector
+    TRY<i32>; COP: 2; R: 38; "c",
+    /// The Z component of the translation vector
+    TRZ<i32>; COP: 2; R: 39; "c",
 
     /// Light matrix entries L11 and L12
-    L11_12<u32>; COP: 2; R: 40,
+    L11_12<u32>; COP: 2; R: 40; "c",
     /// Light matrix entries L13 and L21
-    L13_21<u32>; COP: 2; R: 41,
+    L13_21<u32>; COP: 2; R: 41; "c",
     /// Light matrix entries L22 and L23
-    L22_23<u32>; COP: 2; R: 42,
+    L22_23<u32>; COP: 2; R: 42; "c",
     /// Light matrix entries L31 and L32
-    L31_32<u32>; COP: 2; R: 43,
+    L31_32<u32>; COP: 2; R: 43; "c",
     /// Light matrix entry L33
-    L33<i16>;    COP: 2; R: 44,
+    L33<i16>;    COP: 2; R: 44; "c",
+
+    /// The red component of the background color
+    RBK<i32>; COP: 2; R: 45; "c",
+    /// The green component of the background color
+    GBK<i32>; COP: 2; R: 46; "c",
+    /// The blue component of the background color
+    BBK<i32>; COP: 2; R: 47; "c",
 
     /// Light color matrix entries LR11 and LR12
-    LR11_12<u32>; COP: 2; R: 48,
+    LR11_12<u32>; COP: 2; R: 48; "c",
     /// Light color matrix entries LR13 and LR21
-    LR13_21<u32>; COP: 2; R: 49,
+    LR13_21<u32>; COP: 2; R: 49; "c",
     /// Light color matrix entries LR22 and LR23
-    LR22_23<u32>; COP: 2; R: 50,
+    LR22_23<u32>; COP: 2; R: 50; "c",
     /// Light color matrix entries LR31 and LR32
-    LR31_32<u32>; COP: 2; R: 51,
+    LR31_32<u32>; COP: 2; R: 51; "c",
     /// Light color matrix entry LR33
-    LR33<i16>; COP: 2; R: 52,
-    */
+    LR33<i16>;    COP: 2; R: 52; "c",
+
+    /// The red component of the far color
+    RFC<i32>; COP: 2; R: 53; "c",
+    /// The green component of the far color
+    GFC<i32>; COP: 2; R: 54; "c",
+    /// The blue component of the far color
+    BFC<i32>; COP: 2; R: 55; "c",
+
+    /// Screen X offset
+    OFX<i32>; COP: 2; R: 56; "c",
+    /// Screen Y offset
+    OFY<i32>; COP: 2; R: 57; "c",
+    /// Projection plane distance
+    H<u16>;   COP: 2; R: 58; "c",
+    /// Depth queuing parameter coefficient
+    DQA<i16>; COP: 2; R: 59; "c",
+    /// Depth queuing parameter offset
+    DQB<i32>; COP: 2; R: 60; "c",
+
+    /// Scale factor for the average Z of three values
+    ZSF3<i16>; COP: 2; R: 61; "c",
+    /// Scale factor for the average Z of four values
+    ZSF4<i16>; COP: 2; R: 62; "c",
+    /// Calculation error flags
+    FLAG<u32>; COP: 2; R: 63; "c",
+}
+
+#[cfg(all(test, target_arch = "mips"))]
+mod tests {
+    use super::{RT11_12, RT33, TRX};
+    use crate::hw::{cop0, Register};
+
+    #[test_case]
+    fn control_registers() {
+        cop0::Status::new().enable_gte().store();
+        TRX::skip_load().assign(-1234).store();
+        assert!(TRX::new().to_bits() == -1234);
+        RT11_12::skip_load().assign(0x1000_F000).store();
+        assert!(RT11_12::new().to_bits() == 0x1000_F000);
+        RT33::skip_load().assign(-0x1000).store();
+        assert!(RT33::new().to_bits() == -0x1000);
+    }
 }
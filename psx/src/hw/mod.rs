@@ -12,6 +12,7 @@
 #[macro_use]
 pub mod cop;
 
+#[cfg(target_arch = "mips")]
 pub mod cop0;
 pub mod dma;
 pub mod gpu;
@@ -98,6 +99,30 @@ mod private {
     impl Primitive for u32 {}
     impl Primitive for i16 {}
     impl Primitive for i32 {}
+
+    // Converts register values to and from the 32-bit words moved by the
+    // coprocessor instructions.
+    pub trait Word: Primitive {
+        fn from_word(word: u32) -> Self;
+        fn to_word(self) -> u32;
+    }
+
+    macro_rules! impl_word {
+        ($($ty:ty)*) => {
+            $(
+                impl Word for $ty {
+                    fn from_word(word: u32) -> Self {
+                        word as $ty
+                    }
+                    fn to_word(self) -> u32 {
+                        self as u32
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_word!(u16 u32 i16 i32);
 }
 
 /// A coprocessor or memory-mapped I/O register.
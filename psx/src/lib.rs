@@ -57,8 +57,10 @@
 // Used for crate tests
 #![feature(custom_test_frameworks)]
 #![test_runner(crate::test::runner)]
-#![reexport_test_harness_main = "main"]
-#![cfg_attr(test, no_main)]
+// On the console the runtime calls the test harness's `main`. Tests built for
+// other hosts use std's runtime instead.
+#![cfg_attr(target_arch = "mips", reexport_test_harness_main = "main")]
+#![cfg_attr(all(test, target_arch = "mips"), no_main)]
 
 // This module is first since it defines the fuzz macros for tests
 #[macro_use]
@@ -68,11 +70,13 @@ pub mod dma;
 pub mod format;
 mod framebuffer;
 pub mod gpu;
+pub mod gte;
 #[doc(hidden)]
 pub mod heap;
 pub mod hw;
 mod macros;
 pub mod math;
+#[cfg(target_arch = "mips")]
 mod panic;
 #[doc(hidden)]
 pub mod runtime;
@@ -130,11 +134,11 @@ pub mod irq {
     }
 }
 
-#[cfg(not(feature = "custom_oom"))]
+#[cfg(all(target_arch = "mips", not(feature = "custom_oom")))]
 #[alloc_error_handler]
 fn on_oom(layout: core::alloc::Layout) -> ! {
     panic!("Ran out of memory {:?}", layout);
 }
 
-pub use framebuffer::{Framebuffer, LoadedTIM};
+pub use framebuffer::{Align, Framebuffer, LoadedTIM, TextBatch, TextBox};
 //pub use format::tim::{Bitmap, TIMError, TIM};
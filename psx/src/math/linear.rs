@@ -0,0 +1,380 @@
+use super::{cos, sin, Rad};
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+// 1.0 in 1.3.12 fixed-point
+const ONE: i16 = 0x1000;
+
+/// Converts an 8.8 sine or cosine to 1.3.12 fixed-point.
+fn sin_cos(theta: Rad) -> (i16, i16) {
+    (sin(theta).0 << 4, cos(theta).0 << 4)
+}
+
+fn saturate(x: i64) -> i16 {
+    x.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+// Multiplies a row of a 1.3.12 matrix by a vector keeping the vector's
+// fractional bits
+fn dot(row: [i16; 3], v: [i32; 3]) -> i64 {
+    let sum: i64 = row
+        .into_iter()
+        .zip(v)
+        .map(|(m, v)| m as i64 * v as i64)
+        .sum();
+    sum >> 12
+}
+
+macro_rules! impl_vector {
+    ($name:ident, $($field:ident),*) => {
+        impl $name {
+            /// Computes the dot product of two vectors.
+            pub fn dot(self, other: Self) -> i32 {
+                [$(self.$field as i32 * other.$field as i32),*].into_iter().sum()
+            }
+        }
+
+        impl Add<$name> for $name {
+            type Output = $name;
+            fn add(self, other: $name) -> $name {
+                $name { $($field: self.$field + other.$field),* }
+            }
+        }
+
+        impl Sub<$name> for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name {
+                $name { $($field: self.$field - other.$field),* }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),* }
+            }
+        }
+
+        impl Mul<i16> for $name {
+            type Output = $name;
+            fn mul(self, other: i16) -> $name {
+                $name { $($field: self.$field * other),* }
+            }
+        }
+
+        impl AddAssign<$name> for $name {
+            fn add_assign(&mut self, other: $name) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign<$name> for $name {
+            fn sub_assign(&mut self, other: $name) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign<i16> for $name {
+            fn mul_assign(&mut self, other: i16) {
+                *self = *self * other;
+            }
+        }
+    };
+}
+
+/// A vector with 16-bit components.
+///
+/// Components may be integers or 1.3.12 fixed-point as used for normals.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Vec3 {
+    /// The x component.
+    pub x: i16,
+    /// The y component.
+    pub y: i16,
+    /// The z component.
+    pub z: i16,
+}
+
+/// A vector with four 16-bit components.
+///
+/// This has the same layout as a [`Vec3`] padded to 8 bytes.
+#[repr(C, align(4))]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Vec4 {
+    /// The x component.
+    pub x: i16,
+    /// The y component.
+    pub y: i16,
+    /// The z component.
+    pub z: i16,
+    /// The w component.
+    pub w: i16,
+}
+
+impl_vector!(Vec3, x, y, z);
+impl_vector!(Vec4, x, y, z, w);
+
+impl Vec3 {
+    /// The zero vector.
+    pub const ZERO: Self = Vec3::new(0, 0, 0);
+
+    /// Creates a new vector.
+    pub const fn new(x: i16, y: i16, z: i16) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    /// Extends the vector with a w component.
+    pub const fn extend(self, w: i16) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+}
+
+impl Vec4 {
+    /// The zero vector.
+    pub const ZERO: Self = Vec4::new(0, 0, 0, 0);
+
+    /// Creates a new vector.
+    pub const fn new(x: i16, y: i16, z: i16, w: i16) -> Self {
+        Vec4 { x, y, z, w }
+    }
+
+    /// Gets the x, y and z components.
+    pub const fn xyz(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+impl From<[i16; 3]> for Vec3 {
+    fn from([x, y, z]: [i16; 3]) -> Self {
+        Vec3 { x, y, z }
+    }
+}
+
+impl From<Vec3> for [i16; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<[i16; 4]> for Vec4 {
+    fn from([x, y, z, w]: [i16; 4]) -> Self {
+        Vec4 { x, y, z, w }
+    }
+}
+
+impl From<Vec4> for [i16; 4] {
+    fn from(v: Vec4) -> Self {
+        [v.x, v.y, v.z, v.w]
+    }
+}
+
+/// A 3x3 matrix with 1.3.12 fixed-point entries.
+///
+/// Products are saturated to the range of an `i16` like the GTE's results.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Mat3(pub [[i16; 3]; 3]);
+
+impl Mat3 {
+    /// The zero matrix.
+    pub const ZERO: Self = Mat3([[0; 3]; 3]);
+
+    /// The identity matrix.
+    pub const IDENTITY: Self = Mat3([[ONE, 0, 0], [0, ONE, 0], [0, 0, ONE]]);
+
+    /// Creates a matrix which rotates by `theta` radians about the x axis.
+    pub fn rotation_x(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[ONE, 0, 0], [0, c, -s], [0, s, c]])
+    }
+
+    /// Creates a matrix which rotates by `theta` radians about the y axis.
+    pub fn rotation_y(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[c, 0, s], [0, ONE, 0], [-s, 0, c]])
+    }
+
+    /// Creates a matrix which rotates by `theta` radians about the z axis.
+    pub fn rotation_z(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[c, -s, 0], [s, c, 0], [0, 0, ONE]])
+    }
+
+    /// Creates a matrix which rotates about the x axis, then the y axis and
+    /// then the z axis.
+    pub fn from_euler(x: Rad, y: Rad, z: Rad) -> Self {
+        Self::rotation_z(z) * Self::rotation_y(y) * Self::rotation_x(x)
+    }
+
+    /// Gets the matrix's transpose.
+    pub const fn transpose(self) -> Self {
+        let m = self.0;
+        Mat3([
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ])
+    }
+
+    /// Gets the matrix's inverse.
+    ///
+    /// This is only correct for orthonormal matrices such as rotations, where
+    /// the inverse is the transpose.
+    pub const fn inverse(self) -> Self {
+        self.transpose()
+    }
+
+    fn rotate(self, v: [i32; 3]) -> [i64; 3] {
+        self.0.map(|row| dot(row, v))
+    }
+}
+
+impl Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+    fn mul(self, other: Mat3) -> Mat3 {
+        let cols = other.transpose().0;
+        Mat3(
+            self.0
+                .map(|row| cols.map(|col| saturate(dot(row, col.map(|x| x as i32))))),
+        )
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+    fn mul(self, v: Vec3) -> Vec3 {
+        let [x, y, z] = self.rotate([v.x as i32, v.y as i32, v.z as i32]);
+        Vec3::new(saturate(x), saturate(y), saturate(z))
+    }
+}
+
+impl MulAssign<Mat3> for Mat3 {
+    fn mul_assign(&mut self, other: Mat3) {
+        *self = *self * other;
+    }
+}
+
+impl From<[[i16; 3]; 3]> for Mat3 {
+    fn from(m: [[i16; 3]; 3]) -> Self {
+        Mat3(m)
+    }
+}
+
+impl From<Mat3> for [[i16; 3]; 3] {
+    fn from(m: Mat3) -> Self {
+        m.0
+    }
+}
+
+/// A rotation followed by a translation, as used by the GTE's perspective
+/// transformations.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Transform {
+    /// The rotation matrix.
+    pub rotation: Mat3,
+    /// The translation vector with 32-bit components.
+    pub translation: [i32; 3],
+}
+
+impl Transform {
+    /// The identity transform.
+    pub const IDENTITY: Self = Transform::new(Mat3::IDENTITY, [0; 3]);
+
+    /// Creates a new transform.
+    pub const fn new(rotation: Mat3, translation: [i32; 3]) -> Self {
+        Transform {
+            rotation,
+            translation,
+        }
+    }
+
+    /// Gets the transform's inverse.
+    ///
+    /// This is only correct if the rotation matrix is orthonormal.
+    pub fn inverse(self) -> Self {
+        let rotation = self.rotation.inverse();
+        let translation = rotation.rotate(self.translation).map(|x| -x as i32);
+        Transform::new(rotation, translation)
+    }
+}
+
+impl Mul<Transform> for Transform {
+    type Output = Transform;
+    fn mul(self, other: Transform) -> Transform {
+        let rotation = self.rotation * other.rotation;
+        let rotated = self.rotation.rotate(other.translation);
+        let mut translation = self.translation;
+        for (t, r) in translation.iter_mut().zip(rotated) {
+            *t += r as i32;
+        }
+        Transform::new(rotation, translation)
+    }
+}
+
+impl Mul<Vec3> for Transform {
+    type Output = [i32; 3];
+    fn mul(self, v: Vec3) -> [i32; 3] {
+        let rotated = self.rotation.rotate([v.x as i32, v.y as i32, v.z as i32]);
+        let mut res = self.translation;
+        for (t, r) in res.iter_mut().zip(rotated) {
+            *t += r as i32;
+        }
+        res
+    }
+}
+
+impl MulAssign<Transform> for Transform {
+    fn mul_assign(&mut self, other: Transform) {
+        *self = *self * other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mat3, Transform, Vec3, Vec4};
+    use crate::math::{FRAC_PI_2, FRAC_PI_6};
+
+    #[test_case]
+    fn vectors() {
+        let a = Vec3::new(1, -2, 3);
+        let b = Vec3::new(4, 5, -6);
+        assert!(a + b == Vec3::new(5, 3, -3));
+        assert!(a - b == Vec3::new(-3, -7, 9));
+        assert!(-a * 2 == Vec3::new(-2, 4, -6));
+        assert!(a.dot(b) == -24);
+        assert!(a.extend(7) == Vec4::new(1, -2, 3, 7));
+        assert!(a.extend(7).xyz() == a);
+    }
+
+    #[test_case]
+    fn rotations() {
+        let quarter = Mat3::rotation_z(FRAC_PI_2);
+        assert!(quarter == Mat3([[0, -0x1000, 0], [0x1000, 0, 0], [0, 0, 0x1000]]));
+        assert!(quarter * Vec3::new(1, 2, 3) == Vec3::new(-2, 1, 3));
+        assert!(quarter * quarter.inverse() == Mat3::IDENTITY);
+        let half = Mat3([[-0x1000, 0, 0], [0, -0x1000, 0], [0, 0, 0x1000]]);
+        assert!(quarter * quarter == half);
+        assert!(
+            Mat3::from_euler(FRAC_PI_2, FRAC_PI_2, FRAC_PI_2) * Vec3::new(1, 2, 3) ==
+                Vec3::new(3, 2, -1)
+        );
+
+        // The sine table only has 8 fractional bits so rotations at other angles
+        // are only approximately orthonormal
+        let m = Mat3::from_euler(FRAC_PI_6, FRAC_PI_6, FRAC_PI_6);
+        let product = m * m.transpose();
+        for (row, id) in product.0.into_iter().zip(Mat3::IDENTITY.0) {
+            for (x, y) in row.into_iter().zip(id) {
+                assert!((x - y).abs() < 0x40);
+            }
+        }
+    }
+
+    #[test_case]
+    fn transforms() {
+        let t = Transform::new(Mat3::rotation_z(FRAC_PI_2), [10, 20, 30]);
+        assert!(t * Vec3::new(1, 2, 3) == [8, 21, 33]);
+        assert!(t.inverse() * Vec3::new(8, 21, 33) == [1, 2, 3]);
+        assert!(t * t.inverse() == Transform::IDENTITY);
+        assert!((t * t) * Vec3::new(1, 2, 3) == t * Vec3::new(8, 21, 33));
+    }
+}
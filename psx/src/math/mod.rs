@@ -1,8 +1,14 @@
 //! Fixed-point and trigonometry functions.
+//!
+//! This also has vector and matrix types in the formats used by the GTE.
 
 use core::hint::unreachable_unchecked;
 use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+mod linear;
+
+pub use linear::{Mat3, Transform, Vec3, Vec4};
+
 // TODO: Replace the derived Debug impl with a custom human-readable one
 /// A signed 16-bit fixed-point number with 7-bit integral and 8-bit fractional
 /// parts.
@@ -90,17 +96,18 @@ pub const FRAC_PI_6: Rad = Rad(0x1555);
 /// π/8
 pub const FRAC_PI_8: Rad = Rad(0x1000);
 
+// Angles wrap around at 2π
 impl Add<Rad> for Rad {
     type Output = Rad;
     fn add(self, other: Rad) -> Rad {
-        Rad(self.0 + other.0)
+        Rad(self.0.wrapping_add(other.0))
     }
 }
 
 impl Sub<Rad> for Rad {
     type Output = Rad;
     fn sub(self, other: Rad) -> Rad {
-        Rad(self.0 - other.0)
+        Rad(self.0.wrapping_sub(other.0))
     }
 }
 
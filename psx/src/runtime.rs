@@ -1,3 +1,4 @@
+#[cfg(target_arch = "mips")]
 use core::mem::{size_of, transmute};
 
 /// Define a constructor that runs before `main`.
@@ -29,12 +30,13 @@ macro_rules! dtor {
     };
 }
 
-#[cfg(feature = "loadable_exe")]
+#[cfg(all(target_arch = "mips", feature = "loadable_exe"))]
 type RtReturn = ();
-#[cfg(not(feature = "loadable_exe"))]
+#[cfg(all(target_arch = "mips", not(feature = "loadable_exe")))]
 type RtReturn = !;
 
 /// The runtime used by the default linker scripts.
+#[cfg(target_arch = "mips")]
 #[no_mangle]
 extern "C" fn __start() -> RtReturn {
     // SAFETY: If there is no unmangled function named `main` this causes an error
@@ -0,0 +1,164 @@
+//! Shift-JIS text and the BIOS Kanji ROM font
+//!
+//! The BIOS ROM contains 16x15 glyphs for the full-width Shift-JIS characters
+//! from `0x8140` to `0x84BE` (symbols, full-width ASCII and kana) and the
+//! level 1 Kanji from `0x889F` to `0x9872`.
+use crate::sys::kernel;
+
+/// The width of a Kanji ROM glyph in pixels.
+pub const GLYPH_WIDTH: usize = 16;
+/// The height of a Kanji ROM glyph in pixels.
+pub const GLYPH_HEIGHT: usize = 15;
+
+/// The full-width space.
+pub const SPACE: u16 = 0x8140;
+/// The full-width question mark.
+pub const QUESTION_MARK: u16 = 0x8148;
+
+/// A 1-bit glyph in the Kanji ROM.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph(&'static [u8; GLYPH_HEIGHT * 2]);
+
+impl Glyph {
+    /// Gets the glyph for the two-byte Shift-JIS character `sjis`.
+    ///
+    /// Returns `None` if the character is not in the Kanji ROM.
+    pub fn new(sjis: u16) -> Option<Self> {
+        // SAFETY: Krom2RawAdd only computes an address in the BIOS ROM.
+        let ptr = unsafe { kernel::krom2_raw_add(sjis) };
+        if ptr as usize == usize::MAX {
+            return None
+        }
+        // SAFETY: The BIOS returned the address of a glyph in ROM which is
+        // always mapped.
+        Some(Glyph(unsafe { &*(ptr as *const [u8; GLYPH_HEIGHT * 2]) }))
+    }
+
+    /// Gets a row of the glyph with the leftmost pixel in the most significant
+    /// bit.
+    pub fn row(&self, y: usize) -> u16 {
+        u16::from_be_bytes([self.0[2 * y], self.0[2 * y + 1]])
+    }
+
+    /// Checks if the pixel at `(x, y)` is set.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.row(y) & (0x8000 >> x) != 0
+    }
+}
+
+/// Maps a printable ASCII character to its full-width Shift-JIS equivalent.
+pub fn to_full_width(ascii: u8) -> Option<u16> {
+    let sjis = match ascii {
+        b' ' => SPACE,
+        b'0'..=b'9' => 0x824F + (ascii - b'0') as u16,
+        b'A'..=b'Z' => 0x8260 + (ascii - b'A') as u16,
+        b'a'..=b'z' => 0x8281 + (ascii - b'a') as u16,
+        b'!' => 0x8149,
+        b'"' => 0x8168,
+        b'#' => 0x8194,
+        b'$' => 0x8190,
+        b'%' => 0x8193,
+        b'&' => 0x8195,
+        b'\'' => 0x8166,
+        b'(' => 0x8169,
+        b')' => 0x816A,
+        b'*' => 0x8196,
+        b'+' => 0x817B,
+        b',' => 0x8143,
+        b'-' => 0x817C,
+        b'.' => 0x8144,
+        b'/' => 0x815E,
+        b':' => 0x8146,
+        b';' => 0x8147,
+        b'<' => 0x8183,
+        b'=' => 0x8181,
+        b'>' => 0x8184,
+        b'?' => QUESTION_MARK,
+        b'@' => 0x8197,
+        b'[' => 0x816D,
+        b'\\' => 0x815F,
+        b']' => 0x816E,
+        b'^' => 0x814F,
+        b'_' => 0x8151,
+        b'`' => 0x814D,
+        b'{' => 0x816F,
+        b'|' => 0x8162,
+        b'}' => 0x8170,
+        b'~' => 0x8160,
+        _ => return None,
+    };
+    Some(sjis)
+}
+
+/// An iterator over the characters in a Shift-JIS string.
+///
+/// Single-byte characters are returned as is and two-byte characters are
+/// returned with the lead byte in the upper 8 bits. A lead byte at the end of
+/// the string is returned as a single-byte character.
+#[derive(Clone, Debug)]
+pub struct Chars<'a> {
+    bytes: &'a [u8],
+}
+
+/// Returns an iterator over the characters in the Shift-JIS string `bytes`.
+pub fn chars(bytes: &[u8]) -> Chars {
+    Chars { bytes }
+}
+
+impl Iterator for Chars<'_> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        let (&lead, rest) = self.bytes.split_first()?;
+        let two_bytes = matches!(lead, 0x81..=0x9F | 0xE0..=0xFC);
+        match rest.split_first() {
+            Some((&trail, rest)) if two_bytes => {
+                self.bytes = rest;
+                Some(u16::from_be_bytes([lead, trail]))
+            },
+            _ => {
+                self.bytes = rest;
+                Some(lead as u16)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "mips")]
+    use super::Glyph;
+    use super::{chars, to_full_width, QUESTION_MARK, SPACE};
+
+    #[test_case]
+    fn shift_jis() {
+        // "Aあ" followed by a half-width katakana and a truncated lead byte
+        let mut text = chars(b"A\x82\xA0\xB1\x88");
+        assert!(text.next() == Some(b'A' as u16));
+        assert!(text.next() == Some(0x82A0));
+        assert!(text.next() == Some(0xB1));
+        assert!(text.next() == Some(0x88));
+        assert!(text.next().is_none());
+    }
+
+    #[test_case]
+    fn full_width() {
+        assert!(to_full_width(b' ') == Some(SPACE));
+        assert!(to_full_width(b'?') == Some(QUESTION_MARK));
+        assert!(to_full_width(b'7') == Some(0x8256));
+        assert!(to_full_width(b'Z') == Some(0x8279));
+        assert!(to_full_width(b'a') == Some(0x8281));
+        assert!(to_full_width(b'\n').is_none());
+    }
+
+    // Glyphs are read from the BIOS ROM
+    #[cfg(target_arch = "mips")]
+    #[test_case]
+    fn rom_glyph() {
+        assert!(Glyph::new(0x0041).is_none());
+        let glyph = Glyph::new(0x8260).unwrap();
+        assert!((0..15).any(|y| glyph.row(y) != 0));
+        let space = Glyph::new(SPACE).unwrap();
+        assert!((0..15).all(|y| space.row(y) == 0));
+    }
+}
@@ -3,6 +3,7 @@
 //! These are wrappers for calling BIOS functions directly.
 // This file was automatically generated by gen_bios_mod.rs
 
+#[cfg(target_arch = "mips")]
 core::arch::global_asm!(include_str!("trampoline.s"));
 
 extern "C" {
@@ -134,6 +135,10 @@ extern "C" {
     pub fn start_card();
     /// Calls BIOS function [B(4Ch)](http://problemkaputt.de/psx-spx.htm#biosfunctionsummary)
     pub fn stop_card();
+    /// Calls BIOS function [B(51h)](http://problemkaputt.de/psx-spx.htm#biosfunctionsummary)
+    pub fn krom2_raw_add(sjis: u16) -> *const u8;
+    /// Calls BIOS function [B(53h)](http://problemkaputt.de/psx-spx.htm#biosfunctionsummary)
+    pub fn krom2_offset(sjis: u16) -> u32;
     /// Calls BIOS function [B(54h)](http://problemkaputt.de/psx-spx.htm#biosfunctionsummary)
     pub fn get_last_error() -> u32;
     /// Calls BIOS function [B(55h)](http://problemkaputt.de/psx-spx.htm#biosfunctionsummary)
@@ -5,9 +5,11 @@
 pub mod fs;
 pub mod gamepad;
 pub mod heap;
+pub mod kanji;
 pub mod kernel;
 pub mod rng;
 pub mod tty;
+pub mod vsync;
 
 /// Calls the given function in an interrupt-free critical section using BIOS
 /// syscalls.
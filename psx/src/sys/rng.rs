@@ -49,6 +49,7 @@ impl Rng {
 }
 
 /// Checks that a single rng step produces a 15-bit number.
+#[cfg(target_arch = "mips")]
 #[test_case]
 fn rng_size() {
     fuzz!(|seed: u32| {
@@ -58,6 +59,7 @@ fn rng_size() {
     });
 }
 
+#[cfg(target_arch = "mips")]
 #[test_case]
 fn rng_state() {
     fuzz!(|seed: u32, steps: u8| {
@@ -385,6 +385,18 @@ stop_card:
     j 0xB0
     li $9, 0x4C
 
+.section .text.bios.krom2_raw_add
+.globl krom2_raw_add
+krom2_raw_add:
+    j 0xB0
+    li $9, 0x51
+
+.section .text.bios.krom2_offset
+.globl krom2_offset
+krom2_offset:
+    j 0xB0
+    li $9, 0x53
+
 .section .text.bios.get_last_error
 .globl get_last_error
 get_last_error:
@@ -0,0 +1,291 @@
+//! Vblank interrupt frame counter and frame pacing
+//!
+//! [`VSync`] installs a BIOS event handler which counts vblank interrupts and
+//! runs registered callbacks, for example to poll input or update music at a
+//! steady rate regardless of the game loop. [`FrameLimiter`] uses the count to
+//! run the game loop at a fixed fraction of the refresh rate and reports when
+//! frames are dropped.
+use crate::gpu::VideoMode;
+use crate::math::f16;
+use crate::sys::{critical_section, kernel};
+use core::ptr;
+
+// The BIOS event for the vblank root counter interrupt
+const VBLANK_CLASS: u32 = 0xF200_0003;
+const VBLANK_TIMER: u32 = 3;
+const INTERRUPT_SPEC: u16 = 0x0002;
+// Calls the event's handler when the event is delivered
+const CALLBACK_MODE: u16 = 0x1000;
+const INVALID_EVENT: u32 = u32::MAX;
+
+/// The maximum number of vblank callbacks.
+pub const MAX_CALLBACKS: usize = 4;
+
+// These are only modified by the handler or in critical sections
+static mut VBLANKS: u32 = 0;
+static mut CALLBACKS: [Option<fn()>; MAX_CALLBACKS] = [None; MAX_CALLBACKS];
+static mut INSTALLED: bool = false;
+
+extern "C" fn on_vblank() {
+    // SAFETY: The BIOS calls this with interrupts disabled so nothing else can
+    // access the statics.
+    unsafe {
+        VBLANKS = VBLANKS.wrapping_add(1);
+        for f in CALLBACKS.into_iter().flatten() {
+            f();
+        }
+    }
+}
+
+/// Gets the number of vblanks since the handler was installed.
+fn vblanks() -> u32 {
+    // SAFETY: Aligned word reads can't be interrupted partway through.
+    unsafe { ptr::addr_of!(VBLANKS).read_volatile() }
+}
+
+/// Checks if a [`VSync`] handler is installed.
+pub(crate) fn is_installed() -> bool {
+    // SAFETY: This is only modified in critical sections.
+    unsafe { ptr::addr_of!(INSTALLED).read_volatile() }
+}
+
+/// Spins until the next vblank interrupt.
+pub(crate) fn wait_vblank() {
+    let start = vblanks();
+    while vblanks() == start {}
+}
+
+/// A handle to a registered vblank callback.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Callback(usize);
+
+/// A vblank interrupt handler.
+///
+/// Only one handler may be installed at a time. While it's installed the BIOS
+/// acknowledges vblank interrupts so
+/// [`Framebuffer::wait_vblank`][crate::Framebuffer::wait_vblank] waits for the
+/// handler's count to change instead of polling the interrupt status register.
+/// Interrupts must be enabled for the handler to run.
+pub struct VSync {
+    event: u32,
+}
+
+impl VSync {
+    /// Installs the vblank handler.
+    ///
+    /// Returns `None` if a handler is already installed or the BIOS couldn't
+    /// open the event.
+    pub fn new() -> Option<Self> {
+        critical_section(|| {
+            // SAFETY: We're in a critical section so the handler can't run and
+            // its event is only enabled once it's installed.
+            unsafe {
+                if INSTALLED {
+                    return None
+                }
+                let handler = on_vblank as extern "C" fn() as *const u32;
+                let event =
+                    kernel::open_event(VBLANK_CLASS, INTERRUPT_SPEC, CALLBACK_MODE, handler);
+                if event == INVALID_EVENT {
+                    return None
+                }
+                VBLANKS = 0;
+                CALLBACKS = [None; MAX_CALLBACKS];
+                INSTALLED = true;
+                kernel::enable_timer_irq(VBLANK_TIMER);
+                kernel::enable_event(event);
+                Some(VSync { event })
+            }
+        })
+    }
+
+    /// Gets the number of vblanks since the handler was installed.
+    ///
+    /// This wraps after about 2 years at 60 Hz.
+    pub fn vblanks(&self) -> u32 {
+        vblanks()
+    }
+
+    /// Spins until the next vblank interrupt.
+    pub fn wait(&self) {
+        wait_vblank()
+    }
+
+    /// Registers a function to call on each vblank.
+    ///
+    /// Callbacks run in the interrupt handler so they should be short and must
+    /// not wait for other interrupts. Returns `None` if [`MAX_CALLBACKS`] are
+    /// already registered.
+    pub fn add_callback(&mut self, f: fn()) -> Option<Callback> {
+        critical_section(|| {
+            // SAFETY: We're in a critical section so the handler can't run.
+            let callbacks = unsafe { &mut *ptr::addr_of_mut!(CALLBACKS) };
+            let idx = callbacks.iter().position(Option::is_none)?;
+            callbacks[idx] = Some(f);
+            Some(Callback(idx))
+        })
+    }
+
+    /// Unregisters a vblank callback.
+    pub fn remove_callback(&mut self, callback: Callback) -> &mut Self {
+        critical_section(|| {
+            // SAFETY: We're in a critical section so the handler can't run.
+            unsafe { CALLBACKS[callback.0] = None };
+        });
+        self
+    }
+}
+
+impl Drop for VSync {
+    fn drop(&mut self) {
+        critical_section(|| {
+            // SAFETY: We're in a critical section so the handler can't run and
+            // it won't be called once its event is closed.
+            unsafe {
+                kernel::disable_event(self.event);
+                kernel::close_event(self.event);
+                INSTALLED = false;
+            }
+        })
+    }
+}
+
+/// Paces a game loop to a fixed number of vblanks per frame.
+#[derive(Debug)]
+pub struct FrameLimiter {
+    interval: u32,
+    last: u32,
+    delta: u32,
+}
+
+impl FrameLimiter {
+    /// Creates a frame limiter which runs one frame every `interval` vblanks.
+    ///
+    /// An `interval` of zero is treated as one.
+    pub fn new(vsync: &VSync, interval: u32) -> Self {
+        let interval = interval.max(1);
+        FrameLimiter {
+            interval,
+            last: vsync.vblanks(),
+            delta: interval,
+        }
+    }
+
+    /// Creates a frame limiter which runs at `fps` frames per second.
+    ///
+    /// This must evenly divide the refresh rate, so NTSC allows 60, 30 and 20
+    /// fps and PAL allows 50 and 25 fps among others. Returns `None` otherwise.
+    pub fn with_rate(vsync: &VSync, mode: VideoMode, fps: u32) -> Option<Self> {
+        let refresh_rate = match mode {
+            VideoMode::NTSC => 60,
+            VideoMode::PAL => 50,
+        };
+        if fps == 0 || refresh_rate % fps != 0 {
+            return None
+        }
+        Some(Self::new(vsync, refresh_rate / fps))
+    }
+
+    /// Gets the number of vblanks per frame.
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Gets the vblank count the next frame starts at given the current count.
+    ///
+    /// Frames which run long are pushed back to the next multiple of the
+    /// interval so that frames are always evenly paced.
+    fn target(&self, now: u32) -> u32 {
+        let elapsed = now.wrapping_sub(self.last);
+        let frames = elapsed.div_ceil(self.interval).max(1);
+        self.last.wrapping_add(frames * self.interval)
+    }
+
+    fn advance(&mut self, target: u32) {
+        self.delta = target.wrapping_sub(self.last);
+        self.last = target;
+    }
+
+    /// Spins until the start of the next frame, returning the number of
+    /// vblanks since the previous frame started.
+    pub fn wait(&mut self, vsync: &VSync) -> u32 {
+        let target = self.target(vsync.vblanks());
+        while (vsync.vblanks().wrapping_sub(target) as i32) < 0 {}
+        self.advance(target);
+        self.delta
+    }
+
+    /// Gets the number of vblanks between the start of the last two frames.
+    pub fn delta(&self) -> u32 {
+        self.delta
+    }
+
+    /// Gets the number of frames skipped before the last frame because the
+    /// previous one ran long.
+    pub fn dropped_frames(&self) -> u32 {
+        self.delta / self.interval - 1
+    }
+
+    /// Gets the time between the start of the last two frames in frames.
+    ///
+    /// This is `1.0` when the game loop keeps up and may be used to scale
+    /// movement in game logic when frames are dropped. Stalls of 128 frames or
+    /// more saturate to the largest `f16`; use [`delta`][FrameLimiter::delta]
+    /// to get the exact number of vblanks.
+    pub fn delta_time(&self) -> f16 {
+        let frames = ((self.delta as u64) << f16::FRAC) / self.interval as u64;
+        f16(frames.min(i16::MAX as u64) as i16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameLimiter;
+    use crate::math::f16;
+
+    fn limiter(interval: u32, last: u32) -> FrameLimiter {
+        FrameLimiter {
+            interval,
+            last,
+            delta: interval,
+        }
+    }
+
+    #[test_case]
+    fn on_time() {
+        let mut limiter = limiter(2, 10);
+        assert!(limiter.target(11) == 12);
+        assert!(limiter.target(12) == 12);
+        limiter.advance(12);
+        assert!(limiter.delta() == 2);
+        assert!(limiter.dropped_frames() == 0);
+        assert!(limiter.delta_time() == f16::ONE);
+    }
+
+    #[test_case]
+    fn dropped() {
+        let mut limiter = limiter(2, 10);
+        // A frame which took 3 vblanks waits until the next 30 fps boundary
+        limiter.advance(limiter.target(13));
+        assert!(limiter.delta() == 4);
+        assert!(limiter.dropped_frames() == 1);
+        assert!(limiter.delta_time() == f16::from_int(2));
+    }
+
+    #[test_case]
+    fn wrapping() {
+        let limiter = limiter(3, u32::MAX - 1);
+        assert!(limiter.target(0) == 1);
+        assert!(limiter.target(2) == 4);
+    }
+
+    #[test_case]
+    fn long_stall() {
+        let mut limiter = limiter(2, 10);
+        limiter.advance(limiter.target(10 + 2 * 128));
+        assert!(limiter.dropped_frames() == 127);
+        assert!(limiter.delta_time() == f16(i16::MAX));
+        limiter.advance(limiter.target(u32::MAX));
+        assert!(limiter.delta_time() == f16(i16::MAX));
+    }
+}
@@ -1,12 +1,23 @@
 #![cfg(test)]
 
+// Tests built for other hosts run under std and print to stdout
+#[cfg(not(target_arch = "mips"))]
+extern crate std;
+
+#[cfg(target_arch = "mips")]
 use crate::{print, println};
 use const_random::const_random;
 use core::any::type_name;
 use num::integer::gcd;
+#[cfg(not(target_arch = "mips"))]
+use std::{print, println};
 
+#[cfg(target_arch = "mips")]
 pub const MAX_TESTS: usize = 1_000;
 
+// The fuzz macros use the BIOS's random number generator so tests using them
+// only run on the console
+#[cfg(target_arch = "mips")]
 #[macro_export]
 macro_rules! fuzz {
     (|$($name:ident: $ty:ty),+| { $($body:tt)* }) => {
@@ -23,6 +34,7 @@ macro_rules! fuzz {
     };
 }
 
+#[cfg(target_arch = "mips")]
 #[macro_export]
 macro_rules! fuzz_data {
     (|$name:ident: &[$ty:ty]| { $($body:tt)* }) => {
@@ -61,6 +73,7 @@ fn index_params(n: usize) -> (usize, usize) {
     (a, b)
 }
 
+#[cfg(target_arch = "mips")]
 #[test_case]
 fn test_params() {
     fuzz!(|num_tests: usize| {
@@ -95,6 +108,7 @@ pub fn runner<const N: usize>(tests: &[&dyn Test; N]) {
     let ran_all_tests = executed_tests.iter().cloned().eq(0..N);
     assert!(ran_all_tests, "Test framework failed to run all tests!");
     println!("\ntest result: ok. {} passed; 0 failed\n", N);
+    #[cfg(target_arch = "mips")]
     loop {}
 }
 
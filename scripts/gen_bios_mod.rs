@@ -116,6 +116,7 @@ fn main() {
              //!\n\
              //! These are wrappers for calling BIOS functions directly.\n\
              // This file was automatically generated by gen_bios_mod.rs\n\n\
+             #[cfg(target_arch = \"mips\")]\n\
              core::arch::global_asm!(include_str!(\"trampoline.s\"));\n\n\
              extern \"C\" {{\n\
              {}\